@@ -88,13 +88,16 @@ pub trait DeviceWrapper {
     ) -> io::Result<()> {
         let (ev_type, ev_code) = event_code_to_int(ev_code);
 
-        let data = blob
-            .map(|data| {
-                data.downcast_ref::<AbsInfo>()
-                    .map(|absinfo| &absinfo.as_raw() as *const _ as *const c_void)
-                    .unwrap_or_else(|| data as *const _ as *const c_void)
-            })
-            .unwrap_or_else(|| ptr::null() as *const _ as *const c_void);
+        // the raw absinfo needs to outlive the call, a pointer to a temporary would dangle
+        let raw_absinfo = blob
+            .and_then(|data| data.downcast_ref::<AbsInfo>())
+            .map(|absinfo| absinfo.as_raw());
+
+        let data = match (&raw_absinfo, blob) {
+            (Some(absinfo), _) => absinfo as *const _ as *const c_void,
+            (None, Some(data)) => data as *const _ as *const c_void,
+            (None, None) => ptr::null() as *const _ as *const c_void,
+        };
 
         let result = unsafe {
             raw::libevdev_enable_event_code(
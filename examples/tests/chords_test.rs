@@ -0,0 +1,68 @@
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn chords_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/chords.m2";
+
+    let mut api = test_script(params).await?;
+    sleep(50);
+
+    // both keys within the chord window
+    api.write_action(KeyAction::new(*KEY_J, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_K, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_J, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_K, 0)).await?;
+    sleep(200);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_ESC, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_ESC, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    // the chord window expires
+    api.write_action(KeyAction::new(*KEY_K, 1)).await?;
+    sleep(200);
+    api.write_action(KeyAction::new(*KEY_J, 1)).await?;
+    sleep(200);
+    api.write_action(KeyAction::new(*KEY_K, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_J, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_K, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_J, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_K, 0).to_input_ev(),
+        KeyAction::new(*KEY_J, 0).to_input_ev(),
+    ]);
+
+    // a key that isn't part of the chord replays the held keys in order
+    api.write_action(KeyAction::new(*KEY_S, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_S, 1).to_input_ev(),
+        KeyAction::new(*KEY_A, 1).to_input_ev(),
+    ]);
+
+    api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_S, 0)).await?;
+
+    // chord with a code block
+    api.write_action(KeyAction::new(*KEY_S, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_F, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_D, 1)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_stdout().await, "chord!\n");
+
+    api.stop().await;
+
+    Ok(())
+}
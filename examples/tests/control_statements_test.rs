@@ -18,6 +18,10 @@ async fn control_statements_test() -> Result<()> {
     i is 1
     i is 2
     i is 4
+    j is 0
+    j is 1
+    j is 2
+    k is 16
     "};
     assert_eq!(&*output, expected);
 
@@ -0,0 +1,133 @@
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn control_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/layers.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(50);
+
+    assert_eq!(api.control(ControlCommand::ListMappings).await?, [
+        "[layer nav] h",
+        "[layer nav] j",
+        "[layer nav] k",
+        "[layer nav] l",
+        "f12",
+        "{capslock down}",
+        "{capslock up}",
+        "",
+    ].join("\n"));
+
+    // all events pass through untouched while paused
+    assert_eq!(api.control(ControlCommand::Pause).await?, "paused\n");
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_CAPSLOCK, 1).to_input_ev(),
+        KeyAction::new(*KEY_H, 1).to_input_ev(),
+        KeyAction::new(*KEY_H, 0).to_input_ev(),
+        KeyAction::new(*KEY_CAPSLOCK, 0).to_input_ev(),
+    ]);
+    assert!(api.control(ControlCommand::Status).await?.starts_with("state: paused\n"));
+
+    assert_eq!(api.control(ControlCommand::Resume).await?, "resumed\n");
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    sleep(50);
+
+    let status = api.control(ControlCommand::Status).await?;
+    assert!(status.starts_with("state: running\n"));
+    assert!(status.contains("active layers: nav\n"));
+
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 0)).await?;
+    sleep(50);
+
+    api.stop().await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn control_socket_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/hjkl-arrow-keys.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(50);
+
+    let socket_path = std::env::temp_dir().join(format!("map2-control-test-{}.sock", std::process::id()));
+    api.serve_control(&socket_path).await?;
+    assert!(socket_path.exists());
+
+    assert!(control::send_command(&socket_path, "status").await?.starts_with("state: running\n"));
+    assert!(control::send_command(&socket_path, "jump").await.unwrap_err().to_string()
+        .starts_with("unknown command 'jump'"));
+
+    // modifiers pressed while paused still count once resumed
+    assert_eq!(control::send_command(&socket_path, "pause").await?, "paused\n");
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 1)).await?;
+    sleep(50);
+    assert_eq!(control::send_command(&socket_path, "resume").await?, "resumed\n");
+    api.write_action(KeyAction::new(*KEY_H, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_LEFT_ALT, 1).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+        KeyAction::new(*KEY_LEFT, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT_ALT, 1).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+    ]);
+
+    // the response arrives even though the instance stops right away
+    assert_eq!(control::send_command(&socket_path, "exit").await?, "exiting\n");
+
+    control::remove_socket();
+    assert!(!socket_path.exists());
+
+    api.stop().await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn control_pause_pending_chord_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/chords.m2";
+
+    let mut api = test_script(params).await?;
+    sleep(50);
+
+    // the start of a chord is typed right away instead of completing the chord later on
+    api.write_action(KeyAction::new(*KEY_J, 1)).await?;
+    sleep(20);
+    assert_eq!(api.control(ControlCommand::Pause).await?, "paused\n");
+    api.write_action(KeyAction::new(*KEY_K, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_K, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_J, 0)).await?;
+    sleep(150);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_J, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_K, 1).to_input_ev(),
+        KeyAction::new(*KEY_K, 0).to_input_ev(),
+        KeyAction::new(*KEY_J, 0).to_input_ev(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
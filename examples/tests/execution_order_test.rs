@@ -0,0 +1,61 @@
+use evdev_rs::enums::EventType;
+
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn execution_order_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/execution-order.m2";
+
+    let mut api = test_script(params).await?;
+    sleep(50);
+
+    let key_x = Key::from_str(&EventType::EV_KEY, "KEY_X")?;
+    let key_e = Key::from_str(&EventType::EV_KEY, "KEY_E")?;
+    let key_r = Key::from_str(&EventType::EV_KEY, "KEY_R")?;
+
+    // blocks run in the order they were triggered, even when the keys are tapped quickly
+    for _ in 0..5 {
+        api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+        api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    }
+    sleep(100);
+
+    assert_eq!(api.collect_stdout().await, "a down\na up\n".repeat(5));
+    let tap_x = vec![
+        KeyAction::new(key_x, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(key_x, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ];
+    assert_eq!(api.collect_output_ev().await, (0..5).flat_map(|_| tap_x.clone()).collect::<Vec<_>>());
+
+    // other mappings run while a command runs, 'async' blocks right away
+    api.write_action(KeyAction::new(key_e, 1)).await?;
+    api.write_action(KeyAction::new(key_e, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_B, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_B, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_S, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_S, 0)).await?;
+    sleep(100);
+    let mut lines: Vec<String> = api.collect_stdout().await.lines().map(str::to_string).collect();
+    lines.sort();
+    assert_eq!(lines, vec!["async", "b"]);
+    sleep(250);
+    assert_eq!(api.collect_stdout().await, "slow\n");
+
+    // other mappings run while one sleeps
+    api.write_action(KeyAction::new(key_r, 1)).await?;
+    api.write_action(KeyAction::new(key_r, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_B, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_B, 0)).await?;
+    sleep(100);
+    assert_eq!(api.collect_stdout().await, "b\n");
+    sleep(200);
+    assert_eq!(api.collect_stdout().await, "reminder\n");
+
+    api.stop().await;
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use evdev_rs::enums::{EV_ABS, EventType};
+
+use crate::*;
+use crate::tests::*;
+
+fn abs(code: EV_ABS, value: i32) -> KeyAction {
+    KeyAction::new(Key { event_code: EventCode::EV_ABS(code) }, value)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn gamepad_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/gamepad.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(50);
+
+    let key_right = Key::from_str(&EventType::EV_KEY, "KEY_RIGHT")?;
+
+    // pushing the stick past the threshold holds the key until it crosses back
+    api.write_action(abs(EV_ABS::ABS_X, 100)).await?;
+    api.write_action(abs(EV_ABS::ABS_X, 250)).await?;
+    api.write_action(abs(EV_ABS::ABS_X, 300)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(key_right, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    api.write_action(abs(EV_ABS::ABS_X, 10)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(key_right, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    // unmapped axes pass through
+    api.write_action(abs(EV_ABS::ABS_RX, 42)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        abs(EV_ABS::ABS_RX, 42).to_input_ev(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
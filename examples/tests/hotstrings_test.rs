@@ -0,0 +1,121 @@
+use evdev_rs::enums::EventType;
+
+use crate::*;
+use crate::tests::*;
+
+fn click(key: Key) -> Vec<InputEvent> {
+    vec![
+        KeyAction::new(key, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(key, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]
+}
+
+fn typed(keys: &[Key]) -> Vec<InputEvent> {
+    keys.iter().flat_map(|key| vec![KeyAction::new(*key, 1).to_input_ev(), KeyAction::new(*key, 0).to_input_ev()]).collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn hotstrings_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/hotstrings.m2";
+
+    let mut api = test_script(params).await?;
+    sleep(50);
+
+    let key_dot = Key::from_str(&EventType::EV_KEY, "KEY_DOT")?;
+    let by_the_way = [*KEY_B, *KEY_Y, *KEY_SPACE, *KEY_T, *KEY_H, *KEY_E, *KEY_SPACE, *KEY_W, *KEY_A, *KEY_Y];
+
+    // the abbreviation is replaced once a space is typed
+    for ev in typed(&[*KEY_B, *KEY_T, *KEY_W]) { api.write_event(ev).await?; }
+    api.write_action(KeyAction::new(*KEY_SPACE, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_SPACE, 0)).await?;
+    sleep(50);
+
+    let mut expected = typed(&[*KEY_B, *KEY_T, *KEY_W]);
+    for _ in 0..3 { expected.append(&mut click(*KEY_BACKSPACE)); }
+    for key in by_the_way.iter() { expected.append(&mut click(*key)); }
+    expected.push(KeyAction::new(*KEY_SPACE, 1).to_input_ev());
+    expected.push(SYN_REPORT.clone());
+    expected.push(KeyAction::new(*KEY_SPACE, 0).to_input_ev());
+
+    assert_eq!(api.collect_output_ev().await, expected);
+
+    // abbreviations in the middle of a word are ignored
+    for ev in typed(&[*KEY_A, *KEY_B, *KEY_T, *KEY_W, *KEY_SPACE]) { api.write_event(ev).await?; }
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, typed(&[*KEY_A, *KEY_B, *KEY_T, *KEY_W, *KEY_SPACE]));
+
+    // immediate hotstrings fire on the last character, which never reaches the output
+    for ev in typed(&[*KEY_O, *KEY_M, *KEY_W]) { api.write_event(ev).await?; }
+    sleep(50);
+
+    let mut expected = typed(&[*KEY_O, *KEY_M]);
+    for _ in 0..2 { expected.append(&mut click(*KEY_BACKSPACE)); }
+    for key in [*KEY_O, *KEY_N, *KEY_SPACE, *KEY_M, *KEY_Y, *KEY_SPACE, *KEY_W, *KEY_A, *KEY_Y].iter() {
+        expected.append(&mut click(*key));
+    }
+
+    assert_eq!(api.collect_output_ev().await, expected);
+
+    // the replacement follows the case of the typed abbreviation
+    api.write_action(KeyAction::new(*KEY_SPACE, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_SPACE, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_LEFT_SHIFT, 1)).await?;
+    for ev in typed(&[*KEY_B]) { api.write_event(ev).await?; }
+    api.write_action(KeyAction::new(*KEY_LEFT_SHIFT, 0)).await?;
+    for ev in typed(&[*KEY_T, *KEY_W]) { api.write_event(ev).await?; }
+    api.write_action(KeyAction::new(key_dot, 1)).await?;
+    sleep(50);
+
+    let mut expected = typed(&[*KEY_SPACE]);
+    expected.push(KeyAction::new(*KEY_LEFT_SHIFT, 1).to_input_ev());
+    expected.append(&mut typed(&[*KEY_B]));
+    expected.push(KeyAction::new(*KEY_LEFT_SHIFT, 0).to_input_ev());
+    expected.append(&mut typed(&[*KEY_T, *KEY_W]));
+    for _ in 0..3 { expected.append(&mut click(*KEY_BACKSPACE)); }
+    expected.push(KeyAction::new(*KEY_LEFT_SHIFT, 1).to_input_ev());
+    expected.push(SYN_REPORT.clone());
+    expected.append(&mut click(*KEY_B));
+    expected.push(KeyAction::new(*KEY_LEFT_SHIFT, 0).to_input_ev());
+    expected.push(SYN_REPORT.clone());
+    for key in by_the_way[1..].iter() { expected.append(&mut click(*key)); }
+    expected.push(KeyAction::new(key_dot, 1).to_input_ev());
+    expected.push(SYN_REPORT.clone());
+
+    assert_eq!(api.collect_output_ev().await, expected);
+
+    // end characters that need 'shift' don't change the case of the replacement
+    let key_slash = Key::from_str(&EventType::EV_KEY, "KEY_SLASH")?;
+    api.write_action(KeyAction::new(key_dot, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_SPACE, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_SPACE, 0)).await?;
+    for ev in typed(&[*KEY_B, *KEY_T, *KEY_W]) { api.write_event(ev).await?; }
+    api.write_action(KeyAction::new(*KEY_LEFT_SHIFT, 1)).await?;
+    api.write_action(KeyAction::new(key_slash, 1)).await?;
+    api.write_action(KeyAction::new(key_slash, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_LEFT_SHIFT, 0)).await?;
+    sleep(50);
+
+    let mut expected = vec![KeyAction::new(key_dot, 0).to_input_ev()];
+    expected.append(&mut typed(&[*KEY_SPACE, *KEY_B, *KEY_T, *KEY_W]));
+    expected.push(KeyAction::new(*KEY_LEFT_SHIFT, 1).to_input_ev());
+    expected.push(KeyAction::new(*KEY_LEFT_SHIFT, 0).to_input_ev());
+    expected.push(SYN_REPORT.clone());
+    for _ in 0..3 { expected.append(&mut click(*KEY_BACKSPACE)); }
+    for key in by_the_way.iter() { expected.append(&mut click(*key)); }
+    expected.push(KeyAction::new(*KEY_LEFT_SHIFT, 1).to_input_ev());
+    expected.push(SYN_REPORT.clone());
+    expected.push(KeyAction::new(key_slash, 1).to_input_ev());
+    expected.push(SYN_REPORT.clone());
+    expected.push(KeyAction::new(key_slash, 0).to_input_ev());
+    expected.push(KeyAction::new(*KEY_LEFT_SHIFT, 0).to_input_ev());
+
+    assert_eq!(api.collect_output_ev().await, expected);
+
+    api.stop().await;
+
+    Ok(())
+}
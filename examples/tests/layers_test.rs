@@ -0,0 +1,86 @@
+use evdev_rs::enums::EventType;
+
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn layers_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/layers.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(50);
+
+    let key_f12 = Key::from_str(&EventType::EV_KEY, "KEY_F12")?;
+
+    // the layer is not active
+    api.write_action(KeyAction::new(*KEY_H, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_H, 1).to_input_ev(),
+        KeyAction::new(*KEY_H, 0).to_input_ev(),
+    ]);
+
+    // the layer is active while 'capslock' is held
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_H, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 0)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_LEFT, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    // releasing a key after the layer got deactivated still resolves through the layer
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_J, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 0)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_J, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_DOWN, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_DOWN, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    // toggling the layer
+    api.write_action(KeyAction::new(key_f12, 1)).await?;
+    api.write_action(KeyAction::new(key_f12, 0)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_L, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_L, 0)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(key_f12, 1)).await?;
+    api.write_action(KeyAction::new(key_f12, 0)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_L, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_L, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_stdout().await, "nav on\nnav off\n");
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_RIGHT, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_RIGHT, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_L, 1).to_input_ev(),
+        KeyAction::new(*KEY_L, 0).to_input_ev(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
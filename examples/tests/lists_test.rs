@@ -0,0 +1,81 @@
+use crate::*;
+use crate::tests::*;
+use indoc::indoc;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn lists_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/lists.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(200);
+
+    let output = api.collect_stdout().await;
+
+    let expected = indoc! {"
+    [\"apple\", \"blueberry\", \"cherry\"]
+    the first fruit is apple
+    there are 3 fruits
+    cherry is in the list
+    removed cherry
+    apple
+    blueberry
+    "};
+    assert_eq!(&*output, expected);
+
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_K, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_K, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 0)).await?;
+    sleep(50);
+
+    let output_ev = api.collect_output_ev().await;
+
+    assert_eq!(output_ev, vec![
+        KeyAction::new(*KEY_LEFT_ALT, 1).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+        KeyAction::new(*KEY_UP, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_UP, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn list_values_test() {
+    assert_eq!(eval_inputs(&[
+        "let xs = [1, \"a\"];",
+        // lists are shared by reference
+        "let ys = xs; push(ys, [true]); xs",
+        "xs == [1, \"a\", [true]]",
+        "xs == [1, \"a\"]",
+        "pop(xs); len(ys)",
+        // a list containing itself is printed and compared without looping forever
+        "push(xs, xs); xs",
+        "xs == xs",
+        "let zs = [1, \"a\"]; push(zs, zs); xs == zs",
+        "xs == [1, \"a\", [1, \"a\"]]",
+        "contains(xs, xs)",
+        "let a = []; let b = []; push(a, b); push(b, a); a",
+        "a == b",
+    ]).await, vec![
+        "",
+        "[1, \"a\", [true]]",
+        "true",
+        "false",
+        "2",
+        "[1, \"a\", [...]]",
+        "true",
+        "true",
+        "false",
+        "true",
+        "[[[...]]]",
+        "true",
+    ]);
+}
@@ -0,0 +1,60 @@
+use crate::*;
+use crate::tests::*;
+use indoc::indoc;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn maps_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/maps.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(200);
+
+    let output = api.collect_stdout().await;
+
+    let expected = indoc! {"
+    {\"code\": \"editor\", \"firefox\": \"browser\", \"kitty\": \"fast terminal\"}
+    kitty is a fast terminal
+    gimp is unknown
+    [\"code\", \"firefox\", \"kitty\"]
+    [\"editor\", \"browser\", \"fast terminal\"]
+    code: editor
+    firefox: browser
+    kitty: fast terminal
+    "};
+    assert_eq!(&*output, expected);
+
+    api.stop().await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn map_values_test() {
+    assert_eq!(eval_inputs(&[
+        "let m = {\"b\": 1, \"a\": [2]};",
+        // maps are shared by reference and keep their keys sorted
+        "let n = m; n[\"c\"] = \"x\"; m",
+        "let p = {\"a\": [2], \"b\": 1, \"c\": \"x\"}; m == p",
+        "p[\"a\"] = [3]; m == p",
+        "m[\"d\"]",
+        "has(m, \"c\")",
+        // a map containing itself is printed and compared without looping forever
+        "m[\"self\"] = m; m",
+        "m == m",
+        "let o = {\"a\": [2], \"b\": 1, \"c\": \"x\"}; o[\"self\"] = o; m == o",
+        "[m] == [m]",
+    ]).await, vec![
+        "",
+        "{\"a\": [2], \"b\": 1, \"c\": \"x\"}",
+        "true",
+        "false",
+        "",
+        "true",
+        "{\"a\": [2], \"b\": 1, \"c\": \"x\", \"self\": {...}}",
+        "true",
+        "true",
+        "true",
+    ]);
+}
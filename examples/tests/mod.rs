@@ -1,4 +1,20 @@
 mod functions_test;
 mod math_test;
 mod hjkl_arrow_keys_test;
-mod control_statements_test;
\ No newline at end of file
+mod control_statements_test;
+mod tap_hold_test;
+mod chords_test;
+mod sequences_test;
+mod layers_test;
+mod mouse_wheel_test;
+mod mouse_keys_test;
+mod gamepad_test;
+mod per_device_test;
+mod hotstrings_test;
+mod script_reload_test;
+mod control_test;
+mod lists_test;
+mod maps_test;
+mod runtime_errors_test;
+mod timers_test;
+mod execution_order_test;
@@ -0,0 +1,74 @@
+use evdev_rs::enums::{EV_REL, EventType};
+
+use crate::*;
+use crate::tests::*;
+
+fn rel_values(events: &[InputEvent], code: EV_REL) -> Vec<i32> {
+    events.iter().filter(|ev| ev.event_code == EventCode::EV_REL(code)).map(|ev| ev.value).collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn mouse_keys_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/mouse-keys.m2";
+
+    let mut api = test_script(params).await?;
+    sleep(50);
+
+    let btn_left = Key::from_str(&EventType::EV_KEY, "BTN_LEFT")?;
+
+    // mouse keys only work while the layer is active
+    api.write_action(KeyAction::new(*KEY_L, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_L, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_L, 1).to_input_ev(),
+        KeyAction::new(*KEY_L, 0).to_input_ev(),
+    ]);
+
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    sleep(50);
+
+    // holding a key moves the pointer until it's released
+    api.write_action(KeyAction::new(*KEY_L, 1)).await?;
+    sleep(200);
+    api.write_action(KeyAction::new(*KEY_L, 0)).await?;
+    sleep(50);
+
+    let output_ev = api.collect_output_ev().await;
+    let dx = rel_values(&output_ev, EV_REL::REL_X);
+    assert!(dx.len() > 5);
+    assert!(dx.iter().all(|dx| *dx > 0));
+    assert!(rel_values(&output_ev, EV_REL::REL_Y).is_empty());
+    assert!(dx.last().unwrap() > dx.first().unwrap());
+
+    sleep(50);
+    assert_eq!(api.collect_output_ev().await, vec![]);
+
+    // scrolling
+    api.write_action(KeyAction::new(*KEY_U, 1)).await?;
+    sleep(200);
+    api.write_action(KeyAction::new(*KEY_U, 0)).await?;
+    sleep(50);
+
+    let output_ev = api.collect_output_ev().await;
+    assert!(rel_values(&output_ev, EV_REL::REL_WHEEL_HI_RES).iter().all(|v| *v > 0));
+    assert!(rel_values(&output_ev, EV_REL::REL_WHEEL).iter().sum::<i32>() >= 2);
+
+    // clicking
+    api.write_action(KeyAction::new(*KEY_SPACE, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_SPACE, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(btn_left, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(btn_left, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use evdev_rs::enums::{EV_REL, EventType};
+
+use crate::*;
+use crate::tests::*;
+
+fn rel(code: EV_REL, value: i32) -> KeyAction {
+    KeyAction::new(Key { event_code: EventCode::EV_REL(code) }, value)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn mouse_wheel_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/mouse-wheel.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(50);
+
+    let key_volumeup = Key::from_str(&EventType::EV_KEY, "KEY_VOLUMEUP")?;
+    let key_f1 = Key::from_str(&EventType::EV_KEY, "KEY_F1")?;
+    let key_f3 = Key::from_str(&EventType::EV_KEY, "KEY_F3")?;
+
+    // a mapped wheel notch, the accompanying hi-res event is dropped
+    api.write_action(rel(EV_REL::REL_WHEEL_HI_RES, 120)).await?;
+    api.write_action(rel(EV_REL::REL_WHEEL, 1)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(key_volumeup, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(key_volumeup, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    // unmapped relative events pass through
+    api.write_action(rel(EV_REL::REL_X, 5)).await?;
+    api.write_action(rel(EV_REL::REL_HWHEEL, 1)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        rel(EV_REL::REL_X, 5).to_input_ev(),
+        rel(EV_REL::REL_HWHEEL, 1).to_input_ev(),
+    ]);
+
+    // emitting relative events
+    api.write_action(KeyAction::new(key_f1, 1)).await?;
+    api.write_action(KeyAction::new(key_f1, 0)).await?;
+    api.write_action(KeyAction::new(key_f3, 1)).await?;
+    api.write_action(KeyAction::new(key_f3, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        rel(EV_REL::REL_WHEEL_HI_RES, 360).to_input_ev(),
+        rel(EV_REL::REL_WHEEL, 3).to_input_ev(),
+        SYN_REPORT.clone(),
+        rel(EV_REL::REL_X, 10).to_input_ev(),
+        rel(EV_REL::REL_Y, -5).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use evdev_rs::enums::EventType;
+
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn per_device_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/per-device.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(50);
+
+    let key_f1 = Key::from_str(&EventType::EV_KEY, "KEY_F1")?;
+
+    let macro_pad = Arc::new(DeviceInfo {
+        path: "/dev/input/event7".to_string(),
+        name: "Macro Pad".to_string(),
+        vendor: 0x1209,
+        product: 0xa1e5,
+    });
+    let keyboard = Arc::new(DeviceInfo {
+        path: "/dev/input/event3".to_string(),
+        name: "AT Translated Set 2 keyboard".to_string(),
+        vendor: 0x0001,
+        product: 0x0001,
+    });
+
+    // mappings of the device block only apply to the macro pad
+    api.device = Some(macro_pad.clone());
+    api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_B, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_B, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    api.device = Some(keyboard.clone());
+    api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_A, 1).to_input_ev(),
+        KeyAction::new(*KEY_A, 0).to_input_ev(),
+    ]);
+
+    // device mappings take precedence over global ones
+    api.device = Some(macro_pad.clone());
+    api.write_action(KeyAction::new(key_f1, 1)).await?;
+    api.write_action(KeyAction::new(key_f1, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_stdout().await, "macro pad 1209:a1e5\n");
+
+    api.device = Some(keyboard.clone());
+    api.write_action(KeyAction::new(key_f1, 1)).await?;
+    api.write_action(KeyAction::new(key_f1, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_stdout().await, "AT Translated Set 2 keyboard\n");
+
+    api.stop().await;
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use crate::*;
+use crate::tests::*;
+use indoc::indoc;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn runtime_errors_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/runtime-errors.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_D, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_D, 0)).await?;
+    sleep(50);
+
+    let expected = indoc! {"
+    error: at line 6:
+      return 10 / amount;
+                ^
+    division by zero
+    in function 'step' called at line 10:
+    !d::{ print(step(0)); };
+                ^
+    "};
+    assert_eq!(&*api.collect_stderr().await, expected);
+
+    // the script is still running
+    api.write_action(KeyAction::new(*KEY_S, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_S, 0)).await?;
+    sleep(50);
+
+    assert_eq!(&*api.collect_stdout().await, "5\n");
+
+    api.write_action(KeyAction::new(*KEY_I, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_I, 0)).await?;
+    sleep(50);
+
+    let expected = indoc! {"
+    error: at line 15:
+    !i::{ let fruits = [\"apple\"]; print(\"first: \" + fruits[0] + \", second: \" + fruits[1]); };
+                                                                                     ^
+    index 1 is out of bounds for a list of length 1
+    "};
+    assert_eq!(&*api.collect_stderr().await, expected);
+
+    api.write_action(KeyAction::new(*KEY_P, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_P, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 0)).await?;
+    sleep(50);
+
+    let expected = indoc! {"
+    error: at line 18:
+    !p::{ prnt(\"hello\"); };
+          ^
+    function 'prnt' not found in this scope, did you mean 'print'?
+    "};
+    assert_eq!(&*api.collect_stderr().await, expected);
+
+    api.stop().await;
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use evdev_rs::enums::EventType;
+
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn script_reload_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/hjkl-arrow-keys.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(50);
+
+    let key_f12 = Key::from_str(&EventType::EV_KEY, "KEY_F12")?;
+
+    // the old script keeps running while keys are held
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 1)).await?;
+    sleep(50);
+    api.change_script("examples/layers.m2").await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_H, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_LEFT_ALT, 1).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+        KeyAction::new(*KEY_LEFT, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT_ALT, 1).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+    ]);
+
+    // the new script replaces all mappings of the old one once the keys are released
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_LEFT_ALT, 1).to_input_ev(),
+        KeyAction::new(*KEY_H, 1).to_input_ev(),
+        KeyAction::new(*KEY_H, 0).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+    ]);
+
+    api.write_action(KeyAction::new(key_f12, 1)).await?;
+    api.write_action(KeyAction::new(key_f12, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_stdout().await, "nav on\n");
+
+    // a key that never gets released only delays the reload until the timeout passed
+    let key_f1 = Key::from_str(&EventType::EV_KEY, "KEY_F1")?;
+    api.write_action(KeyAction::new(key_f1, 1)).await?;
+    sleep(50);
+    api.change_script("examples/hjkl-arrow-keys.m2").await?;
+    sleep(script::RELOAD_TIMEOUT.as_millis() as u64 + 50);
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_H, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_LEFT_ALT, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(key_f1, 1).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 1).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+        KeyAction::new(*KEY_LEFT, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT_ALT, 1).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_ALT, 0).to_input_ev(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn script_reload_pending_sequence_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/sequences.m2";
+
+    let mut api = test_script(params).await?;
+    sleep(50);
+
+    // no key is held, but the typed start of a sequence is still buffered
+    api.write_action(KeyAction::new(*KEY_G, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_G, 0)).await?;
+    sleep(50);
+    api.change_script("examples/hjkl-arrow-keys.m2").await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_G, 1).to_input_ev(),
+        KeyAction::new(*KEY_G, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use evdev_rs::enums::EventType;
+
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sequences_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/sequences.m2";
+
+    let mut api = test_script(params).await?;
+    sleep(50);
+
+    let key_f13 = Key::from_str(&EventType::EV_KEY, "KEY_F13")?;
+    let key_home = Key::from_str(&EventType::EV_KEY, "KEY_HOME")?;
+
+    // a sequence with a code block
+    api.write_action(KeyAction::new(key_f13, 1)).await?;
+    api.write_action(KeyAction::new(key_f13, 0)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_G, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_G, 0)).await?;
+    sleep(50);
+    api.write_action(KeyAction::new(*KEY_C, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_C, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_stdout().await, "{f13} -> g\n{f13},g -> c\nsequence!\n");
+    assert_eq!(api.collect_output_ev().await, vec![]);
+
+    // a sequence mapped to a key
+    api.write_action(KeyAction::new(*KEY_G, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_G, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_G, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_G, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_stdout().await, "g -> g\n");
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(key_home, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(key_home, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    // a key that doesn't continue the sequence replays the typed keys
+    api.write_action(KeyAction::new(*KEY_G, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_G, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    sleep(50);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_G, 1).to_input_ev(),
+        KeyAction::new(*KEY_G, 0).to_input_ev(),
+        KeyAction::new(*KEY_A, 1).to_input_ev(),
+        KeyAction::new(*KEY_A, 0).to_input_ev(),
+    ]);
+
+    // the sequence timeout expires
+    api.write_action(KeyAction::new(*KEY_G, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_G, 0)).await?;
+    sleep(400);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_G, 1).to_input_ev(),
+        KeyAction::new(*KEY_G, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tap_hold_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/tap-hold.m2";
+
+    let mut api = test_script(params).await?;
+    sleep(50);
+
+    // tap
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 0)).await?;
+    sleep(100);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_ESC, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_ESC, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    // another key gets pressed and released while holding
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 0)).await?;
+    sleep(100);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_LEFT_CTRL, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_A, 1).to_input_ev(),
+        KeyAction::new(*KEY_A, 0).to_input_ev(),
+        KeyAction::new(*KEY_LEFT_CTRL, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    // fast roll, the other key gets released after the tap-hold key
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    sleep(100);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_ESC, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_ESC, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_A, 1).to_input_ev(),
+        KeyAction::new(*KEY_A, 0).to_input_ev(),
+    ]);
+
+    // held longer than the tapping term
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 1)).await?;
+    sleep(400);
+    api.write_action(KeyAction::new(*KEY_CAPSLOCK, 0)).await?;
+    sleep(100);
+
+    assert_eq!(api.collect_output_ev().await, vec![
+        KeyAction::new(*KEY_LEFT_CTRL, 1).to_input_ev(),
+        SYN_REPORT.clone(),
+        KeyAction::new(*KEY_LEFT_CTRL, 0).to_input_ev(),
+        SYN_REPORT.clone(),
+    ]);
+
+    api.stop().await;
+
+    Ok(())
+}
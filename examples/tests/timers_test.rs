@@ -0,0 +1,58 @@
+use crate::*;
+use crate::tests::*;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn timers_test() -> Result<()> {
+    let mut params = ScriptTestingParameters::default();
+    params.script_path = "examples/timers.m2";
+
+    let mut api = test_script(params).await?;
+    api.event_delay = Some(20);
+    sleep(50);
+
+    // the timeout fires once after its delay
+    api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    sleep(50);
+    assert_eq!(api.collect_stdout().await, "");
+    sleep(150);
+    assert_eq!(api.collect_stdout().await, "reminder\n");
+    sleep(150);
+    assert_eq!(api.collect_stdout().await, "");
+
+    // a cleared timeout never fires
+    api.write_action(KeyAction::new(*KEY_A, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_A, 0)).await?;
+    api.write_action(KeyAction::new(*KEY_B, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_B, 0)).await?;
+    sleep(250);
+    assert_eq!(api.collect_stdout().await, "");
+
+    // the interval repeats until it's cleared
+    api.write_action(KeyAction::new(*KEY_C, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_C, 0)).await?;
+    sleep(230);
+    api.write_action(KeyAction::new(*KEY_C, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_C, 0)).await?;
+    let ticks = api.collect_stdout().await.matches("tick\n").count();
+    assert!(ticks >= 3 && ticks <= 5, "{} ticks", ticks);
+    sleep(150);
+    assert!(api.collect_stdout().await.matches("tick\n").count() <= 1);
+    sleep(150);
+    assert_eq!(api.collect_stdout().await, "");
+
+    // reloading the script clears all timers
+    api.write_action(KeyAction::new(*KEY_C, 1)).await?;
+    api.write_action(KeyAction::new(*KEY_C, 0)).await?;
+    sleep(50);
+    api.change_script("examples/timers.m2").await?;
+    sleep(100);
+    api.reset_stdout().await;
+    sleep(150);
+    assert_eq!(api.collect_stdout().await, "");
+
+    assert_eq!(api.collect_output_ev().await, vec![]);
+    api.stop().await;
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use crate::*;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum AbsComparison {
+    Greater,
+    Less,
+}
+
+/// An absolute axis crossing a threshold, i.e. '{abs_x > 200}'.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct AbsTrigger {
+    pub axis: EventCode,
+    pub comparison: AbsComparison,
+    pub threshold: i32,
+}
+
+impl AbsTrigger {
+    fn is_satisfied(&self, value: i32) -> bool {
+        match self.comparison {
+            AbsComparison::Greater => value > self.threshold,
+            AbsComparison::Less => value < self.threshold,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbsMapping {
+    pub trigger: AbsTrigger,
+    /// runs when the axis crosses the threshold
+    pub enter: Block,
+    /// runs when the axis crosses back
+    pub leave: Block,
+}
+
+struct AbsMappingEntry {
+    layer: Option<String>,
+    device: Option<String>,
+    trigger: AbsTrigger,
+    enter: Arc<(Block, GuardedVarMap)>,
+    leave: Arc<(Block, GuardedVarMap)>,
+    active: bool,
+}
+
+pub struct AbsState {
+    mappings: Vec<AbsMappingEntry>,
+    /// scopes of device blocks in the order they were defined, the first matching one takes precedence
+    device_scopes: Vec<MappingScope>,
+}
+
+impl AbsState {
+    pub fn new() -> Self {
+        AbsState { mappings: vec![], device_scopes: vec![] }
+    }
+
+    pub fn insert(&mut self, layer: Option<String>, device: Option<String>, mapping: AbsMapping, var_map: GuardedVarMap) {
+        let scope = (layer.clone(), device.clone());
+        if scope.1.is_some() && !self.device_scopes.contains(&scope) { self.device_scopes.push(scope); }
+
+        self.mappings.retain(|entry| entry.layer != layer || entry.device != device || entry.trigger != mapping.trigger);
+        self.mappings.push(AbsMappingEntry {
+            layer,
+            device,
+            trigger: mapping.trigger,
+            enter: Arc::new((mapping.enter, var_map.clone())),
+            leave: Arc::new((mapping.leave, var_map)),
+            active: false,
+        });
+    }
+
+    /// Returns the blocks of all crossed thresholds and whether the event should still be forwarded, which is
+    /// the case if the axis isn't mapped in any active layer or for the device the event came from.
+    ///
+    /// Within a layer, the first matching device block that maps the axis takes precedence over the global
+    /// mappings and later device blocks.
+    pub fn process(&mut self, ev: &InputEvent, device: Option<&DeviceInfo>, mappings: &CompiledKeyMappings) -> (Vec<Arc<(Block, GuardedVarMap)>>, bool) {
+        let mut blocks = vec![];
+        let mut is_mapped = false;
+
+        let device_matches = |pattern: &String| device.map(|device| device.matches(pattern)).unwrap_or(false);
+
+        let mut preferred: Vec<MappingScope> = vec![];
+        for scope in &self.device_scopes {
+            if preferred.iter().any(|(layer, _)| *layer == scope.0) { continue; }
+            if !scope.1.as_ref().map(device_matches).unwrap_or(false) { continue; }
+
+            let maps_axis = self.mappings.iter()
+                .any(|entry| entry.trigger.axis == ev.event_code && entry.layer == scope.0 && entry.device == scope.1);
+            if maps_axis { preferred.push(scope.clone()); }
+        }
+
+        for entry in self.mappings.iter_mut().filter(|entry| entry.trigger.axis == ev.event_code) {
+            // events of other devices don't affect the state of device specific mappings
+            if let Some(pattern) = &entry.device {
+                if !device_matches(pattern) { continue; }
+            }
+
+            let is_preferred = match preferred.iter().find(|(layer, _)| *layer == entry.layer) {
+                Some((_, pattern)) => *pattern == entry.device,
+                None => entry.device.is_none(),
+            };
+
+            let is_layer_active = match &entry.layer {
+                Some(layer) => mappings.is_layer_active(layer),
+                None => true,
+            };
+            is_mapped |= is_layer_active;
+
+            // leave blocks still run after the layer got deactivated so that held keys get released
+            let is_satisfied = is_layer_active && is_preferred && entry.trigger.is_satisfied(ev.value);
+            if is_satisfied && !entry.active {
+                entry.active = true;
+                blocks.push(entry.enter.clone());
+            } else if !is_satisfied && entry.active {
+                entry.active = false;
+                blocks.push(entry.leave.clone());
+            }
+        }
+
+        (blocks, !is_mapped)
+    }
+}
+
+impl Default for AbsState {
+    fn default() -> Self { Self::new() }
+}
@@ -3,6 +3,7 @@ use crate::*;
 impl Block {
     pub(crate) fn push_expr(&mut self, expr: Expr) -> &mut Self {
         self.statements.push(Stmt::Expr(expr));
+        self.spans.push(None);
         self
     }
 }
@@ -11,6 +12,7 @@ impl Expr {
 
     pub(crate) fn map_key_click_block(from: KeyClickActionWithMods, mut to: Block) -> Self {
         to.statements.insert(0, Stmt::Expr(Expr::ReleaseRestoreModifiers(from.modifiers.clone(), KeyModifierFlags::new(), TYPE_UP)));
+        to.spans.insert(0, None);
         Expr::KeyMapping(vec![
             KeyMapping { from: KeyActionWithMods::new(from.key, TYPE_DOWN, from.modifiers), to },
             KeyMapping { from: KeyActionWithMods::new(from.key, TYPE_REPEAT, from.modifiers), to: Block::new() }, // stub
@@ -20,6 +22,7 @@ impl Expr {
 
     pub(crate) fn map_key_block(from: KeyActionWithMods, mut to: Block) -> Self {
         to.statements.insert(0, Stmt::Expr(Expr::ReleaseRestoreModifiers(from.modifiers.clone(), KeyModifierFlags::new(), TYPE_UP)));
+        to.spans.insert(0, None);
 
         Expr::KeyMapping(vec![KeyMapping { from, to }])
     }
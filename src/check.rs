@@ -0,0 +1,388 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::Path;
+
+use crate::*;
+use crate::state::format_trigger;
+use crate::suggestion::closest_match;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A mistake found in a script without running it.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// the statement the mistake was found in
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        match &self.span {
+            Some(span) => {
+                writeln!(f, "{}: at line {}:", severity, span.line)?;
+                span.write_snippet(f)?;
+                write!(f, "\n{}", self.message)
+            }
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+struct Scope {
+    /// the variables that are defined at the current statement
+    defined: HashSet<String>,
+    /// all variables the block defines, code that runs later can use variables that are defined after it
+    declared: HashSet<String>,
+    /// the block is the body of a function or a mapping and runs after the surrounding block
+    deferred: bool,
+    /// the key mappings the block defines along with their location
+    mappings: HashMap<KeyActionWithMods, Option<Span>>,
+}
+
+struct Checker {
+    scopes: Vec<Scope>,
+    /// the statement that is being checked
+    span: Option<Span>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn report(&mut self, severity: Severity, message: String) {
+        self.diagnostics.push(Diagnostic { severity, message, span: self.span.clone() });
+    }
+
+    /// The variables that can be used at the current statement.
+    fn visible_names(&self) -> Vec<&str> {
+        let mut names = vec![];
+        let mut deferred = false;
+
+        for scope in self.scopes.iter().rev() {
+            let scope_names = if deferred { &scope.declared } else { &scope.defined };
+            names.extend(scope_names.iter().map(|name| name.as_str()));
+            deferred |= scope.deferred;
+        }
+        names
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.visible_names().contains(&name)
+    }
+
+    fn check_block(&mut self, block: &Block, deferred: bool, params: &[String]) {
+        let declared = block.statements.iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::Expr(Expr::Init(name, _)) | Stmt::For(Expr::Init(name, _), _, _, _) => Some(name.clone()),
+                _ => None,
+            })
+            .chain(params.iter().cloned())
+            .collect();
+        self.scopes.push(Scope { defined: params.iter().cloned().collect(), declared, deferred, mappings: HashMap::new() });
+
+        // statements the parser generates don't have a location, they belong to the surrounding statement
+        let outer_span = self.span.clone();
+        let mut exited_at = None;
+
+        for (idx, stmt) in block.statements.iter().enumerate() {
+            self.span = block.spans.get(idx).cloned().flatten().or_else(|| outer_span.clone());
+
+            if let Some(exit) = exited_at.take() {
+                self.report(Severity::Warning, format!("unreachable code after '{}'", exit));
+                break;
+            }
+
+            self.check_stmt(stmt);
+
+            exited_at = match stmt {
+                Stmt::Return(_) => Some("return"),
+                Stmt::Break => Some("break"),
+                Stmt::Continue => Some("continue"),
+                _ => None,
+            };
+        }
+
+        self.span = outer_span;
+        self.scopes.pop();
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expr) | Stmt::Return(expr) => self.check_expr(expr),
+            Stmt::Block(block) |
+            Stmt::Loop(block) |
+            Stmt::Layer(_, block) |
+            Stmt::Device(_, block) => self.check_block(block, false, &[]),
+            Stmt::If(pairs, else_block) => {
+                for (condition, block) in pairs {
+                    self.check_expr(condition);
+                    self.check_block(block, false, &[]);
+                }
+                if let Some(block) = else_block { self.check_block(block, false, &[]); }
+            }
+            Stmt::For(init, condition, advance, block) => {
+                self.check_expr(init);
+                self.check_expr(condition);
+                self.check_expr(advance);
+                self.check_block(block, false, &[]);
+            }
+            Stmt::ForIn(name, iterable, block) => {
+                self.check_expr(iterable);
+                self.check_block(block, false, std::slice::from_ref(name));
+            }
+            Stmt::While(condition, block) => {
+                self.check_expr(condition);
+                self.check_block(block, false, &[]);
+            }
+            Stmt::Continue | Stmt::Break => {}
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Eq(left, right) |
+            Expr::Neq(left, right) |
+            Expr::LT(left, right) |
+            Expr::GT(left, right) |
+            Expr::Add(left, right) |
+            Expr::Sub(left, right) |
+            Expr::Div(left, right) |
+            Expr::Mul(left, right) |
+            Expr::And(left, right) |
+            Expr::Or(left, right) |
+            Expr::Index(left, right) => {
+                self.check_expr(left);
+                self.check_expr(right);
+            }
+            Expr::Neg(value) |
+            Expr::Spanned(value, _) => self.check_expr(value),
+            Expr::Init(name, value) => {
+                self.check_expr(value);
+                self.scopes.last_mut().unwrap().defined.insert(name.clone());
+            }
+            Expr::Assign(name, value) => {
+                self.check_expr(value);
+                if !self.is_defined(name) {
+                    let message = format!("variable '{}' does not exist", name);
+                    self.report_unknown_name(message, name, false);
+                }
+            }
+            Expr::AssignIndex(target, index, value) => {
+                self.check_expr(target);
+                self.check_expr(index);
+                self.check_expr(value);
+            }
+            Expr::KeyMapping(mappings) => {
+                self.check_mappings(mappings);
+                for mapping in mappings { self.check_block(&mapping.to, true, &[]); }
+            }
+            Expr::ChordMapping(mapping) => self.check_block(&mapping.to, true, &[]),
+            Expr::SequenceMapping(mapping) => self.check_block(&mapping.to, true, &[]),
+            Expr::AbsMapping(mapping) => {
+                self.check_block(&mapping.enter, true, &[]);
+                self.check_block(&mapping.leave, true, &[]);
+            }
+            Expr::Name(name) => {
+                if !self.is_defined(name) {
+                    let message = format!("variable '{}' is not defined", name);
+                    self.report_unknown_name(message, name, false);
+                }
+            }
+            Expr::Lambda(params, block) => self.check_block(block, true, params),
+            Expr::List(items) => {
+                for item in items { self.check_expr(item); }
+            }
+            Expr::Map(entries) => {
+                for (_, value) in entries { self.check_expr(value); }
+            }
+            Expr::FunctionCall(name, args) => {
+                for arg in args { self.check_expr(arg); }
+                self.check_call(name, args.len());
+            }
+            Expr::TapHoldMapping(_) |
+            Expr::MouseKeyMapping(_) |
+            Expr::Hotstring(_) |
+            Expr::Value(_) |
+            Expr::KeyAction(_) |
+            Expr::SleepAction(_) |
+            Expr::ReleaseRestoreModifiers(_, _, _) => {}
+        }
+    }
+
+    fn check_call(&mut self, name: &str, arg_count: usize) {
+        // built-in functions take precedence over variables with the same name
+        if let Some((_, min, max)) = BUILTIN_FUNCTIONS.iter().find(|(builtin, _, _)| *builtin == name) {
+            if arg_count >= *min && max.is_none_or(|max| arg_count <= max) { return; }
+
+            let expected = match max {
+                Some(max) if max == min => format!("{}", min),
+                Some(max) => format!("{} to {}", min, max),
+                None => format!("at least {}", min),
+            };
+            self.report(Severity::Error, format!("function '{}' expects {} argument(s), got {}", name, expected, arg_count));
+            return;
+        }
+
+        if !self.is_defined(name) {
+            let message = format!("function '{}' is not defined", name);
+            self.report_unknown_name(message, name, true);
+        }
+    }
+
+    /// Reports an unknown variable or function along with the closest known name, function names can also be
+    /// built-in functions.
+    fn report_unknown_name(&mut self, message: String, name: &str, is_function: bool) {
+        let suggestion = {
+            let mut candidates: Vec<&str> = vec![];
+            if is_function { candidates.extend(BUILTIN_FUNCTIONS.iter().map(|(name, _, _)| *name)); }
+            candidates.extend(self.visible_names());
+            closest_match(name, candidates).map(|suggestion| suggestion.to_string())
+        };
+
+        let message = match suggestion {
+            Some(suggestion) => format!("{}, did you mean '{}'?", message, suggestion),
+            None => message,
+        };
+        self.report(Severity::Error, message);
+    }
+
+    /// Mappings for the same trigger in the same block replace each other, only the last one ever runs.
+    fn check_mappings(&mut self, mappings: &[KeyMapping]) {
+        let span = self.span.clone();
+        let scope = self.scopes.last_mut().unwrap();
+
+        let replaced: Vec<(KeyActionWithMods, Option<Span>)> = mappings.iter()
+            .filter_map(|mapping| scope.mappings.get(&mapping.from).map(|span| (mapping.from, span.clone())))
+            .collect();
+        for mapping in mappings { scope.mappings.insert(mapping.from, span.clone()); }
+
+        let (from, replaced_span) = match replaced.first() {
+            Some(first) => first.clone(),
+            None => return,
+        };
+
+        // click mappings consist of a mapping for each key state
+        let is_click = replaced.len() == 3;
+        let location = replaced_span.map(|span| format!(" at line {}", span.line)).unwrap_or_default();
+        self.report(Severity::Warning, format!("this mapping for '{}' replaces the mapping{}", format_trigger(&from, is_click), location));
+    }
+}
+
+/// Checks the script for mistakes that would otherwise only show up while it runs, i.e. calls to functions that
+/// don't exist.
+pub fn check_script(script: &Block) -> Vec<Diagnostic> {
+    let mut checker = Checker { scopes: vec![], span: None, diagnostics: vec![] };
+    checker.check_block(script, false, &[]);
+    checker.diagnostics
+}
+
+/// Parses and checks the script at the given path and reports the findings on stderr, returns whether the script is
+/// free of errors.
+pub fn check_file(script_path: &Path) -> bool {
+    let script = match script::load_script(script_path) {
+        Ok(script) => script,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+
+    let diagnostics = check_script(&script);
+    for diagnostic in &diagnostics {
+        eprintln!("{}\n", diagnostic);
+    }
+
+    let errors = diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+    eprintln!("{} error(s), {} warning(s)", errors, diagnostics.len() - errors);
+    errors == 0
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(script: &str) -> Vec<(Severity, usize, String)> {
+        let script = parsing::parser::parse_script(script).unwrap();
+        check_script(&script).into_iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.span.unwrap().line, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_undefined_names() {
+        assert_eq!(check("let count = 1;\nprint(cuont);\ncount = 2;\ntotal = 3;\n"), vec![
+            (Severity::Error, 2, "variable 'cuont' is not defined, did you mean 'count'?".to_string()),
+            (Severity::Error, 4, "variable 'total' does not exist".to_string()),
+        ]);
+
+        // variables of a block aren't visible outside of it
+        assert_eq!(check("if (true) { let a = 1; }\nprint(a);\n"), vec![
+            (Severity::Error, 2, "variable 'a' is not defined".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_deferred_blocks_see_later_variables() {
+        assert_eq!(check(indoc::indoc! {"
+            let fib = |n|{
+              if (n < 2) { return n; }
+              return fib(n - 1) + fib(n - 2);
+            };
+            a::{ print(message); };
+            let message = \"hi\";
+            for (item in [1, 2]) { print(item); }
+        "}), vec![]);
+    }
+
+    #[test]
+    fn test_function_calls() {
+        assert_eq!(check("prnt(1);\nsleep();\nmouse_move(1, 2, 3);\nexecute(\"ls\", \"-a\");\n"), vec![
+            (Severity::Error, 1, "function 'prnt' is not defined, did you mean 'print'?".to_string()),
+            (Severity::Error, 2, "function 'sleep' expects 1 argument(s), got 0".to_string()),
+            (Severity::Error, 3, "function 'mouse_move' expects 2 argument(s), got 3".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_replaced_mappings() {
+        assert_eq!(check("!a::b;\n{b down}::c;\n!a::c;\nb::c;\nlayer nav { !a::d; }\n"), vec![
+            (Severity::Warning, 3, "this mapping for '!a' replaces the mapping at line 1".to_string()),
+            (Severity::Warning, 4, "this mapping for '{b down}' replaces the mapping at line 2".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        assert_eq!(check("let f = ||{\n  return 1;\n  print(2);\n};\nwhile (true) { break; }\n"), vec![
+            (Severity::Warning, 3, "unreachable code after 'return'".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_examples_have_no_errors() {
+        for entry in fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(true, |ext| ext != "m2") { continue; }
+            // shows how errors are reported at runtime, so it has some on purpose
+            if path.ends_with("runtime-errors.m2") { continue; }
+
+            let script = script::load_script(&path).unwrap();
+            let errors: Vec<String> = check_script(&script).into_iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .map(|diagnostic| diagnostic.to_string())
+                .collect();
+            assert!(errors.is_empty(), "{}: {:?}", path.display(), errors);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::*;
+
+pub const CHORD_DEFAULT_WINDOW: time::Duration = time::Duration::from_millis(50);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChordMapping {
+    pub keys: Vec<Key>,
+    pub to: Block,
+}
+
+pub enum ChordAction {
+    /// the event is not part of a chord and should be processed normally
+    Forward(InputEvent),
+    /// all keys of a chord went down within the chord window
+    Fire(Arc<(Block, GuardedVarMap)>),
+}
+
+/// Requests a timeout message once the chord window of a newly started chord has passed.
+pub struct ChordTimer {
+    pub id: usize,
+    pub duration: time::Duration,
+}
+
+struct PendingChord {
+    id: usize,
+    buffered: Vec<InputEvent>,
+}
+
+/// The keys of a chord and the block it runs.
+type ChordEntry = (Vec<Key>, Arc<(Block, GuardedVarMap)>);
+
+pub struct ChordState {
+    pub window: time::Duration,
+    mappings: Vec<ChordEntry>,
+    pending: Option<PendingChord>,
+    /// keys of fired chords that are still held down
+    consumed: Vec<Key>,
+    next_id: usize,
+}
+
+impl ChordState {
+    pub fn new() -> Self {
+        ChordState { window: CHORD_DEFAULT_WINDOW, mappings: vec![], pending: None, consumed: vec![], next_id: 0 }
+    }
+
+    pub fn insert(&mut self, keys: Vec<Key>, block: Block, var_map: GuardedVarMap) {
+        self.mappings.retain(|(other, _)| !is_same_key_set(other, &keys));
+        self.mappings.push((keys, Arc::new((block, var_map))));
+    }
+
+    pub fn process(&mut self, ev: InputEvent) -> (Vec<ChordAction>, Option<ChordTimer>) {
+        let mut out = vec![];
+        let key = Key { event_code: ev.event_code };
+
+        // swallow the remaining events of keys that fired a chord
+        if let Some(idx) = self.consumed.iter().position(|consumed| *consumed == key) {
+            if ev.value == TYPE_UP { self.consumed.remove(idx); }
+            return (out, None);
+        }
+
+        if let Some(pending) = &mut self.pending {
+            let is_buffered = pending.buffered.iter().any(|buffered| buffered.event_code == ev.event_code);
+
+            if ev.value == TYPE_REPEAT && is_buffered { return (out, None); }
+
+            if ev.value == TYPE_DOWN && !is_buffered {
+                let mut keys = buffered_keys(&pending.buffered);
+                keys.push(key);
+
+                if self.mappings.iter().any(|(chord, _)| is_key_subset(&keys, chord)) {
+                    pending.buffered.push(ev);
+
+                    let has_longer_chord = self.mappings.iter()
+                        .any(|(chord, _)| chord.len() > keys.len() && is_key_subset(&keys, chord));
+
+                    if !has_longer_chord {
+                        if let Some(block) = self.find(&keys) {
+                            self.pending = None;
+                            self.consumed.append(&mut keys);
+                            out.push(ChordAction::Fire(block));
+                        }
+                    }
+                    return (out, None);
+                }
+            }
+
+            // the chord can't be completed anymore, replay everything in the original order
+            let pending = self.pending.take().unwrap();
+            for buffered in pending.buffered { out.push(ChordAction::Forward(buffered)); }
+        }
+
+        if ev.value == TYPE_DOWN && self.mappings.iter().any(|(chord, _)| chord.contains(&key)) {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            self.pending = Some(PendingChord { id, buffered: vec![ev] });
+            return (out, Some(ChordTimer { id, duration: self.window }));
+        }
+
+        out.push(ChordAction::Forward(ev));
+        (out, None)
+    }
+
+    /// Called once the chord window ran out, fires the chord if the pressed keys match one exactly and
+    /// replays the held keys otherwise.
+    pub fn timeout(&mut self, id: usize) -> Vec<ChordAction> {
+        let mut out = vec![];
+
+        let is_current = self.pending.as_ref().map(|p| p.id == id).unwrap_or(false);
+        if !is_current { return out; }
+
+        let pending = self.pending.take().unwrap();
+        let mut keys = buffered_keys(&pending.buffered);
+
+        match self.find(&keys) {
+            Some(block) => {
+                self.consumed.append(&mut keys);
+                out.push(ChordAction::Fire(block));
+            }
+            None => {
+                for buffered in pending.buffered { out.push(ChordAction::Forward(buffered)); }
+            }
+        }
+        out
+    }
+
+    /// Gives up on the pending chord and replays its keys, i.e. before the script gets replaced.
+    pub fn flush(&mut self) -> Vec<ChordAction> {
+        self.pending.take().into_iter()
+            .flat_map(|pending| pending.buffered)
+            .map(ChordAction::Forward)
+            .collect()
+    }
+
+    fn find(&self, keys: &[Key]) -> Option<Arc<(Block, GuardedVarMap)>> {
+        self.mappings.iter()
+            .find(|(chord, _)| is_same_key_set(chord, keys))
+            .map(|(_, block)| block.clone())
+    }
+}
+
+impl Default for ChordState {
+    fn default() -> Self { Self::new() }
+}
+
+fn buffered_keys(buffered: &[InputEvent]) -> Vec<Key> {
+    buffered.iter().map(|ev| Key { event_code: ev.event_code }).collect()
+}
+
+fn is_key_subset(keys: &[Key], chord: &[Key]) -> bool {
+    keys.iter().all(|key| chord.contains(key))
+}
+
+fn is_same_key_set(a: &[Key], b: &[Key]) -> bool {
+    a.len() == b.len() && is_key_subset(a, b)
+}
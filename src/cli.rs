@@ -4,16 +4,53 @@ use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Result};
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, SubCommand};
 use xdg::BaseDirectories;
 
+use crate::control::{ControlCommand, default_socket_path};
+use crate::device::output_sink::Output;
+use crate::device::panic_chord::{PANIC_CHORD_DEFAULT, PANIC_CHORD_NONE};
+use crate::Key;
+use crate::parsing::parser::parse_key_combination;
+
 pub struct Configuration {
+    pub script_path: PathBuf,
     pub script_file: fs::File,
     pub verbosity: i32,
     pub devices: Vec<String>,
+    pub socket_path: PathBuf,
+    /// pressing all of these keys stops map2 no matter what the script does, empty if turned off
+    pub panic_chord: Vec<Key>,
+    pub output: Output,
+}
+
+pub enum CliCommand {
+    Run(Configuration),
+    /// sends a command to a running instance through its control socket
+    Control { socket_path: PathBuf, command: String },
+    /// checks the script for mistakes without running it
+    Check { script_path: PathBuf },
+    /// formats the script in place, or only checks whether it's formatted
+    Format { script_path: PathBuf, check: bool },
+    /// evaluates statements read from stdin, optionally with the given devices grabbed
+    Repl { devices: Option<Vec<String>>, verbosity: i32, panic_chord: Vec<Key>, output: Output },
 }
 
-pub fn parse_cli() -> Result<Configuration> {
+fn read_device_list(path: PathBuf) -> Result<Vec<String>> {
+    let file_type = fs::metadata(&path).map_err(|err| anyhow!("failed to get file metadata: {}", err))?.file_type();
+    if file_type.is_char_device() { return Err(anyhow!("the device list file can't be a character device")); }
+    if file_type.is_block_device() { return Err(anyhow!("the device list file can't be a block device")); }
+
+    let file = fs::File::open(&path)
+        .map_err(|err| anyhow!("failed to open device list '{}': {}", &path.display(), err))?;
+
+    BufReader::new(file)
+        .lines()
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|err| anyhow!("failed to parse devices file: {}", err))
+}
+
+pub fn parse_cli() -> Result<CliCommand> {
     let matches = App::new("map2")
         .version("1.0")
         .author("shiro <shiro@usagi.io>")
@@ -29,12 +66,90 @@ pub fn parse_cli() -> Result<Configuration> {
             .long("--devices")
             .takes_value(true)
         )
+        .arg(Arg::with_name("socket")
+            .help("Sets the path of the control socket")
+            .long("--socket")
+            .takes_value(true)
+            .global(true))
+        .arg(Arg::with_name("panic chord")
+            .help("Sets the keys that stop map2 right away when pressed together, in case the script swallows keys, or 'none' to turn it off")
+            .long("--panic-chord")
+            .takes_value(true)
+            .default_value(PANIC_CHORD_DEFAULT))
+        .arg(Arg::with_name("output")
+            .help("Selects where the events end up: 'uinput', 'trace' to print them to stderr or 'record:<file>' to record them")
+            .long("--output")
+            .takes_value(true)
+            .default_value(Output::DEFAULT))
+        .arg(Arg::with_name("check")
+            .help("Checks the script for mistakes without running it")
+            .long("--check"))
+        .arg(Arg::with_name("repl")
+            .help("Evaluates statements interactively, mappings only take effect if devices are selected")
+            .long("--repl")
+            .conflicts_with_all(&["check", "script file"]))
         .arg(Arg::with_name("script file")
             .help("Executes the given script file")
             .index(1)
-            .required(true))
+            .required_unless("repl"))
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(SubCommand::with_name("ctl")
+            .about("Controls a running instance")
+            .arg(Arg::with_name("command")
+                .help("The command to send")
+                .possible_values(&ControlCommand::NAMES)
+                .required(true)))
+        .subcommand(SubCommand::with_name("fmt")
+            .about("Formats a script")
+            .arg(Arg::with_name("check")
+                .help("Only checks whether the script is formatted, without changing it")
+                .long("--check"))
+            .arg(Arg::with_name("script file")
+                .help("The script file to format")
+                .index(1)
+                .required(true)))
         .get_matches();
 
+    let socket_path = matches.value_of("socket")
+        .map(PathBuf::from)
+        .unwrap_or_else(default_socket_path);
+
+    if let Some(matches) = matches.subcommand_matches("ctl") {
+        let command = matches.value_of("command").unwrap().to_string();
+        let socket_path = matches.value_of("socket").map(PathBuf::from).unwrap_or(socket_path);
+        return Ok(CliCommand::Control { socket_path, command });
+    }
+
+    if let Some(matches) = matches.subcommand_matches("fmt") {
+        let script_path = PathBuf::from(matches.value_of("script file").unwrap());
+        return Ok(CliCommand::Format { script_path, check: matches.is_present("check") });
+    }
+
+    let verbosity = matches.occurrences_of("verbosity") as i32;
+
+    let panic_chord = match matches.value_of("panic chord").unwrap() {
+        PANIC_CHORD_NONE => vec![],
+        raw => {
+            let panic_chord = parse_key_combination(raw).map_err(|err| anyhow!("invalid panic chord: {}", err))?;
+            if panic_chord.len() < 2 { return Err(anyhow!("invalid panic chord: it needs at least 2 keys")); }
+            panic_chord
+        }
+    };
+
+    let output = matches.value_of("output").unwrap();
+    let output = Output::parse(output)
+        .ok_or_else(|| anyhow!("invalid output '{}', expected one of: uinput, trace, record:<file>", output))?;
+
+    if matches.is_present("repl") {
+        let devices = matches.value_of("devices").map(|path| read_device_list(PathBuf::from(path))).transpose()?;
+        return Ok(CliCommand::Repl { devices, verbosity, panic_chord, output });
+    }
+
+    if matches.is_present("check") {
+        let script_path = PathBuf::from(matches.value_of("script file").unwrap());
+        return Ok(CliCommand::Check { script_path });
+    }
+
     let device_list_config_name = "devices.list";
 
     let xdg_dirs = BaseDirectories::with_prefix("map2")
@@ -46,34 +161,23 @@ pub fn parse_cli() -> Result<Configuration> {
 
 
     let device_list_path = matches.value_of("devices")
-        .map(|path| Some(PathBuf::from(path)))
-        .unwrap_or_else(|| {
-            xdg_dirs.find_config_file(&device_list_config_name)
-        })
-        .map(|path| {
-            let file_type = fs::metadata(&path).map_err(|err| anyhow!("failed to get file metadata: {}", err))?.file_type();
-            if file_type.is_char_device() { return Err(anyhow!("the device list file can't be a character device")); }
-            if file_type.is_block_device() { return Err(anyhow!("the device list file can't be a block device")); }
-
-            fs::File::open(PathBuf::from(&path))
-                .map_err(|err| anyhow!("failed to open device list '{}': {}", &path.display(), err))
-        });
+        .map(PathBuf::from)
+        .or_else(|| xdg_dirs.find_config_file(device_list_config_name));
 
     let device_list = match device_list_path {
-        Some(path) => BufReader::new(path?)
-            .lines()
-            .collect::<std::result::Result<_, _>>()
-            .map_err(|err| anyhow!("failed to parse devices file: {}", err))?,
+        Some(path) => read_device_list(path)?,
         None => { vec![] }
     };
 
-    let verbosity = matches.occurrences_of("verbosity") as i32;
-
     let config = Configuration {
+        script_path: PathBuf::from(script_path),
         script_file,
         verbosity,
         devices: device_list,
+        socket_path,
+        panic_chord,
+        output,
     };
 
-    Ok(config)
+    Ok(CliCommand::Run(config))
 }
\ No newline at end of file
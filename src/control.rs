@@ -0,0 +1,143 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::PoisonError;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use xdg::BaseDirectories;
+
+use crate::*;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ControlCommand {
+    Status,
+    Reload,
+    /// passes all events through untouched until resumed
+    Pause,
+    Resume,
+    ListMappings,
+    Exit,
+}
+
+impl ControlCommand {
+    pub const NAMES: [&'static str; 6] = ["status", "reload", "pause", "resume", "list-mappings", "exit"];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "status" => Some(ControlCommand::Status),
+            "reload" => Some(ControlCommand::Reload),
+            "pause" => Some(ControlCommand::Pause),
+            "resume" => Some(ControlCommand::Resume),
+            "list-mappings" => Some(ControlCommand::ListMappings),
+            "exit" => Some(ControlCommand::Exit),
+            _ => None,
+        }
+    }
+}
+
+/// A command received through the control socket, the response is sent back to the client.
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    pub response_tx: oneshot::Sender<String>,
+}
+
+lazy_static! {
+    /// the socket this instance is listening on, removed again when the process exits
+    static ref SOCKET_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+/// The control socket lives in the runtime directory, i.e. '/run/user/1000/map2/control.sock'.
+pub fn default_socket_path() -> PathBuf {
+    BaseDirectories::with_prefix("map2").ok()
+        .and_then(|xdg_dirs| xdg_dirs.place_runtime_file("control.sock").ok())
+        .unwrap_or_else(|| std::env::temp_dir().join(format!("map2-{}.sock", unsafe { libc::getuid() })))
+}
+
+/// Listens for commands on the control socket and forwards them to the main loop.
+pub async fn serve(socket_path: &Path, request_tx: mpsc::Sender<ControlRequest>) -> Result<()> {
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).await.is_ok() {
+            return Err(anyhow!("the control socket '{}' is used by another instance", socket_path.display()));
+        }
+
+        // left behind by an instance that didn't shut down cleanly
+        fs::remove_file(socket_path)
+            .map_err(|err| anyhow!("failed to remove stale control socket '{}': {}", socket_path.display(), err))?;
+    }
+
+    let listener = UnixListener::bind(socket_path)
+        .map_err(|err| anyhow!("failed to create control socket '{}': {}", socket_path.display(), err))?;
+
+    // only the owner may control the running instance
+    fs::set_permissions(socket_path, fs::Permissions::from_mode(0o600))
+        .map_err(|err| anyhow!("failed to set control socket permissions: {}", err))?;
+    *SOCKET_PATH.lock().unwrap_or_else(PoisonError::into_inner) = Some(socket_path.to_path_buf());
+
+    task::spawn(async move {
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            let request_tx = request_tx.clone();
+            task::spawn(async move {
+                let _ = handle_client(stream, request_tx).await;
+            });
+        }
+    });
+
+    Ok(())
+}
+
+/// Removes the socket created by [`serve`], if any.
+pub fn remove_socket() {
+    if let Some(socket_path) = SOCKET_PATH.lock().unwrap_or_else(PoisonError::into_inner).take() {
+        let _ = fs::remove_file(socket_path);
+    }
+}
+
+async fn handle_client(mut stream: UnixStream, request_tx: mpsc::Sender<ControlRequest>) -> Result<()> {
+    let mut line = String::new();
+    BufReader::new(&mut stream).read_line(&mut line).await?;
+    let line = line.trim();
+
+    let response = match ControlCommand::parse(line) {
+        // the process is gone as soon as the command is handled, so the client gets its response up front
+        Some(command @ ControlCommand::Exit) => {
+            stream.write_all(b"exiting\n").await?;
+            stream.shutdown(std::net::Shutdown::Write)?;
+
+            let (response_tx, _response_rx) = oneshot::channel();
+            request_tx.send(ControlRequest { command, response_tx }).await
+                .map_err(|_| anyhow!("the main loop stopped"))?;
+            return Ok(());
+        }
+        Some(command) => {
+            let (response_tx, response_rx) = oneshot::channel();
+            request_tx.send(ControlRequest { command, response_tx }).await
+                .map_err(|_| anyhow!("the main loop stopped"))?;
+            response_rx.await?
+        }
+        None => format!("error: unknown command '{}', expected one of: {}", line, ControlCommand::NAMES.join(", ")),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// Sends a command to a running instance and returns its response.
+pub async fn send_command(socket_path: &Path, command: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(socket_path).await
+        .map_err(|err| anyhow!("failed to connect to '{}', is map2 running? ({})", socket_path.display(), err))?;
+
+    stream.write_all(format!("{}\n", command).as_bytes()).await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+
+    if let Some(err) = response.strip_prefix("error: ") {
+        return Err(anyhow!("{}", err.trim_end()));
+    }
+    Ok(response)
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use evdev_rs::{Device, DeviceWrapper};
+
+/// Identifies the physical device an input event was read from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceInfo {
+    pub path: String,
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+}
+
+impl DeviceInfo {
+    pub fn from_device(path: &Path, device: &Device) -> Self {
+        DeviceInfo {
+            path: path.to_string_lossy().into_owned(),
+            name: device.name().unwrap_or_default().to_string(),
+            vendor: device.vendor_id(),
+            product: device.product_id(),
+        }
+    }
+
+    /// The vendor and product id formatted as in 'lsusb', i.e. '046d:c07c'.
+    pub fn id(&self) -> String {
+        format!("{:04x}:{:04x}", self.vendor, self.product)
+    }
+
+    /// Checks whether a device pattern describes this device, the pattern is either the device name, the
+    /// device file path or the vendor and product id.
+    pub fn matches(&self, pattern: &str) -> bool {
+        self.name == pattern || self.path == pattern || self.id().eq_ignore_ascii_case(pattern)
+    }
+}
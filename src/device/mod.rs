@@ -1,4 +1,9 @@
 pub mod virtual_input_device;
 mod virt_device;
 pub mod device_logging;
+pub mod device_info;
+pub mod panic_chord;
+pub mod output_sink;
 mod virtual_output_device;
+
+pub use virtual_output_device::{close_output_device, held_keys, init_virtual_output_device, release_held_keys};
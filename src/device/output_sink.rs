@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use evdev_rs::{AbsInfo, UInputDevice};
+use evdev_rs::util::event_code_to_int;
+
+use crate::*;
+use crate::logging::print_input_event;
+use super::virt_device;
+
+/// Where the events that the script sends end up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// a virtual device that the rest of the system sees as a real one
+    Uinput,
+    /// prints the events to stderr, stdout belongs to the script
+    Trace,
+    /// writes the events into a file in the format of 'evemu-record'
+    Record(PathBuf),
+}
+
+impl Output {
+    pub const DEFAULT: &'static str = "uinput";
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "uinput" => Some(Output::Uinput),
+            "trace" => Some(Output::Trace),
+            _ => s.strip_prefix("record:")
+                .filter(|path| !path.is_empty())
+                .map(|path| Output::Record(PathBuf::from(path))),
+        }
+    }
+}
+
+pub trait OutputSink: Send {
+    fn write_event(&mut self, ev: &InputEvent) -> Result<()>;
+}
+
+/// Writes the events into uinput devices, absolute axes go to a device of their own.
+pub struct UInputSink {
+    device: UInputDevice,
+    abs_device: Option<UInputDevice>,
+    /// whether the devices got events since the last sync event
+    unsynced: (bool, bool),
+}
+
+impl UInputSink {
+    pub fn new(device: UInputDevice, abs_device: Option<UInputDevice>) -> Self {
+        UInputSink { device, abs_device, unsynced: (false, false) }
+    }
+}
+
+impl OutputSink for UInputSink {
+    fn write_event(&mut self, ev: &InputEvent) -> Result<()> {
+        let write = |device: &UInputDevice| device.write_event(ev)
+            .map_err(|err| anyhow!("failed to write event into uinput device: {}", err));
+
+        if ev.event_code == SYN_REPORT.event_code {
+            let (device_unsynced, abs_device_unsynced) = std::mem::take(&mut self.unsynced);
+            if let (true, Some(abs_device)) = (abs_device_unsynced, &self.abs_device) { write(abs_device)?; }
+            if device_unsynced || !abs_device_unsynced { write(&self.device)?; }
+            return Ok(());
+        }
+
+        match (&ev.event_code, &self.abs_device) {
+            (EventCode::EV_ABS(_), Some(abs_device)) => {
+                self.unsynced.1 = true;
+                write(abs_device)
+            }
+            _ => {
+                self.unsynced.0 = true;
+                write(&self.device)
+            }
+        }
+    }
+}
+
+pub struct TraceSink;
+
+impl OutputSink for TraceSink {
+    fn write_event(&mut self, ev: &InputEvent) -> Result<()> {
+        eprintln!("{}", print_input_event(ev));
+        Ok(())
+    }
+}
+
+pub struct RecordSink<W: io::Write + Send> {
+    writer: W,
+    /// timestamps are relative to the first event, like the ones 'evemu-record' writes
+    start: Option<Instant>,
+}
+
+impl RecordSink<BufWriter<fs::File>> {
+    pub fn create(path: &Path, abs_axes: &[(EventCode, AbsInfo)]) -> Result<Self> {
+        let file = fs::File::create(path)
+            .map_err(|err| anyhow!("failed to create recording file '{}': {}", path.display(), err))?;
+        RecordSink::new(BufWriter::new(file), abs_axes)
+    }
+}
+
+impl<W: io::Write + Send> RecordSink<W> {
+    /// Starts the recording with the description of the virtual device, 'evemu-play' needs it to create the device
+    /// that it replays the events on.
+    pub fn new(mut writer: W, abs_axes: &[(EventCode, AbsInfo)]) -> Result<Self> {
+        writeln!(writer, "# EVEMU 1.3")?;
+        writeln!(writer, "# recorded by map2")?;
+        write_device_description(&mut writer, abs_axes)
+            .map_err(|err| anyhow!("failed to write device description into recording file: {}", err))?;
+        Ok(RecordSink { writer, start: None })
+    }
+}
+
+/// Writes the name, ids, properties, enabled codes and absolute axes of the virtual device the way 'evemu-record' does.
+/// The absolute axes are part of the same device here, 'evemu-play' only creates one.
+fn write_device_description(writer: &mut impl io::Write, abs_axes: &[(EventCode, AbsInfo)]) -> io::Result<()> {
+    writeln!(writer, "N: {}", virt_device::VIRTUAL_DEVICE_NAME)?;
+    writeln!(writer, "I: 0000 0000 0000 0000")?;
+    writeln!(writer, "P: 00 00 00 00 00 00 00 00")?;
+
+    // a bitmask of the enabled codes per event type, the one of EV_SYN holds the enabled event types
+    let mut masks: BTreeMap<u32, Vec<u8>> = BTreeMap::new();
+    let mut enable = |ev_type: u32, code: u32| {
+        let mask = masks.entry(ev_type).or_default();
+        let idx = code as usize / 8;
+        // evemu reads the bitmasks in lines of 8 bytes
+        if mask.len() <= idx { mask.resize((idx / 8 + 1) * 8, 0); }
+        mask[idx] |= 1 << (code % 8);
+    };
+
+    enable(0, 0);
+    let abs_codes = abs_axes.iter().map(|(code, _)| *code);
+    for code in virt_device::virtual_device_codes().chain(abs_codes) {
+        let (ev_type, ev_code) = event_code_to_int(&code);
+        enable(0, ev_type);
+        enable(ev_type, ev_code);
+    }
+
+    for (ev_type, mask) in masks {
+        for line in mask.chunks(8) {
+            write!(writer, "B: {:02x}", ev_type)?;
+            for byte in line { write!(writer, " {:02x}", byte)?; }
+            writeln!(writer)?;
+        }
+    }
+
+    for (code, abs_info) in abs_axes {
+        let (_, ev_code) = event_code_to_int(code);
+        writeln!(writer, "A: {:02x} {} {} {} {} {}",
+                 ev_code, abs_info.minimum, abs_info.maximum, abs_info.fuzz, abs_info.flat, abs_info.resolution)?;
+    }
+    Ok(())
+}
+
+impl<W: io::Write + Send> OutputSink for RecordSink<W> {
+    fn write_event(&mut self, ev: &InputEvent) -> Result<()> {
+        let elapsed = self.start.get_or_insert_with(Instant::now).elapsed();
+        let (ev_type, ev_code) = event_code_to_int(&ev.event_code);
+
+        writeln!(self.writer, "E: {}.{:06} {:04x} {:04x} {}",
+                 elapsed.as_secs(), elapsed.subsec_micros(), ev_type, ev_code, ev.value)
+            .map_err(|err| anyhow!("failed to write event into recording file: {}", err))?;
+
+        // the process might exit at any time, only a complete report is worth keeping though
+        if ev.event_code == SYN_REPORT.event_code { self.writer.flush()?; }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::enums::EV_ABS;
+
+    use super::*;
+
+    #[test]
+    fn test_output_parse() {
+        assert_eq!(Output::parse("uinput"), Some(Output::Uinput));
+        assert_eq!(Output::parse("trace"), Some(Output::Trace));
+        assert_eq!(Output::parse("record:out.evemu"), Some(Output::Record(PathBuf::from("out.evemu"))));
+        assert_eq!(Output::parse("record:"), None);
+        assert_eq!(Output::parse("stdout"), None);
+    }
+
+    #[test]
+    fn test_record_sink() -> Result<()> {
+        let abs_x = AbsInfo { value: 0, minimum: 0, maximum: 1920, fuzz: 0, flat: 0, resolution: 12 };
+        let mut sink = RecordSink::new(vec![], &[(EventCode::EV_ABS(EV_ABS::ABS_X), abs_x)])?;
+        sink.write_event(&KeyAction::new(*KEY_A, TYPE_DOWN).to_input_ev())?;
+        sink.write_event(&SYN_REPORT.clone())?;
+
+        let recording = String::from_utf8(sink.writer)?;
+        let lines: Vec<&str> = recording.lines().collect();
+        assert_eq!(lines[0], "# EVEMU 1.3");
+        assert_eq!(lines[2], "N: Virtual Device");
+        // EV_SYN, EV_KEY, EV_REL and EV_ABS
+        assert_eq!(lines[5], "B: 00 0f 00 00 00 00 00 00 00");
+        assert!(lines.iter().all(|line| !line.starts_with("B: ") || line.split(' ').count() == 10));
+        assert!(lines.contains(&"B: 03 01 00 00 00 00 00 00 00"));
+        assert!(lines.contains(&"A: 00 0 1920 0 0 12"));
+
+        let events: Vec<&str> = lines.into_iter().filter(|line| line.starts_with("E: ")).collect();
+        assert!(events[0].starts_with("E: 0.0"));
+        assert!(events[0].ends_with(" 0001 001e 1"), "{}", events[0]);
+        assert!(events[1].ends_with(" 0000 0000 0"), "{}", events[1]);
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use crate::*;
+
+pub const PANIC_CHORD_DEFAULT: &str = "ctrl+alt+pause";
+/// Passed as the panic chord to turn it off.
+pub const PANIC_CHORD_NONE: &str = "none";
+
+/// Watches the keys held on all grabbed devices for the panic chord, which stops map2 no matter what the script does.
+/// Clones share the held keys, so the keys of the chord can be pressed on different devices.
+#[derive(Clone)]
+pub struct PanicChord {
+    keys: Arc<Vec<Key>>,
+    pressed: Arc<Mutex<Vec<Key>>>,
+}
+
+/// The same modifier on the other side of the keyboard.
+fn other_side(key: &Key) -> Option<Key> {
+    let pairs = [
+        (*KEY_LEFT_CTRL, *KEY_RIGHT_CTRL),
+        (*KEY_LEFT_ALT, *KEY_RIGHT_ALT),
+        (*KEY_LEFT_SHIFT, *KEY_RIGHT_SHIFT),
+        (*KEY_LEFT_META, *KEY_RIGHT_META),
+    ];
+    pairs.iter().find_map(|(left, right)| match key {
+        key if key == left => Some(*right),
+        key if key == right => Some(*left),
+        _ => None,
+    })
+}
+
+impl PanicChord {
+    pub fn new(keys: Vec<Key>) -> Self {
+        PanicChord { keys: Arc::new(keys), pressed: Default::default() }
+    }
+
+    /// Returns whether all keys of the chord are held down once the event is applied, modifiers can be held on
+    /// either side.
+    pub fn process(&self, ev: &InputEvent) -> bool {
+        if !matches!(ev.event_code, EventCode::EV_KEY(_)) { return false; }
+        let key = Key { event_code: ev.event_code };
+
+        let mut pressed = self.pressed.lock().unwrap();
+        if ev.value == TYPE_UP {
+            pressed.retain(|pressed| *pressed != key);
+            return false;
+        }
+        if !pressed.contains(&key) { pressed.push(key); }
+
+        self.keys.iter().all(|key| pressed.contains(key) || other_side(key).is_some_and(|key| pressed.contains(&key)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_chord() {
+        let chord = PanicChord::new(vec![*KEY_LEFT_CTRL, *KEY_LEFT_ALT, *KEY_BACKSPACE]);
+
+        assert!(!chord.process(&KeyAction::new(*KEY_LEFT_CTRL, TYPE_DOWN).to_input_ev()));
+        assert!(!chord.process(&KeyAction::new(*KEY_BACKSPACE, TYPE_DOWN).to_input_ev()));
+        assert!(!chord.process(&KeyAction::new(*KEY_BACKSPACE, TYPE_UP).to_input_ev()));
+        assert!(!chord.process(&KeyAction::new(*KEY_LEFT_ALT, TYPE_DOWN).to_input_ev()));
+        assert!(!chord.process(&SYN_REPORT.clone()));
+        assert!(chord.process(&KeyAction::new(*KEY_BACKSPACE, TYPE_DOWN).to_input_ev()));
+    }
+
+    #[test]
+    fn test_panic_chord_across_devices() {
+        let keyboard = PanicChord::new(vec![*KEY_LEFT_CTRL, *KEY_LEFT_ALT, *KEY_BACKSPACE]);
+        let keypad = keyboard.clone();
+
+        // right modifiers count as well
+        assert!(!keyboard.process(&KeyAction::new(*KEY_RIGHT_CTRL, TYPE_DOWN).to_input_ev()));
+        assert!(!keyboard.process(&KeyAction::new(*KEY_RIGHT_ALT, TYPE_DOWN).to_input_ev()));
+        assert!(keypad.process(&KeyAction::new(*KEY_BACKSPACE, TYPE_DOWN).to_input_ev()));
+
+        assert!(!keyboard.process(&KeyAction::new(*KEY_RIGHT_ALT, TYPE_UP).to_input_ev()));
+        assert!(!keypad.process(&KeyAction::new(*KEY_BACKSPACE, TYPE_DOWN).to_input_ev()));
+    }
+}
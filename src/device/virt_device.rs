@@ -5,51 +5,55 @@ use evdev_rs::enums::*;
 
 use crate::*;
 
-fn set_code_bits(dev: &Device, ev_code: &EventCode, max: &EventCode) -> Result<()> {
-    for code in ev_code.iter() {
-        if code == *max {
-            break;
-        }
+pub(crate) const VIRTUAL_DEVICE_NAME: &str = "Virtual Device";
 
-        dev.enable(&code)
-            .map_err(|err|anyhow!("failed to enable code bit: {}", err))?;
+/// The codes that the virtual device supports, absolute axes go on a device of their own.
+pub(crate) fn virtual_device_codes() -> impl Iterator<Item=EventCode> {
+    let range = |first: EventCode, max: EventCode| first.iter().take_while(move |code| *code != max);
+
+    range(EventCode::EV_KEY(EV_KEY::KEY_RESERVED), EventCode::EV_KEY(EV_KEY::KEY_MAX))
+        .chain(range(EventCode::EV_REL(EV_REL::REL_X), EventCode::EV_REL(EV_REL::REL_MAX)))
+    // .chain(range(EventCode::EV_LED(EV_LED::LED_NUML), EventCode::EV_LED(EV_LED::LED_MAX)))
+}
+
+/// Reads the absolute axes of a device along with their ranges.
+pub(crate) fn abs_axes(dev: &Device) -> Vec<(EventCode, AbsInfo)> {
+    let mut axes = vec![];
+    for code in EventCode::EV_ABS(EV_ABS::ABS_X).iter() {
+        if code == EventCode::EV_ABS(EV_ABS::ABS_MAX) { break; }
+        if !dev.has(&code) { continue; }
+
+        if let Some(abs_info) = dev.abs_info(&code) {
+            axes.push((code, abs_info));
+        }
     }
-    Ok(())
+    axes
 }
 
 fn set_bits(dev: &Device) -> Result<()> {
-    for ev_type in EventType::EV_SYN.iter() {
-        match ev_type {
-            EventType::EV_KEY => set_code_bits(
-                dev,
-                &EventCode::EV_KEY(EV_KEY::KEY_RESERVED),
-                &EventCode::EV_KEY(EV_KEY::KEY_MAX),
-            )?,
-            EventType::EV_REL => set_code_bits(
-                dev,
-                &EventCode::EV_REL(EV_REL::REL_X),
-                &EventCode::EV_REL(EV_REL::REL_MAX),
-            )?,
-            // EventType::EV_ABS => clone_code_bits(
-            //     dev,
-            //     &EventCode::EV_ABS(EV_ABS::ABS_X),
-            //     &EventCode::EV_ABS(EV_ABS::ABS_MAX),
-            // )?,
-            // EventType::EV_LED => {}
-                // clone_code_bits(
-                // dev,
-                // &EventCode::EV_LED(EV_LED::LED_NUML),
-                // &EventCode::EV_LED(EV_LED::LED_MAX),
-            // )?,
-            _ => (),
-        }
+    for code in virtual_device_codes() {
+        dev.enable(&code)
+            .map_err(|err|anyhow!("failed to enable code bit: {}", err))?;
     }
     Ok(())
 }
 
 pub(crate) fn init_virtual_device(dev: &Device) -> Result<()> {
-    dev.set_name("Virtual Device");
+    dev.set_name(VIRTUAL_DEVICE_NAME);
     set_bits(dev)?;
 
     Ok(())
 }
+
+/// Sets up the device that carries the absolute axes. They can't go on the main device, udev would classify a device
+/// with absolute axes and buttons as a tablet or joystick and it would stop being treated as a keyboard.
+pub(crate) fn init_virtual_abs_device(dev: &Device, abs_axes: &[(EventCode, AbsInfo)]) -> Result<()> {
+    dev.set_name("Virtual Device (absolute axes)");
+
+    // absolute axes need their ranges, which are copied from the grabbed devices
+    for (code, abs_info) in abs_axes {
+        dev.enable_event_code(code, Some(abs_info))
+            .map_err(|err| anyhow!("failed to enable absolute axis '{}': {}", code, err))?;
+    }
+    Ok(())
+}
@@ -2,6 +2,7 @@ use std::{fs, io, thread, time};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
 
 use anyhow::{anyhow, Result};
 use evdev_rs::*;
@@ -11,7 +12,30 @@ use tokio::sync::{mpsc, oneshot};
 use tokio::task;
 use walkdir::WalkDir;
 
+use crate::Key;
 use super::*;
+use super::device_info::DeviceInfo;
+use super::output_sink::Output;
+use super::panic_chord::PanicChord;
+
+lazy_static! {
+    /// every device that is grabbed, so they can all be released when the panic chord is pressed
+    static ref GRABBED_DEVICES: Mutex<Vec<Weak<Mutex<Device>>>> = Mutex::new(vec![]);
+}
+
+/// Releases the held keys and all grabbed devices and exits, even if the script or the event loop is stuck.
+fn panic_chord_exit() -> ! {
+    eprintln!("error: the panic chord was pressed, exiting");
+    release_held_keys();
+
+    for device in GRABBED_DEVICES.lock().unwrap_or_else(PoisonError::into_inner).drain(..) {
+        if let Some(device) = device.upgrade() {
+            let _ = device.lock().unwrap_or_else(PoisonError::into_inner).grab(GrabMode::Ungrab);
+        }
+    }
+
+    crate::shutdown::exit(1)
+}
 
 fn get_fd_list(patterns: &Vec<Regex>) -> Vec<PathBuf> {
     let mut list = vec![];
@@ -30,7 +54,8 @@ fn get_fd_list(patterns: &Vec<Regex>) -> Vec<PathBuf> {
 
 
 pub fn read_from_device_input_fd_thread_handler(
-    device: Device,
+    device: Arc<Mutex<Device>>,
+    panic_chord: Option<PanicChord>,
     mut handler: impl FnMut(InputEvent),
     mut abort_rx: oneshot::Receiver<()>,
 ) {
@@ -38,13 +63,14 @@ pub fn read_from_device_input_fd_thread_handler(
     loop {
         if abort_rx.try_recv().is_ok() { return; }
 
-        a = device.next_event(ReadFlag::NORMAL);
+        // the lock is only held while reading, the panic chord needs it to release the device
+        a = device.lock().unwrap().next_event(ReadFlag::NORMAL);
         if a.is_ok() {
             let mut result = a.ok().unwrap();
             match result.0 {
                 ReadStatus::Sync => { // dropped, need to sync
                     while result.0 == ReadStatus::Sync {
-                        a = device.next_event(ReadFlag::SYNC);
+                        a = device.lock().unwrap().next_event(ReadFlag::SYNC);
                         if a.is_ok() {
                             result = a.ok().unwrap();
                         } else { // something failed, abort sync and carry on
@@ -53,6 +79,9 @@ pub fn read_from_device_input_fd_thread_handler(
                     }
                 }
                 ReadStatus::Success => {
+                    // checked before the event is passed on, the script never gets a chance to swallow it
+                    if panic_chord.as_ref().is_some_and(|panic_chord| panic_chord.process(&result.1)) { panic_chord_exit(); }
+
                     handler(result.1);
                     // futures::executor::block_on(
                     //     reader_tx.send(result.1)
@@ -79,9 +108,7 @@ pub fn read_from_device_input_fd_thread_handler(
 }
 
 
-async fn runner_it(fd_path: &Path,
-                   writer: mpsc::Sender<InputEvent>)
-                   -> Result<oneshot::Sender<()>> {
+fn open_device(fd_path: &Path) -> Result<Device> {
     let fd_file = fs::OpenOptions::new()
         .read(true)
         .open(&fd_path)
@@ -92,37 +119,77 @@ async fn runner_it(fd_path: &Path,
     device.grab(GrabMode::Grab)
         .map_err(|err| anyhow!("failed to grab device '{}': {}", fd_path.to_string_lossy(), err))?;
 
+    Ok(device)
+}
+
+/// An input event tagged with the device it was read from.
+pub type DeviceEvent = (InputEvent, Option<Arc<DeviceInfo>>);
+
+fn spawn_device_reader(fd_path: &Path, device: Device, writer: mpsc::Sender<DeviceEvent>, panic_chord: Option<PanicChord>) -> oneshot::Sender<()> {
+    let device_info = Arc::new(DeviceInfo::from_device(fd_path, &device));
+
+    let device = Arc::new(Mutex::new(device));
+    {
+        let mut grabbed_devices = GRABBED_DEVICES.lock().unwrap();
+        grabbed_devices.retain(|device| device.strong_count() > 0);
+        grabbed_devices.push(Arc::downgrade(&device));
+    }
+
+    // events are forwarded on their own thread, the reader keeps checking for the panic chord even if the event
+    // loop is stuck and stops taking events
+    let (forward_tx, forward_rx) = std::sync::mpsc::channel::<DeviceEvent>();
+    thread::spawn(move || {
+        for ev in forward_rx {
+            if futures::executor::block_on(writer.send(ev)).is_err() { return; }
+        }
+    });
+
     // spawn tasks for reading devices
     let (abort_tx, abort_rx) = oneshot::channel();
     thread::spawn(move || {
         read_from_device_input_fd_thread_handler(
             device,
-            |ev| {
-                let _ = futures::executor::block_on(
-                    writer.send(ev)
-                );
-            },
+            panic_chord,
+            |ev| { let _ = forward_tx.send((ev, Some(device_info.clone()))); },
             abort_rx,
         );
     });
 
-    Ok(abort_tx)
+    abort_tx
 }
 
 async fn runner
 (device_fd_path_pattens: Vec<Regex>,
- reader_init: oneshot::Sender<mpsc::Sender<InputEvent>>,
- writer: mpsc::Sender<InputEvent>,
+ panic_chord: Option<PanicChord>,
+ output: Output,
+ reader_init: oneshot::Sender<Result<mpsc::Sender<InputEvent>>>,
+ writer: mpsc::Sender<DeviceEvent>,
 ) -> Result<()> {
     task::spawn(async move {
         let (fs_reader_tx, reader_rx) = mpsc::channel(128);
 
-        // send the reader to the client
-        reader_init.send(fs_reader_tx.clone()).unwrap();
+        // grab the devices before creating the output device, it needs the ranges of their absolute axes
+        let mut initial_devices = vec![];
+        for device_fd_path in get_fd_list(&device_fd_path_pattens) {
+            match open_device(&device_fd_path) {
+                Ok(device) => initial_devices.push((device_fd_path, device)),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
 
-        virtual_output_device::init_virtual_output_device(reader_rx).await
-            .map_err(|err| anyhow!("uinput error: {}", err))
-            .unwrap();
+        let mut abs_axes = vec![];
+        for (_, device) in &initial_devices {
+            for (code, abs_info) in virt_device::abs_axes(device) {
+                if !abs_axes.iter().any(|(other, _)| *other == code) { abs_axes.push((code, abs_info)); }
+            }
+        }
+
+        // send the reader to the client, or why there is nothing to send the events to
+        if let Err(err) = virtual_output_device::init_virtual_output_device(reader_rx, &abs_axes, &output).await {
+            let _ = reader_init.send(Err(anyhow!("output error: {}", err)));
+            return Ok(());
+        }
+        let _ = reader_init.send(Ok(fs_reader_tx.clone()));
 
         #[derive(Debug)]
         enum FsWatchEvent {
@@ -157,16 +224,8 @@ async fn runner
 
         let mut device_map = HashMap::new();
 
-        for device_fd_path in get_fd_list(&device_fd_path_pattens) {
-            let res = runner_it(&device_fd_path, writer.clone()).await;
-            let abort_tx = match res {
-                Ok(v) => v,
-                Err(err) => {
-                    eprintln!("{}", err);
-                    continue;
-                }
-            };
-
+        for (device_fd_path, device) in initial_devices {
+            let abort_tx = spawn_device_reader(&device_fd_path, device, writer.clone(), panic_chord.clone());
             device_map.insert(device_fd_path, abort_tx);
         }
 
@@ -178,7 +237,19 @@ async fn runner
                         continue;
                     }
 
-                    let abort_tx = runner_it(&path, writer.clone()).await?;
+                    let mut device = open_device(&path)?;
+
+                    // the output device can't get new axes, events of axes it doesn't have would be lost
+                    let has_missing_axes = virt_device::abs_axes(&device).iter()
+                        .any(|(code, _)| !abs_axes.iter().any(|(other, _)| other == code));
+                    if has_missing_axes {
+                        let _ = device.grab(GrabMode::Ungrab);
+                        eprintln!("warning: not grabbing '{}', its absolute axes are missing on the output device, \
+                                   restart map2 to use it", path.to_string_lossy());
+                        continue;
+                    }
+
+                    let abort_tx = spawn_device_reader(&path, device, writer.clone(), panic_chord.clone());
                     device_map.insert(path, abort_tx);
                 }
                 FsWatchEvent::REMOVE(path) => {
@@ -197,14 +268,18 @@ async fn runner
 }
 
 
-pub async fn bind_udev_inputs(fd_patterns: &[impl AsRef<str>], reader_init_tx: oneshot::Sender<mpsc::Sender<InputEvent>>, writer_tx: mpsc::Sender<InputEvent>) -> Result<()> {
+/// Grabs the devices matching the patterns, pressing all keys of the panic chord stops map2. The keys are tracked
+/// across all grabbed devices, so the chord can be spread over several of them. An empty panic chord is turned off.
+pub async fn bind_udev_inputs(fd_patterns: &[impl AsRef<str>], panic_chord: Vec<Key>, output: Output, reader_init_tx: oneshot::Sender<Result<mpsc::Sender<InputEvent>>>, writer_tx: mpsc::Sender<DeviceEvent>) -> Result<()> {
     let fd_patterns_regex = fd_patterns.into_iter()
         .map(|v| Regex::new(v.as_ref()))
         .collect::<std::result::Result<_, _>>()
         .map_err(|err| anyhow!("failed to parse regex: {}", err))?;
 
+    let panic_chord = if panic_chord.is_empty() { None } else { Some(PanicChord::new(panic_chord)) };
+
     task::spawn(async move {
-        runner(fd_patterns_regex, reader_init_tx, writer_tx).await.unwrap();
+        runner(fd_patterns_regex, panic_chord, output, reader_init_tx, writer_tx).await.unwrap();
         Ok::<(), anyhow::Error>(())
     });
 
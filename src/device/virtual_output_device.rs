@@ -1,16 +1,95 @@
-use evdev_rs::{UInputDevice, UninitDevice};
+use std::sync::{MutexGuard, TryLockError};
+use std::thread;
+
+use evdev_rs::{AbsInfo, Device, UInputDevice, UninitDevice};
 use crate::*;
 use super::*;
+use super::output_sink::{Output, OutputSink, RecordSink, TraceSink, UInputSink};
 
-pub async fn init_virtual_output_device(
-    mut reader_rx: mpsc::Receiver<InputEvent>,
-) -> Result<()> {
-    let mut new_device = UninitDevice::new()
+/// Keys that the output device is holding down.
+#[derive(Default)]
+struct HeldKeys(Vec<EventCode>);
+
+impl HeldKeys {
+    fn update(&mut self, ev: &InputEvent) {
+        if !matches!(ev.event_code, EventCode::EV_KEY(_)) { return; }
+
+        let idx = self.0.iter().position(|code| *code == ev.event_code);
+        if ev.value == TYPE_UP {
+            if let Some(idx) = idx { self.0.remove(idx); }
+        } else if idx.is_none() {
+            self.0.push(ev.event_code);
+        }
+    }
+
+    /// The events that release all held keys, most recently pressed first.
+    fn release_events(&mut self) -> Vec<InputEvent> {
+        let mut events: Vec<InputEvent> = self.0.drain(..).rev()
+            .map(|event_code| KeyAction::new(Key { event_code }, TYPE_UP).to_input_ev())
+            .collect();
+        if !events.is_empty() { events.push(SYN_REPORT.clone()); }
+        events
+    }
+}
+
+struct OutputDevice {
+    sink: Box<dyn OutputSink>,
+    held_keys: HeldKeys,
+}
+
+lazy_static! {
+    /// shared with the shutdown path, which needs to release the held keys no matter where the process exits
+    static ref OUTPUT_DEVICE: Mutex<Option<OutputDevice>> = Mutex::new(None);
+}
+
+/// Locks the output device, `None` if it stays locked. The lock might be held by a thread that panicked while
+/// writing an event, waiting for it would never end then.
+fn lock_output_device() -> Option<MutexGuard<'static, Option<OutputDevice>>> {
+    for _ in 0..100 {
+        match OUTPUT_DEVICE.try_lock() {
+            Ok(output_device) => return Some(output_device),
+            // a panic while writing an event must not keep the keys from being released
+            Err(TryLockError::Poisoned(err)) => return Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => thread::sleep(time::Duration::from_millis(1)),
+        }
+    }
+    None
+}
+
+fn release_keys(output_device: &mut OutputDevice) {
+    for ev in output_device.held_keys.release_events() {
+        let _ = output_device.sink.write_event(&ev);
+    }
+}
+
+/// The keys the output device is holding down, most recently pressed last.
+pub fn held_keys() -> Vec<Key> {
+    lock_output_device().as_ref()
+        .and_then(|output_device| output_device.as_ref())
+        .map(|output_device| output_device.held_keys.0.iter().map(|&event_code| Key { event_code }).collect())
+        .unwrap_or_default()
+}
+
+/// Releases all keys the output device is holding down, the device keeps working.
+pub fn release_held_keys() {
+    if let Some(output_device) = lock_output_device().as_mut().and_then(|output_device| output_device.as_mut()) {
+        release_keys(output_device);
+    }
+}
+
+/// Releases all keys the output device is holding down and destroys it, events written afterwards are dropped.
+pub fn close_output_device() {
+    if let Some(mut output_device) = lock_output_device().and_then(|mut output_device| output_device.take()) {
+        release_keys(&mut output_device);
+    }
+}
+
+fn create_uinput_device(init: impl FnOnce(&Device) -> Result<()>) -> Result<UInputDevice> {
+    let new_device = UninitDevice::new()
         .ok_or(anyhow!("failed to instantiate udev device: libevdev didn't return a device"))?
         .unstable_force_init();
 
-    virt_device::init_virtual_device(&mut new_device)
-        .map_err(|err| anyhow!("failed to instantiate udev device: {}", err))?;
+    init(&new_device).map_err(|err| anyhow!("failed to instantiate udev device: {}", err))?;
 
     let input_device = UInputDevice::create_from_device(&new_device);
 
@@ -20,7 +99,29 @@ pub async fn init_virtual_output_device(
         }
     };
 
-    let input_device = input_device.map_err(|err| anyhow!("failed to initialize uinput device: {}", err))?;
+    input_device.map_err(|err| anyhow!("failed to initialize uinput device: {}", err))
+}
+
+/// Writes the events received from the channel into the selected output.
+pub async fn init_virtual_output_device(
+    mut reader_rx: mpsc::Receiver<InputEvent>,
+    abs_axes: &[(EventCode, AbsInfo)],
+    output: &Output,
+) -> Result<()> {
+    let sink: Box<dyn OutputSink> = match output {
+        Output::Uinput => {
+            let device = create_uinput_device(virt_device::init_virtual_device)?;
+            let abs_device = if abs_axes.is_empty() {
+                None
+            } else {
+                Some(create_uinput_device(|dev| virt_device::init_virtual_abs_device(dev, abs_axes))?)
+            };
+            Box::new(UInputSink::new(device, abs_device))
+        }
+        Output::Trace => Box::new(TraceSink),
+        Output::Record(path) => Box::new(RecordSink::create(path, abs_axes)?),
+    };
+    *OUTPUT_DEVICE.lock().unwrap() = Some(OutputDevice { sink, held_keys: HeldKeys::default() });
 
     task::spawn(async move {
         loop {
@@ -29,11 +130,41 @@ pub async fn init_virtual_output_device(
                 Some(v) => v,
                 None => return Err(anyhow!("message channel closed unexpectedly")),
             };
-            input_device.write_event(&ev)
-                .map_err(|err| anyhow!("failed to write event into uinput device: {}", err))?;
+
+            let mut output_device = OUTPUT_DEVICE.lock().unwrap();
+            let output_device = match output_device.as_mut() {
+                Some(output_device) => output_device,
+                // shutting down
+                None => return Ok(()),
+            };
+
+            output_device.sink.write_event(&ev)?;
+            output_device.held_keys.update(&ev);
         }
-        #[allow(unreachable_code)]
-            Ok(())
     });
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_held_keys() {
+        let mut held_keys = HeldKeys::default();
+        held_keys.update(&KeyAction::new(*KEY_LEFT_CTRL, TYPE_DOWN).to_input_ev());
+        held_keys.update(&KeyAction::new(*KEY_A, TYPE_DOWN).to_input_ev());
+        held_keys.update(&KeyAction::new(*KEY_A, TYPE_REPEAT).to_input_ev());
+        held_keys.update(&SYN_REPORT.clone());
+        held_keys.update(&KeyAction::new(*KEY_B, TYPE_DOWN).to_input_ev());
+        held_keys.update(&KeyAction::new(*KEY_B, TYPE_UP).to_input_ev());
+        held_keys.update(&KeyAction::new(*KEY_C, TYPE_UP).to_input_ev());
+
+        assert_eq!(held_keys.release_events(), vec![
+            KeyAction::new(*KEY_A, TYPE_UP).to_input_ev(),
+            KeyAction::new(*KEY_LEFT_CTRL, TYPE_UP).to_input_ev(),
+            SYN_REPORT.clone(),
+        ]);
+        assert_eq!(held_keys.release_events(), vec![]);
+    }
+}
@@ -1,3 +1,5 @@
+use evdev_rs::enums::EV_REL;
+
 use crate::*;
 use messaging::*;
 use crate::cli::Configuration;
@@ -38,49 +40,288 @@ pub(crate) fn update_modifiers(state: &mut State, action: &KeyAction) {
 }
 
 pub async fn handle_stdin_ev(
-    mut state: &mut State,
+    state: &mut State,
     ev: InputEvent,
+    device: Option<Arc<DeviceInfo>>,
     mappings: &mut CompiledKeyMappings,
     ev_writer: &mut mpsc::Sender<InputEvent>,
     message_tx: &mut ExecutionMessageSender,
     window_cycle_token: usize,
-    configuration: &Configuration,
+    verbosity: i32,
 ) -> Result<()> {
-    if configuration.verbosity >= 3 {
+    if verbosity >= 3 {
         logging::print_debug(format!("input event: {}", logging::print_input_event(&ev)));
     }
 
+    state.device = device;
+
+    if let EventCode::EV_KEY(_) = ev.event_code {
+        let key = Key { event_code: ev.event_code };
+        if ev.value == TYPE_DOWN && !state.pressed_keys.contains(&key) { state.pressed_keys.push(key); }
+        if ev.value == TYPE_UP { state.pressed_keys.retain(|pressed| *pressed != key); }
+    }
+
+    if state.paused {
+        // the modifiers are still tracked, they'd be stale once resumed otherwise
+        if let EventCode::EV_KEY(_) = ev.event_code { update_modifiers(state, &KeyAction::from_input_ev(&ev)); }
+        ev_writer.send(ev).await.unwrap();
+        return Ok(());
+    }
+
     match ev.event_code {
         EventCode::EV_KEY(_) => {}
+        EventCode::EV_REL(_) => {
+            return handle_rel_ev(state, ev, mappings, ev_writer, message_tx, window_cycle_token).await;
+        }
+        EventCode::EV_ABS(_) => {
+            let (blocks, forward) = state.abs.process(&ev, state.device.as_deref(), mappings);
+            for block in blocks {
+                spawn_block(block, state, ev_writer, message_tx, window_cycle_token);
+            }
+            if forward { ev_writer.send(ev).await.unwrap(); }
+            return Ok(());
+        }
         _ => {
             ev_writer.send(ev).await.unwrap();
             return Ok(());
         }
     }
 
-    let mut from_modifiers = KeyModifierFlags::new();
-    from_modifiers.ctrl = state.modifiers.is_ctrl();
-    from_modifiers.alt = state.modifiers.is_alt();
-    from_modifiers.shift = state.modifiers.is_shift();
-    from_modifiers.meta = state.modifiers.is_meta();
+    let (actions, timer) = state.tap_hold.process(ev);
+    handle_tap_hold_actions(state, actions, timer, mappings, ev_writer, message_tx, window_cycle_token).await
+}
+
+async fn handle_tap_hold_actions(
+    state: &mut State,
+    actions: Vec<TapHoldAction>,
+    timer: Option<TapHoldTimer>,
+    mappings: &mut CompiledKeyMappings,
+    ev_writer: &mut mpsc::Sender<InputEvent>,
+    message_tx: &mut ExecutionMessageSender,
+    window_cycle_token: usize,
+) -> Result<()> {
+    if let Some(timer) = timer {
+        let message_tx = message_tx.clone();
+        task::spawn(async move {
+            tokio::time::sleep(timer.duration).await;
+            let _ = message_tx.send(ExecutionMessage::TapHoldTimeout(timer.key, timer.id)).await;
+        });
+    }
 
+    for action in actions {
+        match action {
+            TapHoldAction::Forward(ev) => {
+                let (actions, timer) = state.chords.process(ev);
+                handle_chord_actions(state, actions, timer, mappings, ev_writer, message_tx, window_cycle_token).await?;
+            }
+            TapHoldAction::Emit(action) => {
+                update_modifiers(state, &action);
+                ev_writer.send(action.to_input_ev()).await.unwrap();
+                ev_writer.send(SYN_REPORT.clone()).await.unwrap();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_chord_actions(
+    state: &mut State,
+    actions: Vec<ChordAction>,
+    timer: Option<ChordTimer>,
+    mappings: &mut CompiledKeyMappings,
+    ev_writer: &mut mpsc::Sender<InputEvent>,
+    message_tx: &mut ExecutionMessageSender,
+    window_cycle_token: usize,
+) -> Result<()> {
+    if let Some(timer) = timer {
+        let message_tx = message_tx.clone();
+        task::spawn(async move {
+            tokio::time::sleep(timer.duration).await;
+            let _ = message_tx.send(ExecutionMessage::ChordTimeout(timer.id)).await;
+        });
+    }
+
+    for action in actions {
+        match action {
+            ChordAction::Forward(ev) => {
+                let (actions, timer) = state.sequences.process(ev);
+                handle_sequence_actions(state, actions, timer, mappings, ev_writer, message_tx, window_cycle_token).await?;
+            }
+            ChordAction::Fire(block) => {
+                spawn_block(block, state, ev_writer, message_tx, window_cycle_token);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_sequence_actions(
+    state: &mut State,
+    actions: Vec<SequenceAction>,
+    timer: Option<SequenceTimer>,
+    mappings: &mut CompiledKeyMappings,
+    ev_writer: &mut mpsc::Sender<InputEvent>,
+    message_tx: &mut ExecutionMessageSender,
+    window_cycle_token: usize,
+) -> Result<()> {
+    if let Some(timer) = timer {
+        let message_tx = message_tx.clone();
+        task::spawn(async move {
+            tokio::time::sleep(timer.duration).await;
+            let _ = message_tx.send(ExecutionMessage::SequenceTimeout(timer.id)).await;
+        });
+    }
+
+    for action in actions {
+        match action {
+            SequenceAction::Forward(ev) => {
+                handle_key_ev(state, ev, mappings, ev_writer, message_tx, window_cycle_token).await?;
+            }
+            SequenceAction::Fire(block) => {
+                spawn_block(block, state, ev_writer, message_tx, window_cycle_token);
+            }
+            SequenceAction::Pending(prefix, continuations) => {
+                if let Some(callback) = state.sequences.prefix_callback_block(&prefix, &continuations) {
+                    spawn_block(callback, state, ev_writer, message_tx, window_cycle_token);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_block(
+    block: Arc<(Block, GuardedVarMap)>,
+    state: &State,
+    ev_writer: &mpsc::Sender<InputEvent>,
+    message_tx: &ExecutionMessageSender,
+    window_cycle_token: usize,
+) {
+    let mut message_tx = message_tx.clone();
+    let ev_writer = ev_writer.clone();
+    let modifier_state = state.modifiers.clone();
+    let event_device = state.device.clone();
+    let script_generation = state.script_generation;
+
+    // the place in the queue is taken right away, the task might start after one that was spawned later
+    let mut turn = if block.0.is_async() { None } else { Some(state.execution_queue.enqueue()) };
+    task::spawn(async move {
+        if let Some(turn) = &mut turn { turn.wait().await; }
+
+        let (block, var_map) = block.deref();
+        let mut amb = Ambient {
+            ev_writer_tx: ev_writer,
+            message_tx: Some(&mut message_tx),
+            window_cycle_token,
+            in_window_change_handler: false,
+            script_generation,
+            modifier_state: &modifier_state,
+            layer: None,
+            device: None,
+            event_device,
+            turn,
+        };
+
+        if let Err(err) = eval_block(&block, &var_map, &mut amb).await {
+            let _ = amb.message_tx.unwrap().send(ExecutionMessage::RuntimeError(err)).await;
+        }
+    });
+}
+
+fn spawn_mouse_keys_timer(timer: MouseKeysTimer, message_tx: &ExecutionMessageSender) {
+    let message_tx = message_tx.clone();
+    task::spawn(async move {
+        tokio::time::sleep(timer.duration).await;
+        let _ = message_tx.send(ExecutionMessage::MouseKeysTick(timer.id)).await;
+    });
+}
+
+fn spawn_timer(tick: TimerTick, message_tx: &ExecutionMessageSender) {
+    let message_tx = message_tx.clone();
+    task::spawn(async move {
+        tokio::time::sleep(tick.duration).await;
+        let _ = message_tx.send(ExecutionMessage::TimerTimeout(tick.id)).await;
+    });
+}
+
+fn modifier_flags(state: &State) -> KeyModifierFlags {
+    let mut flags = KeyModifierFlags::new();
+    flags.ctrl = state.modifiers.is_ctrl();
+    flags.alt = state.modifiers.is_alt();
+    flags.shift = state.modifiers.is_shift();
+    flags.meta = state.modifiers.is_meta();
+    flags
+}
+
+async fn handle_rel_ev(
+    state: &mut State,
+    ev: InputEvent,
+    mappings: &mut CompiledKeyMappings,
+    ev_writer: &mut mpsc::Sender<InputEvent>,
+    message_tx: &mut ExecutionMessageSender,
+    window_cycle_token: usize,
+) -> Result<()> {
+    if ev.value != 0 {
+        // relative events are matched by their direction only
+        let from_key_action = KeyActionWithMods {
+            key: Key { event_code: ev.event_code },
+            value: ev.value.signum(),
+            modifiers: modifier_flags(state),
+        };
+
+        if let Some(block) = mappings.get(&from_key_action, state.device.as_deref()) {
+            spawn_block(block, state, ev_writer, message_tx, window_cycle_token);
+            return Ok(());
+        }
+
+        // hi-res wheel events accompany the regular ones, drop them if the regular wheel is mapped
+        let low_res_code = match ev.event_code {
+            EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES) => Some(EventCode::EV_REL(EV_REL::REL_WHEEL)),
+            EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES) => Some(EventCode::EV_REL(EV_REL::REL_HWHEEL)),
+            _ => None,
+        };
+        if let Some(event_code) = low_res_code {
+            let low_res_action = KeyActionWithMods { key: Key { event_code }, ..from_key_action };
+            if mappings.get(&low_res_action, state.device.as_deref()).is_some() { return Ok(()); }
+        }
+    }
+
+    ev_writer.send(ev).await.unwrap();
+    Ok(())
+}
+
+async fn handle_key_ev(
+    mut state: &mut State,
+    ev: InputEvent,
+    mappings: &mut CompiledKeyMappings,
+    ev_writer: &mut mpsc::Sender<InputEvent>,
+    message_tx: &mut ExecutionMessageSender,
+    window_cycle_token: usize,
+) -> Result<()> {
     let from_key_action = KeyActionWithMods {
         key: Key { event_code: ev.event_code },
         value: ev.value,
-        modifiers: from_modifiers,
+        modifiers: modifier_flags(state),
     };
 
-    if let Some(block) = mappings.0.get(&from_key_action) {
-        let block = block.clone();
-        let mut message_tx = message_tx.clone();
-        let ev_writer = ev_writer.clone();
-        let modifier_state = state.modifiers.clone();
-        task::spawn(async move {
-            let (block, var_map) = block.deref();
-            let mut amb = Ambient { ev_writer_tx: ev_writer, message_tx: Some(&mut message_tx), window_cycle_token, modifier_state: &modifier_state };
+    if let Some((events, timer)) = state.mouse_keys.process(&from_key_action, state.device.as_deref(), mappings) {
+        if let Some(timer) = timer { spawn_mouse_keys_timer(timer, message_tx); }
+        for ev in events { ev_writer.send(ev).await.unwrap(); }
+        return Ok(());
+    }
 
-            eval_block(&block, &var_map, &mut amb).await;
-        });
+    if let Some(block) = mappings.get(&from_key_action, state.device.as_deref()) {
+        // the mapping might move the cursor or type text, the typed text can't be trusted anymore
+        if ev.value == TYPE_DOWN { state.hotstrings.reset(); }
+        spawn_block(block, state, ev_writer, message_tx, window_cycle_token);
+        return Ok(());
+    }
+
+    if let Some(events) = state.hotstrings.process(&ev, &state.modifiers) {
+        for ev in events { ev_writer.send(ev).await.unwrap(); }
         return Ok(());
     }
 
@@ -94,21 +335,129 @@ pub async fn handle_stdin_ev(
 
 pub async fn handle_execution_message(
     out: &mut impl Write,
+    err_out: &mut impl Write,
     current_token: usize,
     msg: ExecutionMessage,
     state: &mut State,
     mappings: &mut CompiledKeyMappings,
     window_change_handlers: &mut Vec<(Block, GuardedVarMap)>,
+    ev_writer: &mut mpsc::Sender<InputEvent>,
+    message_tx: &mut ExecutionMessageSender,
 ) {
     match msg {
         // ExecutionMessage::EatEv(action) => {
         //     state.ignore_list.ignore(&action);
         // }
-        ExecutionMessage::AddMapping(token, from, to, var_map) => {
+        ExecutionMessage::AddMapping(token, layer, device, from, to, var_map) => {
+            if token == current_token {
+                mappings.insert(layer, device, from, Arc::new((to, var_map)));
+            }
+        }
+        ExecutionMessage::PushLayer(token, layer) => {
+            if token == current_token {
+                mappings.push_layer(layer);
+            }
+        }
+        ExecutionMessage::PopLayer(token) => {
+            if token == current_token {
+                mappings.pop_layer();
+            }
+        }
+        ExecutionMessage::ToggleLayer(token, layer) => {
+            if token == current_token {
+                mappings.toggle_layer(layer);
+            }
+        }
+        ExecutionMessage::IsLayerActive(layer, tx) => {
+            tx.send(mappings.is_layer_active(&layer)).await.unwrap();
+        }
+        ExecutionMessage::AddTapHoldMapping(token, mapping) => {
             if token == current_token {
-                mappings.0.insert(from, Arc::new((to, var_map)));
+                state.tap_hold.mappings.insert(mapping.key, mapping);
             }
         }
+        ExecutionMessage::AddChordMapping(token, keys, block, var_map) => {
+            if token == current_token {
+                state.chords.insert(keys, block, var_map);
+            }
+        }
+        ExecutionMessage::SetChordWindow(window) => {
+            state.chords.window = window;
+        }
+        ExecutionMessage::ChordTimeout(id) => {
+            let actions = state.chords.timeout(id);
+            let has_replayed_events = actions.iter().any(|action| matches!(action, ChordAction::Forward(_)));
+
+            handle_chord_actions(state, actions, None, mappings, ev_writer, message_tx, current_token).await.unwrap();
+
+            if has_replayed_events { ev_writer.send(SYN_REPORT.clone()).await.unwrap(); }
+        }
+        ExecutionMessage::AddSequenceMapping(token, keys, block, var_map) => {
+            if token == current_token {
+                state.sequences.insert(keys, block, var_map);
+            }
+        }
+        ExecutionMessage::SetSequenceTimeout(timeout) => {
+            state.sequences.timeout = timeout;
+        }
+        ExecutionMessage::RegisterSequencePrefixCallback(params, block, var_map) => {
+            state.sequences.prefix_callback = Some((params, block, var_map));
+        }
+        ExecutionMessage::SequenceTimeout(id) => {
+            let actions = state.sequences.timeout(id);
+            let has_replayed_events = actions.iter().any(|action| matches!(action, SequenceAction::Forward(_)));
+
+            handle_sequence_actions(state, actions, None, mappings, ev_writer, message_tx, current_token).await.unwrap();
+
+            if has_replayed_events { ev_writer.send(SYN_REPORT.clone()).await.unwrap(); }
+        }
+        ExecutionMessage::TapHoldTimeout(key, id) => {
+            let (actions, timer) = state.tap_hold.timeout(key, id);
+            let has_replayed_events = actions.iter().any(|action| matches!(action, TapHoldAction::Forward(_)));
+
+            handle_tap_hold_actions(state, actions, timer, mappings, ev_writer, message_tx, current_token).await.unwrap();
+
+            if has_replayed_events { ev_writer.send(SYN_REPORT.clone()).await.unwrap(); }
+        }
+        ExecutionMessage::AddMouseKeyMapping(token, layer, mapping) => {
+            if token == current_token {
+                state.mouse_keys.insert(layer, mapping);
+            }
+        }
+        ExecutionMessage::SetMouseKeysConfig(setting) => {
+            state.mouse_keys.config.apply(setting);
+        }
+        ExecutionMessage::MouseKeysTick(id) => {
+            let (events, timer) = state.mouse_keys.tick(id);
+            if let Some(timer) = timer { spawn_mouse_keys_timer(timer, message_tx); }
+            for ev in events { ev_writer.send(ev).await.unwrap(); }
+        }
+        ExecutionMessage::AddAbsMapping(token, layer, device, mapping, var_map) => {
+            if token == current_token {
+                state.abs.insert(layer, device, mapping, var_map);
+            }
+        }
+        ExecutionMessage::AddHotstring(token, hotstring) => {
+            if token == current_token {
+                state.hotstrings.insert(hotstring);
+            }
+        }
+        ExecutionMessage::AddTimer(script_generation, token, in_window_change_handler, duration, repeat, block, var_map, tx) => {
+            if script_generation == state.script_generation && token == current_token {
+                let window_cycle_token = if in_window_change_handler { Some(token) } else { None };
+                let tick = state.timers.insert(duration, repeat, window_cycle_token, block, var_map);
+                let _ = tx.send(tick.id).await;
+                spawn_timer(tick, message_tx);
+            }
+        }
+        ExecutionMessage::ClearTimer(id) => {
+            state.timers.remove(id);
+        }
+        ExecutionMessage::TimerTimeout(id) => {
+            let (callback, tick) = state.timers.timeout(id, current_token);
+            if let Some(tick) = tick { spawn_timer(tick, message_tx); }
+            if let Some(callback) = callback { spawn_block(callback, state, ev_writer, message_tx, current_token); }
+        }
         ExecutionMessage::GetFocusedWindowInfo(tx) => {
             tx.send(state.active_window.clone()).await.unwrap();
         }
@@ -117,21 +466,179 @@ pub async fn handle_execution_message(
         }
         ExecutionMessage::Write(message) => {
             out.write(message.as_ref()).unwrap();
+            out.flush().unwrap();
         }
         ExecutionMessage::UpdateModifiers(action) => {
             event_handlers::update_modifiers(state, &action);
         }
-        ExecutionMessage::Exit(exit_code) => { std::process::exit(exit_code) }
+        ExecutionMessage::Exit(exit_code) => { shutdown::exit(exit_code) }
         ExecutionMessage::FatalError(err, exit_code) => {
-            eprintln!("error: {}", err);
-            std::process::exit(exit_code)
+            let _ = writeln!(err_out, "error: {}", err);
+            shutdown::exit(exit_code)
+        }
+        ExecutionMessage::RuntimeError(err) => {
+            let _ = writeln!(err_out, "error: {}", err);
+        }
+    }
+}
+
+
+/// Executes a command received through the control socket and returns the response for the client.
+pub async fn handle_control_command(
+    command: ControlCommand,
+    configuration: &Configuration,
+    state: &mut State,
+    mappings: &mut CompiledKeyMappings,
+    window_change_handlers: &mut Vec<(Block, GuardedVarMap)>,
+    window_cycle_token: &mut usize,
+    ev_writer: &mpsc::Sender<InputEvent>,
+    message_tx: &ExecutionMessageSender,
+) -> String {
+    match command {
+        ControlCommand::Status => {
+            let layers = mappings.active_layers().cloned().collect::<Vec<_>>();
+            let mut status = format!("state: {}\n", if state.paused { "paused" } else { "running" });
+            status.push_str(&format!("script: {}\n", configuration.script_path.display()));
+            status.push_str(&format!("active layers: {}\n", if layers.is_empty() { "none".to_string() } else { layers.join(", ") }));
+            if let Some(device) = &state.device {
+                status.push_str(&format!("last input device: {} ({})\n", device.name, device.path));
+            }
+            status
+        }
+        ControlCommand::Reload => {
+            match script::load_script(&configuration.script_path) {
+                Ok(script_ast) => {
+                    handle_script_change(script_ast, state, mappings, window_change_handlers, window_cycle_token, ev_writer, message_tx).await;
+                    "script reloaded\n".to_string()
+                }
+                Err(err) => format!("error: {}, the previous version of the script keeps running\n", err),
+            }
         }
+        ControlCommand::Pause => {
+            // pending chords and sequences would replay their keys through the mappings once they time out
+            flush_pending_keys(state, mappings, ev_writer, message_tx, *window_cycle_token).await.unwrap();
+
+            // keys pressed by the script, i.e. held tap-hold modifiers or '{ctrl down}', would stay down until resumed
+            release_output_keys(ev_writer).await;
+            state.tap_hold.forget_held();
+            state.mouse_keys.forget_held();
+            state.paused = true;
+            "paused\n".to_string()
+        }
+        ControlCommand::Resume => {
+            state.paused = false;
+            "resumed\n".to_string()
+        }
+        ControlCommand::ListMappings => {
+            mappings.describe().into_iter().map(|line| line + "\n").collect()
+        }
+        ControlCommand::Exit => {
+            let _ = message_tx.send(ExecutionMessage::Exit(0)).await;
+            "exiting\n".to_string()
+        }
+    }
+}
+
+/// Replays the keys held back by a pending tap-hold key, chord or sequence, i.e. before the script gets paused or
+/// replaced.
+async fn flush_pending_keys(
+    state: &mut State,
+    mappings: &mut CompiledKeyMappings,
+    ev_writer: &mpsc::Sender<InputEvent>,
+    message_tx: &ExecutionMessageSender,
+    window_cycle_token: usize,
+) -> Result<()> {
+    let mut ev_writer = ev_writer.clone();
+    let mut message_tx = message_tx.clone();
+
+    let actions = state.tap_hold.flush();
+    let mut has_replayed_events = !actions.is_empty();
+    handle_tap_hold_actions(state, actions, None, mappings, &mut ev_writer, &mut message_tx, window_cycle_token).await?;
+
+    let actions = state.chords.flush();
+    has_replayed_events |= !actions.is_empty();
+    handle_chord_actions(state, actions, None, mappings, &mut ev_writer, &mut message_tx, window_cycle_token).await?;
+
+    let actions = state.sequences.flush();
+    has_replayed_events |= !actions.is_empty();
+    handle_sequence_actions(state, actions, None, mappings, &mut ev_writer, &mut message_tx, window_cycle_token).await?;
+
+    // replayed events lost their original sync event
+    if has_replayed_events { ev_writer.send(SYN_REPORT.clone()).await.unwrap(); }
+    Ok(())
+}
+
+/// Releases all keys the output device is holding down, the device keeps working.
+async fn release_output_keys(ev_writer: &mpsc::Sender<InputEvent>) {
+    let held_keys = device::held_keys();
+    for key in held_keys.iter().rev() {
+        let _ = ev_writer.send(KeyAction::new(*key, TYPE_UP).to_input_ev()).await;
     }
+    if !held_keys.is_empty() { let _ = ev_writer.send(SYN_REPORT.clone()).await; }
 }
 
+/// Replaces the running script, the mappings of the old script stay active until all keys are released so
+/// that no keys get stuck, or until the reload timeout passed.
+pub async fn handle_script_change(
+    script_ast: Block,
+    state: &mut State,
+    mappings: &mut CompiledKeyMappings,
+    window_change_handlers: &mut Vec<(Block, GuardedVarMap)>,
+    window_cycle_token: &mut usize,
+    ev_writer: &mpsc::Sender<InputEvent>,
+    message_tx: &ExecutionMessageSender,
+) {
+    state.pending_script = Some((script_ast, time::Instant::now()));
+    apply_pending_script(state, mappings, window_change_handlers, window_cycle_token, ev_writer, message_tx).await;
+}
+
+pub async fn apply_pending_script(
+    state: &mut State,
+    mappings: &mut CompiledKeyMappings,
+    window_change_handlers: &mut Vec<(Block, GuardedVarMap)>,
+    window_cycle_token: &mut usize,
+    ev_writer: &mpsc::Sender<InputEvent>,
+    message_tx: &ExecutionMessageSender,
+) {
+    let is_expired = match &state.pending_script {
+        Some((_, since)) => since.elapsed() >= script::RELOAD_TIMEOUT,
+        None => return,
+    };
+    if !state.pressed_keys.is_empty() && !is_expired { return; }
+    let script_ast = match state.pending_script.take() {
+        Some((script_ast, _)) => script_ast,
+        None => return,
+    };
+
+    // keys buffered by a pending tap-hold key, chord or sequence would get lost, replay them through the old mappings
+    flush_pending_keys(state, mappings, ev_writer, message_tx, *window_cycle_token).await.unwrap();
+
+    // the state that would release held keys, i.e. a resolved tap-hold key, gets dropped along with the old script
+    release_output_keys(ev_writer).await;
+
+    *mappings = CompiledKeyMappings::new();
+    window_change_handlers.clear();
+    state.clear_script_state();
+
+    // mappings and timers that the old script is still about to add get dropped
+    *window_cycle_token += 1;
+    state.script_generation += 1;
+
+    let message_tx = message_tx.clone();
+    let ev_writer = ev_writer.clone();
+    let window_cycle_token = *window_cycle_token;
+    let script_generation = state.script_generation;
+    task::spawn(async move {
+        // the devices are still grabbed, keep running with whatever the script managed to set up
+        if let Err(err) = script::evaluate_script(script_ast, message_tx.clone(), ev_writer, window_cycle_token, script_generation).await {
+            let _ = message_tx.send(ExecutionMessage::RuntimeError(err)).await;
+        }
+    });
+}
 
 pub fn handle_active_window_change(ev_writer_tx: &mut mpsc::Sender<InputEvent>, message_tx: &mut ExecutionMessageSender,
-                                   window_cycle_token: usize, window_change_handlers: &mut Vec<(Block, GuardedVarMap)>) {
+                                   window_cycle_token: usize, script_generation: usize,
+                                   window_change_handlers: &mut Vec<(Block, GuardedVarMap)>) {
     for (handler, var_map) in window_change_handlers {
         let mut message_tx = message_tx.clone();
         let ev_writer_tx = ev_writer_tx.clone();
@@ -139,15 +646,25 @@ pub fn handle_active_window_change(ev_writer_tx: &mut mpsc::Sender<InputEvent>,
         let mut var_map = var_map.clone();
 
         task::spawn(async move {
-            eval_block(&handler,
-                       &mut var_map,
-                       &mut Ambient {
-                           ev_writer_tx,
-                           message_tx: Some(&mut message_tx),
-                           window_cycle_token,
-                           modifier_state: &KeyModifierState::new(),
-                       },
+            let res = eval_block(&handler,
+                                 &mut var_map,
+                                 &mut Ambient {
+                                     ev_writer_tx,
+                                     message_tx: Some(&mut message_tx),
+                                     window_cycle_token,
+                                     in_window_change_handler: true,
+                                     script_generation,
+                                     modifier_state: &KeyModifierState::new(),
+                                     layer: None,
+                                     device: None,
+                                     event_device: None,
+                                     turn: None,
+                                 },
             ).await;
+
+            if let Err(err) = res {
+                let _ = message_tx.send(ExecutionMessage::RuntimeError(err)).await;
+            }
         });
     }
 }
\ No newline at end of file
@@ -77,7 +77,8 @@ fn get_props(fd: PathBuf, reader_tx: mpsc::UnboundedSender<PathBuf>) -> Result<D
     // open listen thread
     std::thread::spawn(move || {
         read_from_device_input_fd_thread_handler(
-            device,
+            Arc::new(Mutex::new(device)),
+            None,
             |_| {
                 if start.elapsed() < time::Duration::from_millis(100) { return; }
                 start = time::Instant::now();
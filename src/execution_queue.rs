@@ -0,0 +1,109 @@
+use std::future::Future;
+
+use crate::*;
+
+/// Runs the blocks of mappings one at a time in the order they were triggered, so that the events sent by one
+/// mapping don't interleave with those of another. A block gives up its turn while it sleeps, a long 'sleep' doesn't
+/// hold up other keys.
+#[derive(Clone)]
+pub struct ExecutionQueue {
+    /// resolves once the turn of the last block in the queue is over
+    tail: Arc<Mutex<Option<oneshot::Receiver<()>>>>,
+}
+
+/// A place in the execution queue, the turn is over once it's dropped.
+pub struct ExecutionTurn {
+    queue: ExecutionQueue,
+    previous: Option<oneshot::Receiver<()>>,
+    done: Option<oneshot::Sender<()>>,
+}
+
+impl ExecutionQueue {
+    pub fn new() -> Self {
+        ExecutionQueue { tail: Arc::new(Mutex::new(None)) }
+    }
+
+    /// Takes the last place in the queue, the queue is joined right away so the order of calls is kept.
+    pub fn enqueue(&self) -> ExecutionTurn {
+        let (done_tx, done_rx) = oneshot::channel();
+        let previous = self.tail.lock().unwrap().replace(done_rx);
+        ExecutionTurn { queue: self.clone(), previous, done: Some(done_tx) }
+    }
+}
+
+impl Default for ExecutionQueue {
+    fn default() -> Self { Self::new() }
+}
+
+impl ExecutionTurn {
+    /// Waits until the turns of all blocks that were queued earlier are over.
+    pub async fn wait(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            // the sender gets dropped rather than used, which ends the turn all the same
+            let _ = previous.await;
+        }
+    }
+
+    /// Gives up the turn while the future runs and waits for a new turn at the end of the queue afterwards.
+    pub async fn release_while<F: Future>(&mut self, future: F) -> F::Output {
+        self.done.take();
+        let output = future.await;
+
+        *self = self.queue.enqueue();
+        self.wait().await;
+        output
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_execution_order() {
+        let queue = ExecutionQueue::new();
+        let log = Arc::new(Mutex::new(vec![]));
+
+        // the first block takes longest but still finishes first
+        let mut handles = vec![];
+        for (name, millis) in vec![("a", 30), ("b", 0), ("c", 10)] {
+            let mut turn = queue.enqueue();
+            let log = log.clone();
+            handles.push(task::spawn(async move {
+                turn.wait().await;
+                log.lock().unwrap().push(format!("{} start", name));
+                tokio::time::sleep(time::Duration::from_millis(millis)).await;
+                log.lock().unwrap().push(format!("{} end", name));
+            }));
+        }
+        for handle in handles { handle.await.unwrap(); }
+
+        assert_eq!(*log.lock().unwrap(), vec!["a start", "a end", "b start", "b end", "c start", "c end"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_release_while() {
+        let queue = ExecutionQueue::new();
+        let log = Arc::new(Mutex::new(vec![]));
+
+        let mut first = queue.enqueue();
+        first.wait().await;
+        let mut second = queue.enqueue();
+
+        let handle = {
+            let log = log.clone();
+            task::spawn(async move {
+                second.wait().await;
+                log.lock().unwrap().push("second");
+            })
+        };
+
+        // the second block runs while the first one sleeps, the first one waits for its turn again afterwards
+        first.release_while(tokio::time::sleep(time::Duration::from_millis(20))).await;
+        log.lock().unwrap().push("first");
+        handle.await.unwrap();
+
+        assert_eq!(*log.lock().unwrap(), vec!["second", "first"]);
+    }
+}
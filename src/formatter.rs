@@ -0,0 +1,629 @@
+use std::path::Path;
+
+use crate::*;
+use crate::parsing::parser::{self, Trivia};
+
+const INDENT: &str = "  ";
+/// lists and maps that would make a line longer than this get one item per line, so do lists and maps of lists and
+/// maps since they're usually tables
+const MAX_WIDTH: usize = 100;
+
+/// Prints a parsed script back to source in the canonical style.
+struct Printer<'a> {
+    script: &'a str,
+    trivia: Trivia,
+    /// the index of the first comment that wasn't printed yet
+    next_comment: usize,
+    /// the source offset of what is being printed, used to find the source text of key mappings
+    pos: usize,
+    /// source ranges that are printed as they were written, comments in them are part of the text
+    verbatim: Vec<(usize, usize)>,
+    indent: usize,
+    out: String,
+}
+
+/// The state of the printer, see [Printer::restore].
+struct Checkpoint {
+    len: usize,
+    next_comment: usize,
+    pos: usize,
+    verbatim: usize,
+}
+
+/// How tightly an expression binds, operands that bind less tightly than their operator need parentheses.
+fn precedence(expr: &Expr) -> u8 {
+    match expr.without_span() {
+        Expr::Init(_, _) | Expr::Assign(_, _) | Expr::AssignIndex(_, _, _) => 0,
+        Expr::Eq(_, _) | Expr::Neq(_, _) | Expr::LT(_, _) | Expr::GT(_, _) | Expr::And(_, _) | Expr::Or(_, _) => 1,
+        Expr::Add(_, _) | Expr::Sub(_, _) => 2,
+        Expr::Mul(_, _) | Expr::Div(_, _) => 3,
+        Expr::Neg(_) => 4,
+        _ => 5,
+    }
+}
+
+fn binary_operator(expr: &Expr) -> Option<(&Expr, &'static str, &Expr)> {
+    let (left, op, right) = match expr.without_span() {
+        Expr::Eq(left, right) => (left, "==", right),
+        Expr::Neq(left, right) => (left, "!=", right),
+        Expr::LT(left, right) => (left, "<", right),
+        Expr::GT(left, right) => (left, ">", right),
+        Expr::And(left, right) => (left, "&&", right),
+        Expr::Or(left, right) => (left, "||", right),
+        Expr::Add(left, right) => (left, "+", right),
+        Expr::Sub(left, right) => (left, "-", right),
+        Expr::Mul(left, right) => (left, "*", right),
+        Expr::Div(left, right) => (left, "/", right),
+        _ => return None,
+    };
+    Some((left, op, right))
+}
+
+fn is_key_mapping(expr: &Expr) -> bool {
+    matches!(expr, Expr::KeyMapping(_) | Expr::TapHoldMapping(_) | Expr::ChordMapping(_) | Expr::SequenceMapping(_) |
+        Expr::MouseKeyMapping(_) | Expr::AbsMapping(_) | Expr::Hotstring(_))
+}
+
+/// The block a key mapping maps to if it was written as a block, i.e. 'a::{ print("a"); }'.
+fn mapping_block(expr: &Expr) -> Option<&Block> {
+    let block = match expr {
+        Expr::KeyMapping(mappings) => mappings.iter().map(|mapping| &mapping.to).find(|block| block.end.is_some())?,
+        Expr::ChordMapping(mapping) => &mapping.to,
+        Expr::SequenceMapping(mapping) => &mapping.to,
+        Expr::AbsMapping(mapping) => &mapping.enter,
+        _ => return None,
+    };
+    block.end.as_ref().map(|_| block)
+}
+
+/// The statements of the block that were written in the script, mappings add statements of their own.
+fn written_statements(block: &Block) -> Vec<(&Stmt, Option<&Span>)> {
+    block.statements.iter().enumerate()
+        .filter(|(_, stmt)| !matches!(stmt, Stmt::Expr(Expr::ReleaseRestoreModifiers(_, _, _))))
+        .map(|(idx, stmt)| (stmt, block.spans.get(idx).and_then(Option::as_ref)))
+        .collect()
+}
+
+impl<'a> Printer<'a> {
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { len: self.out.len(), next_comment: self.next_comment, pos: self.pos, verbatim: self.verbatim.len() }
+    }
+
+    /// Throws away everything that was printed since the checkpoint.
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        self.out.truncate(checkpoint.len);
+        self.next_comment = checkpoint.next_comment;
+        self.pos = checkpoint.pos;
+        self.verbatim.truncate(checkpoint.verbatim);
+    }
+
+    fn current_line_width(&self) -> usize {
+        self.out[self.out.rfind('\n').map_or(0, |idx| idx + 1)..].chars().count()
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent { self.out.push_str(INDENT); }
+    }
+
+    /// Keeps a single blank line where the script had one or more before the given offset.
+    fn write_blank_line_before(&mut self, offset: usize) {
+        if self.out.is_empty() || self.out.ends_with("{\n") || self.out.ends_with("\n\n") { return; }
+
+        let whitespace = &self.script[self.script[..offset].trim_end().len()..offset];
+        if whitespace.matches('\n').count() > 1 { self.out.push('\n'); }
+    }
+
+    /// Prints the comments that come before the given offset. A comment that follows code on the same line stays at
+    /// the end of the line, the others get lines of their own.
+    fn write_comments_before(&mut self, offset: usize) {
+        while let Some((comment_offset, text)) = self.trivia.comments.get(self.next_comment).cloned() {
+            if comment_offset >= offset { break; }
+            self.next_comment += 1;
+
+            if self.verbatim.iter().any(|(start, end)| (*start..*end).contains(&comment_offset)) { continue; }
+
+            let line_start = self.script[..comment_offset].rfind('\n').map_or(0, |idx| idx + 1);
+            let is_own_line = self.script[line_start..comment_offset].trim().is_empty();
+
+            if is_own_line || !self.out.ends_with('\n') {
+                self.write_blank_line_before(comment_offset);
+                self.write_indent();
+            } else {
+                self.out.pop();
+                self.out.push(' ');
+            }
+            self.out.push_str(&text);
+            self.out.push('\n');
+        }
+    }
+
+    fn write_statements(&mut self, statements: &[(&Stmt, Option<&Span>)]) {
+        for (stmt, span) in statements {
+            if let Some(span) = span {
+                let offset = span.offset_in(self.script);
+                self.write_comments_before(offset);
+                self.write_blank_line_before(offset);
+                self.pos = offset;
+            }
+
+            self.write_indent();
+            self.write_statement(stmt);
+            self.out.push('\n');
+        }
+    }
+
+    fn write_block(&mut self, block: &Block) {
+        let statements = written_statements(block);
+        let end = block.end.as_ref().map_or(self.pos, |end| end.offset_in(self.script));
+        let has_comments = self.trivia.comments.get(self.next_comment).is_some_and(|(offset, _)| *offset < end);
+
+        if statements.is_empty() && !has_comments {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.indent += 1;
+        self.write_statements(&statements);
+        self.write_comments_before(end);
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    fn write_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expr) => {
+                self.write_expr(expr);
+                self.out.push(';');
+            }
+            Stmt::Block(block) => self.write_block(block),
+            Stmt::If(pairs, else_block) => {
+                for (idx, (condition, block)) in pairs.iter().enumerate() {
+                    if idx > 0 { self.out.push_str(" else "); }
+                    self.out.push_str("if (");
+                    self.write_expr(condition);
+                    self.out.push_str(") ");
+                    self.write_block(block);
+                }
+                if let Some(block) = else_block {
+                    self.out.push_str(" else ");
+                    self.write_block(block);
+                }
+            }
+            Stmt::For(init, condition, step, block) => {
+                self.out.push_str("for (");
+                self.write_expr(init);
+                self.out.push_str("; ");
+                self.write_expr(condition);
+                self.out.push_str("; ");
+                self.write_expr(step);
+                self.out.push_str(") ");
+                self.write_block(block);
+            }
+            Stmt::ForIn(name, list, block) => {
+                self.out.push_str(&format!("for ({} in ", name));
+                self.write_expr(list);
+                self.out.push_str(") ");
+                self.write_block(block);
+            }
+            Stmt::While(condition, block) => {
+                self.out.push_str("while (");
+                self.write_expr(condition);
+                self.out.push_str(") ");
+                self.write_block(block);
+            }
+            Stmt::Loop(block) => {
+                self.out.push_str("loop ");
+                self.write_block(block);
+            }
+            Stmt::Layer(name, block) => {
+                self.out.push_str(&format!("layer {} ", name));
+                self.write_block(block);
+            }
+            Stmt::Device(pattern, block) => {
+                self.out.push_str(&format!("device(\"{}\") ", pattern));
+                self.write_block(block);
+            }
+            Stmt::Return(expr) => {
+                self.out.push_str("return ");
+                self.write_expr(expr);
+                self.out.push(';');
+            }
+            Stmt::Continue => self.out.push_str("continue;"),
+            Stmt::Break => self.out.push_str("break;"),
+        }
+    }
+
+    /// Writes an operand, with parentheses if it binds less tightly than required.
+    fn write_operand(&mut self, expr: &Expr, min_precedence: u8) {
+        if precedence(expr) < min_precedence {
+            self.out.push('(');
+            self.write_expr(expr);
+            self.out.push(')');
+        } else {
+            self.write_expr(expr);
+        }
+    }
+
+    fn write_expr(&mut self, expr: &Expr) {
+        let expr = expr.without_span();
+        if let Some((left, op, right)) = binary_operator(expr) {
+            let precedence = precedence(expr);
+            // operators are left associative
+            self.write_operand(left, precedence);
+            self.out.push_str(&format!(" {} ", op));
+            self.write_operand(right, precedence + 1);
+            return;
+        }
+
+        if is_key_mapping(expr) {
+            self.write_key_mapping(expr);
+            return;
+        }
+
+        match expr {
+            Expr::Neg(expr) => {
+                self.out.push('!');
+                // '!{' would start a key action
+                let min_precedence = if matches!(**expr, Expr::Map(_)) || is_key_mapping(expr) { u8::MAX } else { 4 };
+                self.write_operand(expr, min_precedence);
+            }
+            Expr::Init(name, value) => {
+                self.out.push_str(&format!("let {} = ", name));
+                self.write_expr(value);
+            }
+            Expr::Assign(name, value) => {
+                self.out.push_str(&format!("{} = ", name));
+                self.write_expr(value);
+            }
+            Expr::AssignIndex(target, index, value) => {
+                self.write_operand(target, 5);
+                self.out.push('[');
+                self.write_expr(index);
+                self.out.push_str("] = ");
+                self.write_expr(value);
+            }
+            Expr::Name(name) => self.out.push_str(name),
+            Expr::Value(ValueType::String(value)) => self.out.push_str(&format!("\"{}\"", value)),
+            Expr::Value(value) => self.out.push_str(&value.to_string()),
+            Expr::Lambda(params, block) => {
+                self.out.push_str(&format!("|{}| ", params.join(", ")));
+                self.write_block(block);
+            }
+            Expr::List(items) => {
+                let items: Vec<(Option<&str>, &Expr)> = items.iter().map(|item| (None, item)).collect();
+                self.write_items("[", &items, "]");
+            }
+            Expr::Map(entries) => {
+                let entries: Vec<(Option<&str>, &Expr)> = entries.iter()
+                    .map(|(key, value)| (Some(key.as_str()), value))
+                    .collect();
+                self.write_items("{", &entries, "}");
+            }
+            Expr::Index(collection, index) => {
+                self.write_operand(collection, 5);
+                self.out.push('[');
+                self.write_expr(index);
+                self.out.push(']');
+            }
+            Expr::FunctionCall(name, args) => {
+                self.out.push_str(name);
+                self.out.push('(');
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 { self.out.push_str(", "); }
+                    self.write_expr(arg);
+                }
+                self.out.push(')');
+            }
+            // only created by the parser as part of key mappings
+            _ => {}
+        }
+    }
+
+    /// Writes the items of a list or map, on a single line if they fit and one per line otherwise.
+    fn write_items(&mut self, open: &str, items: &[(Option<&str>, &Expr)], close: &str) {
+        let checkpoint = self.checkpoint();
+        let is_table = items.len() > 1 && items.iter().any(|(_, value)| matches!(value, Expr::List(_) | Expr::Map(_)));
+
+        self.out.push_str(open);
+        for (idx, (key, value)) in items.iter().enumerate() {
+            if idx > 0 { self.out.push_str(", "); }
+            if let Some(key) = key { self.out.push_str(&format!("\"{}\": ", key)); }
+            self.write_expr(value);
+        }
+        self.out.push_str(close);
+
+        let is_single_line = !self.out[checkpoint.len..].contains('\n');
+        if !is_single_line || (!is_table && self.current_line_width() <= MAX_WIDTH) { return; }
+
+        self.restore(checkpoint);
+        self.out.push_str(open);
+        self.out.push('\n');
+        self.indent += 1;
+        for (key, value) in items {
+            self.write_indent();
+            if let Some(key) = key { self.out.push_str(&format!("\"{}\": ", key)); }
+            self.write_expr(value);
+            self.out.push_str(",\n");
+        }
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push_str(close);
+    }
+
+    /// Writes a key mapping the way it was written, only the block it maps to gets formatted.
+    fn write_key_mapping(&mut self, expr: &Expr) {
+        let (start, end) = match self.trivia.mappings.iter().find(|(start, _)| *start >= self.pos) {
+            Some(range) => *range,
+            None => return,
+        };
+        let text = &self.script[start..end];
+
+        let block = match mapping_block(expr) {
+            Some(block) => block,
+            None => {
+                self.out.push_str(text);
+                self.verbatim.push((start, end));
+                self.pos = end;
+                return;
+            }
+        };
+
+        // the trigger ends at the '::' the block follows
+        let trigger_len = text.match_indices("::")
+            .map(|(idx, _)| idx + 2)
+            .find(|idx| {
+                let rest = text[*idx..].trim_start();
+                rest.strip_prefix("async").map_or(rest, str::trim_start).starts_with('{')
+            })
+            .unwrap_or(0);
+        self.out.push_str(&text[..trigger_len]);
+        self.verbatim.push((start, start + trigger_len));
+        self.pos = start + trigger_len;
+        if block.is_async { self.out.push_str("async "); }
+
+        // a single statement stays on the same line as the trigger, i.e. '{capslock up}::{ layer_pop(); }'
+        let statements = written_statements(block);
+        let block_end = block.end.as_ref().map_or(end, |span| span.offset_in(self.script));
+        let has_comments = self.trivia.comments.get(self.next_comment).is_some_and(|(offset, _)| *offset < block_end);
+        if let (false, [(stmt @ Stmt::Expr(_), _)]) = (has_comments, statements.as_slice()) {
+            let checkpoint = self.checkpoint();
+            self.out.push_str("{ ");
+            self.write_statement(stmt);
+            self.out.push_str(" }");
+
+            if !self.out[checkpoint.len..].contains('\n') && self.current_line_width() < MAX_WIDTH {
+                self.pos = end;
+                return;
+            }
+            self.restore(checkpoint);
+        }
+
+        self.write_block(block);
+        self.pos = end;
+    }
+}
+
+/// Formats the script in the canonical style, comments are kept where they are. Parse errors are reported on stderr.
+pub fn format_script(raw_script: &str) -> Result<String> {
+    let parse = |raw_script| parser::parse_with_trivia(raw_script).map_err(|errors| {
+        for err in &errors {
+            eprintln!("{}\n", err);
+        }
+        anyhow!("parsing failed with {} error(s)", errors.len())
+    });
+
+    let (block, trivia) = parse(raw_script)?;
+    let mut printer = Printer {
+        script: raw_script,
+        trivia,
+        next_comment: 0,
+        pos: 0,
+        verbatim: vec![],
+        indent: 0,
+        out: String::new(),
+    };
+
+    printer.write_statements(&written_statements(&block));
+    printer.write_comments_before(raw_script.len());
+    let formatted = printer.out;
+
+    // formatting must never change what the script does or lose a comment
+    let (formatted_block, formatted_trivia) = parse(&formatted)?;
+    let comments = |trivia: &Trivia| trivia.comments.iter().map(|(_, text)| text.clone()).collect::<Vec<_>>();
+    if formatted_block != block || comments(&formatted_trivia) != comments(&printer.trivia) {
+        return Err(anyhow!("formatting would change the meaning of the script, the script was left as it is"));
+    }
+
+    Ok(formatted)
+}
+
+/// Formats the script at the given path in place. In check mode the script is only compared against its formatted
+/// version. Returns whether the script was formatted successfully or, in check mode, is already formatted.
+pub fn format_file(script_path: &Path, check: bool) -> bool {
+    let res = fs::read_to_string(script_path)
+        .map_err(|err| anyhow!("failed to read script file '{}': {}", script_path.display(), err))
+        .and_then(|raw_script| Ok((format_script(&raw_script)?, raw_script)));
+
+    let (formatted, raw_script) = match res {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+
+    if formatted == raw_script { return true; }
+
+    if check {
+        eprintln!("'{}' is not formatted, run 'map2 fmt {}' to format it", script_path.display(), script_path.display());
+        return false;
+    }
+
+    if let Err(err) = fs::write(script_path, formatted) {
+        eprintln!("failed to write script file '{}': {}", script_path.display(), err);
+        return false;
+    }
+    true
+}
+
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_format_statements() {
+        let script = indoc! {r#"
+            let a=3;
+            if(a==3){print("3");}else if (a == 4){
+            print("4");
+            }else{}
+            for(let i=0;i<5;i=i+1){ continue; }
+            for(x in [1,2]){print(x);}
+            while(a<10){a=a+1;}
+            loop{break;}
+            layer nav{
+            let f=|x,y|{return x+y;};
+            }
+            device("keyboard"){xs[0][1]=!true;}
+        "#};
+
+        assert_eq!(format_script(script).unwrap(), indoc! {r#"
+            let a = 3;
+            if (a == 3) {
+              print("3");
+            } else if (a == 4) {
+              print("4");
+            } else {}
+            for (let i = 0; i < 5; i = i + 1) {
+              continue;
+            }
+            for (x in [1, 2]) {
+              print(x);
+            }
+            while (a < 10) {
+              a = a + 1;
+            }
+            loop {
+              break;
+            }
+            layer nav {
+              let f = |x, y| {
+                return x + y;
+              };
+            }
+            device("keyboard") {
+              xs[0][1] = !true;
+            }
+        "#});
+    }
+
+    #[test]
+    fn test_format_parentheses() {
+        assert_eq!(format_script("let a = (1 + 2) * (3 - (4 - 5));").unwrap(), "let a = (1 + 2) * (3 - (4 - 5));\n");
+        assert_eq!(format_script("let a = ((1 + 2) + 3) * 2;").unwrap(), "let a = (1 + 2 + 3) * 2;\n");
+        assert_eq!(format_script("let a = !(b == (c || d));").unwrap(), "let a = !(b == (c || d));\n");
+        assert_eq!(format_script("let a = (f(1))[0];").unwrap(), "let a = f(1)[0];\n");
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let script = indoc! {r#"
+            // header
+
+
+            let a = 1; // trailing
+            /* before */ print(a);
+            if (a == 1) { // opening
+              // inside
+
+              print(a);
+              // last
+            }
+            f12::{
+              // mapping
+            };
+            // end
+        "#};
+
+        assert_eq!(format_script(script).unwrap(), indoc! {r#"
+            // header
+
+            let a = 1; // trailing
+            /* before */
+            print(a);
+            if (a == 1) { // opening
+              // inside
+
+              print(a);
+              // last
+            }
+            f12::{
+              // mapping
+            };
+            // end
+        "#});
+    }
+
+    #[test]
+    fn test_format_key_mappings() {
+        let script = indoc! {r#"
+            a::b;
+            !{capslock down}::"hello{enter}";
+            j+k::{print("chord");};
+            {f13},g::{
+            print("one");
+            print("two");
+            };
+            capslock::tap_hold(esc,  ctrl);
+            :*:btw::by the way;
+            layer nav { h::left; }
+            f1::async   {sleep(100);};
+        "#};
+
+        assert_eq!(format_script(script).unwrap(), indoc! {r#"
+            a::b;
+            !{capslock down}::"hello{enter}";
+            j+k::{ print("chord"); };
+            {f13},g::{
+              print("one");
+              print("two");
+            };
+            capslock::tap_hold(esc,  ctrl);
+            :*:btw::by the way;
+            layer nav {
+              h::left;
+            }
+            f1::async { sleep(100); };
+        "#});
+    }
+
+    #[test]
+    fn test_format_collections() {
+        let long = format!("let xs = [\"{}\", \"{}\"];", "a".repeat(50), "b".repeat(50));
+        assert_eq!(format_script(&long).unwrap(), format!("let xs = [\n  \"{}\",\n  \"{}\",\n];\n", "a".repeat(50), "b".repeat(50)));
+
+        assert_eq!(format_script("let m = {\"a\": 1,\n \"b\": [1, 2],};").unwrap(), "let m = {\n  \"a\": 1,\n  \"b\": [1, 2],\n};\n");
+        assert_eq!(format_script("let m = { \"a\" : 1 };").unwrap(), "let m = {\"a\": 1};\n");
+    }
+
+    #[test]
+    fn test_format_parse_error() {
+        assert!(format_script("let a = ;\n").is_err());
+    }
+
+    #[test]
+    fn test_examples_are_stable() {
+        for entry in fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(true, |ext| ext != "m2") { continue; }
+
+            let formatted = format_script(&fs::read_to_string(&path).unwrap()).unwrap();
+            assert_eq!(format_script(&formatted).unwrap(), formatted, "{}", path.display());
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::parsing::parser::parse_key_sequence;
+use crate::*;
+
+/// Characters that complete a hotstring unless it fires immediately.
+const END_CHARS: &str = "-()[]{}':;\"/\\,.?! \n\t";
+
+/// The typed text is only kept for as long as it can still be part of a hotstring.
+const BUFFER_LIMIT: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hotstring {
+    pub abbreviation: String,
+    pub replacement: String,
+    /// fire as soon as the abbreviation is typed instead of waiting for an end character
+    pub immediate: bool,
+    /// fire even if the abbreviation is typed in the middle of a word
+    pub inside_word: bool,
+    /// match the case of the abbreviation exactly instead of adapting the replacement to the typed case
+    pub case_sensitive: bool,
+}
+
+impl Hotstring {
+    /// Checks whether the typed text ends with the abbreviation and returns the typed abbreviation.
+    fn find_in<'a>(&self, typed: &'a str) -> Option<&'a str> {
+        let len = self.abbreviation.chars().count();
+        let start = typed.char_indices().rev().nth(len.checked_sub(1)?)?.0;
+        let candidate = &typed[start..];
+
+        let is_match = if self.case_sensitive {
+            candidate == self.abbreviation
+        } else {
+            candidate.to_lowercase() == self.abbreviation.to_lowercase()
+        };
+        if !is_match { return None; }
+
+        let is_word_start = typed[..start].chars().last().map(|ch| END_CHARS.contains(ch)).unwrap_or(true);
+        if !self.inside_word && !is_word_start { return None; }
+
+        Some(candidate)
+    }
+
+    /// Adapts the replacement to the case the abbreviation was typed in, i.e. 'BTW' -> 'BY THE WAY'.
+    fn conform_case(&self, typed: &str) -> String {
+        if self.case_sensitive { return self.replacement.clone(); }
+
+        let mut letters = typed.chars().filter(|ch| ch.is_alphabetic());
+        let first_upper = match letters.next() {
+            Some(first) => first.is_uppercase(),
+            None => return self.replacement.clone(),
+        };
+        let rest: Vec<char> = letters.collect();
+
+        if first_upper && !rest.is_empty() && rest.iter().all(|ch| ch.is_uppercase()) {
+            return self.replacement.to_uppercase();
+        }
+        if first_upper {
+            let mut chars = self.replacement.chars();
+            return match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            };
+        }
+        self.replacement.clone()
+    }
+}
+
+pub struct HotstringState {
+    hotstrings: Vec<Hotstring>,
+    buffer: String,
+    /// keys whose press completed an immediate hotstring, the release gets swallowed
+    consumed: Vec<Key>,
+}
+
+impl HotstringState {
+    pub fn new() -> Self {
+        HotstringState { hotstrings: vec![], buffer: String::new(), consumed: vec![] }
+    }
+
+    pub fn insert(&mut self, hotstring: Hotstring) {
+        self.hotstrings.retain(|other| other.abbreviation != hotstring.abbreviation);
+        self.hotstrings.push(hotstring);
+    }
+
+    /// Forgets the typed text, i.e. after the cursor was moved.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Watches unmapped key events, returns the events to send instead of the given one if a hotstring fired.
+    pub fn process(&mut self, ev: &InputEvent, modifiers: &KeyModifierState) -> Option<Vec<InputEvent>> {
+        let key = Key { event_code: ev.event_code };
+
+        if let Some(idx) = self.consumed.iter().position(|consumed| *consumed == key) {
+            if ev.value == TYPE_UP { self.consumed.remove(idx); }
+            return Some(vec![]);
+        }
+
+        if ev.value == TYPE_UP || self.hotstrings.is_empty() || is_modifier(&key) { return None; }
+
+        if key == *KEY_BACKSPACE {
+            self.buffer.pop();
+            return None;
+        }
+
+        let ch = match typed_char(&key, modifiers.is_shift()) {
+            Some(ch) if !modifiers.is_ctrl() && !modifiers.is_alt() && !modifiers.is_meta() => ch,
+            _ => {
+                self.buffer.clear();
+                return None;
+            }
+        };
+
+        if END_CHARS.contains(ch) {
+            let found = self.hotstrings.iter()
+                .filter(|hotstring| !hotstring.immediate)
+                .find_map(|hotstring| hotstring.find_in(&self.buffer).map(|typed| (hotstring, typed)));
+
+            if let Some((hotstring, typed)) = found {
+                // the end character is typed after the replacement
+                let mut out = expand(hotstring, typed, typed.chars().count(), modifiers);
+                out.push(ev.clone());
+                out.push(SYN_REPORT.clone());
+                self.buffer.clear();
+                self.buffer.push(ch);
+                return Some(out);
+            }
+        }
+
+        self.buffer.push(ch);
+        if self.buffer.chars().count() > BUFFER_LIMIT { self.buffer.remove(0); }
+
+        let found = self.hotstrings.iter()
+            .filter(|hotstring| hotstring.immediate)
+            .find_map(|hotstring| hotstring.find_in(&self.buffer).map(|typed| (hotstring, typed)));
+
+        if let Some((hotstring, typed)) = found {
+            // the last character never reached the output, only the ones before it need to be erased
+            let out = expand(hotstring, typed, typed.chars().count() - 1, modifiers);
+            self.buffer.clear();
+            self.consumed.push(key);
+            return Some(out);
+        }
+
+        None
+    }
+}
+
+impl Default for HotstringState {
+    fn default() -> Self { Self::new() }
+}
+
+fn expand(hotstring: &Hotstring, typed: &str, erase: usize, modifiers: &KeyModifierState) -> Vec<InputEvent> {
+    let mut out = vec![];
+
+    // held modifiers, i.e. 'shift' for typing a '?' as the end character, would change what the replacement types
+    let held_modifiers = modifiers.held_keys();
+    for key in &held_modifiers { out.push(KeyAction::new(*key, TYPE_UP).to_input_ev()); }
+    if !held_modifiers.is_empty() { out.push(SYN_REPORT.clone()); }
+
+    for _ in 0..erase {
+        out.push(KeyAction::new(*KEY_BACKSPACE, TYPE_DOWN).to_input_ev());
+        out.push(SYN_REPORT.clone());
+        out.push(KeyAction::new(*KEY_BACKSPACE, TYPE_UP).to_input_ev());
+        out.push(SYN_REPORT.clone());
+    }
+
+    // replacements are validated when parsing, changing the case can't make them invalid
+    for action in parse_key_sequence(&hotstring.conform_case(typed)).unwrap_or_default() {
+        out.push(action.to_input_ev());
+        out.push(SYN_REPORT.clone());
+    }
+
+    for key in &held_modifiers { out.push(KeyAction::new(*key, TYPE_DOWN).to_input_ev()); }
+    if !held_modifiers.is_empty() { out.push(SYN_REPORT.clone()); }
+    out
+}
+
+fn is_modifier(key: &Key) -> bool {
+    [*KEY_LEFT_CTRL, *KEY_RIGHT_CTRL, *KEY_LEFT_ALT, *KEY_RIGHT_ALT, *KEY_LEFT_SHIFT, *KEY_RIGHT_SHIFT, *KEY_LEFT_META, *KEY_RIGHT_META]
+        .contains(key)
+}
+
+/// The character a key types on a US layout.
+fn typed_char(key: &Key, shift: bool) -> Option<char> {
+    let name = key.event_code.to_string();
+    let name = name.strip_prefix("KEY_")?;
+
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        if ch.is_ascii_alphabetic() {
+            return Some(if shift { ch } else { ch.to_ascii_lowercase() });
+        }
+        if let Some(digit) = ch.to_digit(10) {
+            return Some(if shift { ")!@#$%^&*(".chars().nth(digit as usize)? } else { ch });
+        }
+    }
+
+    let (plain, shifted) = match name {
+        "SPACE" => (' ', ' '),
+        "ENTER" | "KPENTER" => ('\n', '\n'),
+        "TAB" => ('\t', '\t'),
+        "MINUS" => ('-', '_'),
+        "EQUAL" => ('=', '+'),
+        "LEFTBRACE" => ('[', '{'),
+        "RIGHTBRACE" => (']', '}'),
+        "SEMICOLON" => (';', ':'),
+        "APOSTROPHE" => ('\'', '"'),
+        "GRAVE" => ('`', '~'),
+        "BACKSLASH" => ('\\', '|'),
+        "COMMA" => (',', '<'),
+        "DOT" => ('.', '>'),
+        "SLASH" => ('/', '?'),
+        _ => return None,
+    };
+    Some(if shift { shifted } else { plain })
+}
@@ -85,6 +85,16 @@ impl KeyModifierState {
     pub fn is_alt(&self) -> bool { self.left_alt || self.right_alt }
     pub fn is_shift(&self) -> bool { self.left_shift || self.right_shift }
     pub fn is_meta(&self) -> bool { self.left_meta || self.right_meta }
+
+    /// The modifier keys that are held down.
+    pub fn held_keys(&self) -> Vec<Key> {
+        [
+            (self.left_ctrl, *KEY_LEFT_CTRL), (self.right_ctrl, *KEY_RIGHT_CTRL),
+            (self.left_shift, *KEY_LEFT_SHIFT), (self.right_shift, *KEY_RIGHT_SHIFT),
+            (self.left_alt, *KEY_LEFT_ALT), (self.right_alt, *KEY_RIGHT_ALT),
+            (self.left_meta, *KEY_LEFT_META), (self.right_meta, *KEY_RIGHT_META),
+        ].iter().filter(|(is_down, _)| *is_down).map(|(_, key)| *key).collect()
+    }
 }
 
 
@@ -0,0 +1,546 @@
+use std::io::BufRead;
+
+use serde_json::{json, Value};
+
+use crate::*;
+use crate::check::{check_script, Severity};
+use crate::parsing::{lexer, parser};
+use crate::suggestion::key_names;
+
+lazy_static! {
+    /// The signature and description of each documented built-in function, taken from the README so there's only
+    /// one place to keep them up to date.
+    static ref BUILTIN_DOCS: HashMap<&'static str, (&'static str, String)> = {
+        let readme = include_str!("../README.md");
+        let section = readme.split("### List of built-in functions").nth(1).unwrap_or("");
+        let section = section.split("\n## ").next().unwrap_or("");
+
+        section.split("\n#### ").skip(1)
+            .map(|entry| {
+                let (signature, description) = entry.split_at(entry.find('\n').unwrap_or(entry.len()));
+                let name = &signature[..signature.find('(').unwrap_or(signature.len())];
+                (name, (signature, description.trim().to_string()))
+            })
+            .collect()
+    };
+}
+
+// https://microsoft.github.io/language-server-protocol/specifications/specification-current/
+const ERROR_PARSE: i64 = -32700;
+const ERROR_METHOD_NOT_FOUND: i64 = -32601;
+const DIAGNOSTIC_ERROR: i64 = 1;
+const DIAGNOSTIC_WARNING: i64 = 2;
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_VARIABLE: i64 = 6;
+const COMPLETION_CONSTANT: i64 = 21;
+
+/// Reads a message framed by a 'Content-Length' header, `None` once the input is closed.
+fn read_message(input: &mut impl BufRead) -> Result<Option<String>> {
+    let mut content_length = None;
+
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 { return Ok(None); }
+
+        let line = line.trim_end();
+        if line.is_empty() { break; }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            content_length = Some(value.trim().parse::<usize>()
+                .map_err(|_| anyhow!("invalid content length '{}'", value.trim()))?);
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| anyhow!("message without a content length"))?;
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+    Ok(Some(String::from_utf8(content)?))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Converts a byte offset in the text to a position, which counts characters in UTF-16 code units.
+fn position_at(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count();
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+/// Converts a position to a byte offset in the text, positions past the end of a line refer to the end of the line.
+fn offset_at(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+
+    let line_start = match line {
+        0 => 0,
+        _ => match text.match_indices('\n').nth(line - 1) {
+            Some((idx, _)) => idx + 1,
+            None => return text.len(),
+        },
+    };
+
+    let mut utf16_offset = 0;
+    for (idx, ch) in text[line_start..].char_indices() {
+        if utf16_offset >= character || ch == '\n' { return line_start + idx; }
+        utf16_offset += ch.len_utf16();
+    }
+    text.len()
+}
+
+fn is_word_char(ch: char) -> bool { ch.is_alphanumeric() || ch == '_' }
+
+/// The byte range of the word at the given offset.
+fn word_at(text: &str, offset: usize) -> (usize, usize) {
+    let start = text[..offset].rfind(|ch| !is_word_char(ch)).map_or(0, |idx| idx + 1);
+    let end = text[offset..].find(|ch| !is_word_char(ch)).map_or(text.len(), |idx| offset + idx);
+    (start, end)
+}
+
+/// A variable that's defined with 'let', as a lambda parameter or as a for-in loop variable.
+struct Definition {
+    name: String,
+    offset: usize,
+    /// the byte range of the block the variable can be used in
+    scope: (usize, usize),
+}
+
+/// Finds the variable definitions in the text. This works on the text rather than the parsed script since the script
+/// only knows where statements start and still needs to work while the script has mistakes in it.
+fn definitions(text: &str) -> Vec<Definition> {
+    // the start of each open block along with the definitions that belong to it
+    let mut blocks: Vec<(usize, Vec<(String, usize)>)> = vec![(0, vec![])];
+    // definitions that belong to the next block that gets opened, i.e. lambda parameters
+    let mut pending: Vec<(String, usize)> = vec![];
+    let mut definitions = vec![];
+    let mut prev_word = "";
+    // the end of the word or lambda parameters that were already handled
+    let mut skip_until = 0;
+
+    let next_word = |from: usize| -> Option<(usize, &str)> {
+        let start = from + text[from..].find(|ch: char| !ch.is_whitespace())?;
+        let (word_start, word_end) = word_at(text, start);
+        if word_start != start || word_end == start { return None; }
+        Some((start, &text[start..word_end]))
+    };
+
+    for (i, ch) in lexer::code_bytes(text) {
+        if i < skip_until { continue; }
+
+        match ch {
+            b'{' => blocks.push((i, std::mem::take(&mut pending))),
+            b'}' if blocks.len() > 1 => {
+                let (start, names) = blocks.pop().unwrap();
+                definitions.extend(names.into_iter().map(|(name, offset)| Definition { name, offset, scope: (start, i + 1) }));
+            }
+            b'|' => {
+                // lambda parameters, i.e. '|a, b|{'
+                let end = i + 1 + text[i + 1..].find('|').unwrap_or(text.len() - i - 1);
+                let params = text.get(i + 1..end).unwrap_or("");
+                let is_lambda = text.get(end + 1..).is_some_and(|rest| rest.trim_start().starts_with('{')) &&
+                    params.chars().all(|ch| is_word_char(ch) || ch == ',' || ch.is_whitespace());
+                if is_lambda {
+                    let mut offset = i + 1;
+                    for param in params.split(',') {
+                        let trimmed = param.trim();
+                        if !trimmed.is_empty() {
+                            pending.push((trimmed.to_string(), offset + param.find(trimmed).unwrap()));
+                        }
+                        offset += param.len() + 1;
+                    }
+                    skip_until = end + 1;
+                }
+            }
+            // only the first byte of a multi-byte character starts it, i.e. identifiers like 'é'
+            _ if text.is_char_boundary(i) && text[i..].chars().next().is_some_and(is_word_char) => {
+                let (_, end) = word_at(text, i);
+                let word = &text[i..end];
+
+                if word == "let" {
+                    if let Some((offset, name)) = next_word(end) {
+                        blocks.last_mut().unwrap().1.push((name.to_string(), offset));
+                    }
+                }
+                // for-in loop variables, i.e. 'for (key in keys){'
+                if prev_word == "for" && next_word(end).is_some_and(|(_, next)| next == "in") {
+                    pending.push((word.to_string(), i));
+                }
+
+                prev_word = word;
+                skip_until = end;
+            }
+            _ => {}
+        }
+    }
+
+    for (start, names) in blocks {
+        definitions.extend(names.into_iter().map(|(name, offset)| Definition { name, offset, scope: (start, text.len()) }));
+    }
+    definitions
+}
+
+/// The definition a use of a variable refers to. The innermost definition wins, within a block the latest definition
+/// before the use, or the first one after it since functions and mappings can use variables that are defined later.
+fn find_definition<'a>(definitions: &'a [Definition], name: &str, offset: usize) -> Option<&'a Definition> {
+    let candidates: Vec<&Definition> = definitions.iter()
+        .filter(|definition| definition.name == name && definition.scope.0 <= offset && offset <= definition.scope.1)
+        .collect();
+    let innermost = candidates.iter().map(|definition| definition.scope.0).max()?;
+    let candidates: Vec<&Definition> = candidates.into_iter().filter(|definition| definition.scope.0 == innermost).collect();
+
+    candidates.iter().filter(|definition| definition.offset <= offset).max_by_key(|definition| definition.offset)
+        .or_else(|| candidates.iter().min_by_key(|definition| definition.offset))
+        .copied()
+}
+
+fn builtin_markdown(name: &str) -> Option<String> {
+    if !BUILTIN_FUNCTIONS.iter().any(|(builtin, _, _)| *builtin == name) { return None; }
+
+    Some(match BUILTIN_DOCS.get(name) {
+        Some((signature, description)) => format!("```\n{}\n```\n\n{}", signature, description),
+        None => format!("```\n{}(...)\n```\n\nbuilt-in function", name),
+    })
+}
+
+/// Serves the language server protocol on the given input and output until the client sends 'exit'.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> Result<()> {
+    let mut server = LanguageServer { documents: HashMap::new() };
+
+    while let Some(content) = read_message(&mut input)? {
+        let message: Value = match serde_json::from_str(&content) {
+            Ok(message) => message,
+            Err(err) => {
+                let error = json!({ "code": ERROR_PARSE, "message": err.to_string() });
+                write_message(&mut output, &json!({ "jsonrpc": "2.0", "id": null, "error": error }))?;
+                continue;
+            }
+        };
+
+        if message["method"] == "exit" { return Ok(()); }
+
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+
+    Ok(())
+}
+
+struct LanguageServer {
+    /// the text of each open document by its URI
+    documents: HashMap<String, String>,
+}
+
+impl LanguageServer {
+    /// Handles a request or a notification, returns the messages to send back.
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            // responses to requests of the server, it doesn't send any
+            None => return vec![],
+        };
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    // the client sends the full text on every change
+                    "textDocumentSync": 1,
+                    "completionProvider": {},
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "map2-lsp" },
+            }),
+            "shutdown" => Value::Null,
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("").to_string();
+                self.documents.insert(uri.clone(), text);
+                return vec![self.diagnostics(&uri)];
+            }
+            "textDocument/didChange" => {
+                if let Some(text) = params["contentChanges"].as_array().and_then(|changes| changes.last()) {
+                    self.documents.insert(uri.clone(), text["text"].as_str().unwrap_or("").to_string());
+                }
+                return vec![self.diagnostics(&uri)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": [] },
+                })];
+            }
+            "textDocument/completion" => self.completion(&uri),
+            "textDocument/hover" => self.hover(&uri, &params["position"]),
+            "textDocument/definition" => self.definition(&uri, &params["position"]),
+            _ => {
+                // notifications don't get a response, even if they're unknown
+                if message.get("id").is_none() { return vec![]; }
+
+                let error = json!({ "code": ERROR_METHOD_NOT_FOUND, "message": format!("unknown method '{}'", method) });
+                return vec![json!({ "jsonrpc": "2.0", "id": message["id"], "error": error })];
+            }
+        };
+
+        if message.get("id").is_none() { return vec![]; }
+        vec![json!({ "jsonrpc": "2.0", "id": message["id"], "result": result })]
+    }
+
+    fn diagnostics(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map(|text| text.as_str()).unwrap_or("");
+
+        let diagnostics: Vec<Value> = match parser::parse(text) {
+            Err(errors) => errors.iter()
+                .map(|err| {
+                    // parse errors point at a token, mark the word that starts there
+                    let start = err.span.as_ref().map_or(0, |span| span.offset_in(text));
+                    let end = word_at(text, start).1.max(text[start..].chars().next().map_or(start, |ch| start + ch.len_utf8()));
+                    json!({
+                        "range": { "start": position_at(text, start), "end": position_at(text, end) },
+                        "severity": DIAGNOSTIC_ERROR,
+                        "source": "map2",
+                        "message": err.message,
+                    })
+                })
+                .collect(),
+            Ok(script) => check_script(&script).iter()
+                .map(|diagnostic| {
+                    // diagnostics of the checker refer to a statement, mark the rest of its first line
+                    let start = diagnostic.span.as_ref().map_or(0, |span| span.offset_in(text));
+                    let end = text[start..].find('\n').map_or(text.len(), |idx| start + idx);
+                    let severity = match diagnostic.severity {
+                        Severity::Error => DIAGNOSTIC_ERROR,
+                        Severity::Warning => DIAGNOSTIC_WARNING,
+                    };
+                    json!({
+                        "range": { "start": position_at(text, start), "end": position_at(text, end) },
+                        "severity": severity,
+                        "source": "map2",
+                        "message": diagnostic.message,
+                    })
+                })
+                .collect(),
+        };
+
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })
+    }
+
+    fn completion(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map(|text| text.as_str()).unwrap_or("");
+        let mut items = vec![];
+
+        for (name, _, _) in BUILTIN_FUNCTIONS {
+            let signature = BUILTIN_DOCS.get(name).map_or(*name, |(signature, _)| signature);
+            items.push(json!({
+                "label": name,
+                "kind": COMPLETION_FUNCTION,
+                "detail": signature,
+                "documentation": { "kind": "markdown", "value": builtin_markdown(name).unwrap_or_default() },
+            }));
+        }
+
+        let mut variables: Vec<String> = definitions(text).into_iter().map(|definition| definition.name).collect();
+        variables.sort();
+        variables.dedup();
+        for name in variables {
+            items.push(json!({ "label": name, "kind": COMPLETION_VARIABLE }));
+        }
+
+        for name in key_names() {
+            items.push(json!({ "label": name, "kind": COMPLETION_CONSTANT, "detail": "key" }));
+        }
+
+        json!(items)
+    }
+
+    fn hover(&self, uri: &str, position: &Value) -> Value {
+        let text = self.documents.get(uri).map(|text| text.as_str()).unwrap_or("");
+        let (start, end) = word_at(text, offset_at(text, position));
+
+        match builtin_markdown(&text[start..end]) {
+            Some(markdown) => json!({
+                "contents": { "kind": "markdown", "value": markdown },
+                "range": { "start": position_at(text, start), "end": position_at(text, end) },
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        let text = self.documents.get(uri).map(|text| text.as_str()).unwrap_or("");
+        let offset = offset_at(text, position);
+        let (start, end) = word_at(text, offset);
+        if start == end { return Value::Null; }
+
+        let definitions = definitions(text);
+        match find_definition(&definitions, &text[start..end], offset) {
+            Some(definition) => {
+                let range_end = definition.offset + definition.name.len();
+                json!({
+                    "uri": uri,
+                    "range": { "start": position_at(text, definition.offset), "end": position_at(text, range_end) },
+                })
+            }
+            None => Value::Null,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends the messages to the server and returns everything it replied with.
+    fn run(messages: &[Value]) -> Vec<Value> {
+        let mut input = vec![];
+        for message in messages { write_message(&mut input, message).unwrap(); }
+
+        let mut output = vec![];
+        serve(input.as_slice(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        let mut replies = vec![];
+        while let Some(content) = read_message(&mut output).unwrap() {
+            replies.push(serde_json::from_str(&content).unwrap());
+        }
+        replies
+    }
+
+    fn open(text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///test.m2", "languageId": "map2", "version": 1, "text": text } },
+        })
+    }
+
+    fn request(id: i64, method: &str, line: usize, character: usize) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": "file:///test.m2" },
+                "position": { "line": line, "character": character },
+            },
+        })
+    }
+
+    #[test]
+    fn test_initialize_and_shutdown() {
+        let replies = run(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} } }),
+            json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "workspace/symbol", "params": { "query": "" } }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "shutdown" }),
+        ]);
+
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0]["id"], 1);
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert_eq!(replies[1]["error"]["code"], ERROR_METHOD_NOT_FOUND);
+        assert_eq!(replies[2], json!({ "jsonrpc": "2.0", "id": 3, "result": null }));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let replies = run(&[
+            open("let a = 1;\na::{entert};\n"),
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": "file:///test.m2", "version": 2 },
+                    "contentChanges": [{ "text": "let a = 1;\nprnt(a);\n" }],
+                },
+            }),
+        ]);
+
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(diagnostics[0]["severity"], DIAGNOSTIC_ERROR);
+        assert_eq!(diagnostics[0]["message"], "unknown key 'entert', did you mean 'enter'?");
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 4 }));
+        assert_eq!(diagnostics[0]["range"]["end"], json!({ "line": 1, "character": 10 }));
+
+        let diagnostics = &replies[1]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["message"], "function 'prnt' is not defined, did you mean 'print'?");
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 0 }));
+    }
+
+    #[test]
+    fn test_completion() {
+        let replies = run(&[open("let counter = 1;\n"), request(1, "textDocument/completion", 1, 0)]);
+
+        let items = replies[1]["result"].as_array().unwrap();
+        let item = |label: &str| items.iter().find(|item| item["label"] == label).cloned();
+        assert_eq!(item("sleep").unwrap()["kind"], COMPLETION_FUNCTION);
+        assert_eq!(item("counter").unwrap()["kind"], COMPLETION_VARIABLE);
+        assert_eq!(item("enter").unwrap()["kind"], COMPLETION_CONSTANT);
+        assert_eq!(item("btn_left").unwrap()["kind"], COMPLETION_CONSTANT);
+    }
+
+    #[test]
+    fn test_hover() {
+        let replies = run(&[
+            open("let a = 1;\nsleep(100);\n"),
+            request(1, "textDocument/hover", 1, 2),
+            request(2, "textDocument/hover", 0, 4),
+        ]);
+
+        let contents = replies[1]["result"]["contents"]["value"].as_str().unwrap();
+        assert!(contents.starts_with("```\nsleep("));
+        assert_eq!(replies[1]["result"]["range"]["end"], json!({ "line": 1, "character": 5 }));
+        assert_eq!(replies[2]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_definition() {
+        let script = "let a = 1;\nlet f = |a| {\n  print(a);\n};\nprint(a);\nfor (a in [1]) { print(a); }\n";
+        let replies = run(&[
+            open(script),
+            request(1, "textDocument/definition", 2, 8),
+            request(2, "textDocument/definition", 4, 6),
+            request(3, "textDocument/definition", 5, 23),
+            request(4, "textDocument/definition", 4, 0),
+        ]);
+
+        let start = |reply: &Value| reply["result"]["range"]["start"].clone();
+        assert_eq!(start(&replies[1]), json!({ "line": 1, "character": 9 }));
+        assert_eq!(start(&replies[2]), json!({ "line": 0, "character": 4 }));
+        assert_eq!(start(&replies[3]), json!({ "line": 5, "character": 5 }));
+        assert_eq!(replies[4]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_definitions_skip_strings_and_comments() {
+        let text = "// let a = 1;\nlet b = \"let c\";\n/* let d */";
+        let names: Vec<String> = definitions(text).into_iter().map(|definition| definition.name).collect();
+        assert_eq!(names, vec!["b"]);
+    }
+
+    #[test]
+    fn test_definitions_multi_byte_characters() {
+        // the last byte of '✪' is an alphanumeric character when read on its own
+        let text = "let é = 1;\nprint(✪);\nlet b = 2;";
+        let names: Vec<String> = definitions(text).into_iter().map(|definition| definition.name).collect();
+        assert_eq!(names, vec!["é", "b"]);
+    }
+}
@@ -18,13 +18,24 @@ pub use tokio::prelude::*;
 pub use tokio::sync::{mpsc, oneshot};
 pub use tokio::task;
 
-pub use crate::cli::parse_cli;
-pub use crate::device::virtual_input_device::bind_udev_inputs;
+pub use crate::abs_mapping::*;
+pub use crate::chord::*;
+pub use crate::control::{ControlCommand, ControlRequest};
+pub use crate::cli::{CliCommand, parse_cli};
+pub use crate::device::device_info::DeviceInfo;
+pub use crate::device::virtual_input_device::{bind_udev_inputs, DeviceEvent};
+pub use crate::execution_queue::{ExecutionQueue, ExecutionTurn};
+pub use crate::hotstring::*;
 pub use crate::key_defs::*;
 pub use crate::key_primitives::*;
+pub use crate::mouse_keys::*;
 pub use crate::runtime::*;
 pub use crate::runtime::evaluation::*;
+pub use crate::runtime::error::{RuntimeError, Span};
+pub use crate::sequence::*;
 pub use crate::state::*;
+pub use crate::tap_hold::*;
+pub use crate::timer::*;
 pub use crate::x11::{x11_initialize, get_window_info_x11};
 pub use crate::x11::ActiveWindowInfo;
 
@@ -42,6 +53,21 @@ pub mod ignore_list;
 pub mod messaging;
 pub mod event_handlers;
 pub mod logging;
+pub mod tap_hold;
+pub mod chord;
+pub mod sequence;
+pub mod mouse_keys;
+pub mod abs_mapping;
+pub mod control;
+pub mod check;
+pub mod suggestion;
+pub mod formatter;
+pub mod language_server;
+pub mod hotstring;
+pub mod timer;
+pub mod execution_queue;
+pub mod shutdown;
+pub mod repl;
 
 #[cfg(test)]
 pub mod tests;
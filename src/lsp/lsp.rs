@@ -0,0 +1,7 @@
+use map2::*;
+
+fn main() -> Result<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    language_server::serve(stdin.lock(), stdout.lock())
+}
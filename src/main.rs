@@ -1,12 +1,41 @@
+use futures::FutureExt;
 use map2::*;
 use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
 use std::thread;
 
 mod event_handlers;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let mut configuration = parse_cli()?;
+    shutdown::install_handlers()?;
+
+    // returning an error exits the process as well, a panic in any other task only stops that task
+    let res = match AssertUnwindSafe(run()).catch_unwind().await {
+        Ok(res) => res,
+        Err(_) => shutdown::exit(101),
+    };
+    shutdown::clean_up();
+    res
+}
+
+async fn run() -> Result<()> {
+    let mut configuration = match parse_cli()? {
+        CliCommand::Run(configuration) => configuration,
+        CliCommand::Control { socket_path, command } => {
+            print!("{}", control::send_command(&socket_path, &command).await?);
+            return Ok(());
+        }
+        CliCommand::Check { script_path } => {
+            if !check::check_file(&script_path) { std::process::exit(1); }
+            return Ok(());
+        }
+        CliCommand::Format { script_path, check } => {
+            if !formatter::format_file(&script_path, check) { std::process::exit(1); }
+            return Ok(());
+        }
+        CliCommand::Repl { devices, verbosity, panic_chord, output } => return repl::run(devices, verbosity, panic_chord, output).await,
+    };
 
     // create X11 communication channels
     let (window_ev_tx, mut window_ev_rx) = mpsc::channel(128);
@@ -14,7 +43,14 @@ async fn main() -> Result<()> {
 
     // spawn X11 thread
     tokio::spawn(async move {
-        let x11_state = Arc::new(x11_initialize().unwrap());
+        // i.e. on Wayland or a TTY, everything but reacting to window changes still works
+        let x11_state = match x11_initialize() {
+            Ok(x11_state) => Arc::new(x11_state),
+            Err(err) => {
+                eprintln!("warning: failed to connect to the X server ({}), the active window won't be tracked", err);
+                return;
+            }
+        };
 
         loop {
             let x11_state_clone = x11_state.clone();
@@ -30,12 +66,25 @@ async fn main() -> Result<()> {
 
     // initialize global state
     let mut stdout = io::stdout();
+    let mut stderr = io::stderr();
     let mut state = State::new();
     let mut window_cycle_token: usize = 0;
     let mut mappings = CompiledKeyMappings::new();
     let mut window_change_handlers = vec![];
 
-    let script_ast = script::parse_script(&mut configuration.script_file);
+    let script_ast = script::parse_script(&mut configuration.script_file)?;
+
+    // reload the script whenever it changes, the devices stay grabbed
+    let (script_change_tx, mut script_change_rx) = mpsc::channel(1);
+    if let Err(err) = script::watch_script(&configuration.script_path, script_change_tx) {
+        eprintln!("warning: {}, changes to the script won't be reloaded automatically", err);
+    }
+
+    // accept commands from 'map2 ctl', a script can still run without the control socket
+    let (control_tx, mut control_rx) = mpsc::channel(8);
+    if let Err(err) = control::serve(&configuration.socket_path, control_tx).await {
+        eprintln!("warning: {}, 'map2 ctl' won't be available", err);
+    }
 
     // add a small delay if run from TTY so we don't miss 'enter up' which is often released when the device is grabbed
     if atty::is(atty::Stream::Stdout) {
@@ -47,15 +96,18 @@ async fn main() -> Result<()> {
     let (ev_writer_tx, mut ev_writer_rx) = mpsc::channel(128);
 
     // send one end of the communication channels to the readers/writer
-    bind_udev_inputs(&configuration.devices, ev_reader_init_tx, ev_writer_tx).await?;
-    let mut ev_reader_tx = ev_reader_init_rx.await?;
+    bind_udev_inputs(&configuration.devices, configuration.panic_chord.clone(), configuration.output.clone(), ev_reader_init_tx, ev_writer_tx).await?;
+    let mut ev_reader_tx = ev_reader_init_rx.await??;
 
     // initial evaluation pass on global scope
     {
         let execution_message_tx = execution_message_tx.clone();
         let ev_reader_tx = ev_reader_tx.clone();
+        let script_generation = state.script_generation;
         task::spawn(async move {
-            script::evaluate_script(script_ast, execution_message_tx, ev_reader_tx, window_cycle_token).await;
+            if let Err(err) = script::evaluate_script(script_ast, execution_message_tx.clone(), ev_reader_tx, window_cycle_token, script_generation).await {
+                let _ = execution_message_tx.send(messaging::ExecutionMessage::FatalError(err.into(), 1)).await;
+            }
         });
     }
 
@@ -66,21 +118,39 @@ async fn main() -> Result<()> {
                 state.active_window = Some(window);
                 window_cycle_token = window_cycle_token + 1;
                 event_handlers::handle_active_window_change(&mut ev_reader_tx,
-                    &mut execution_message_tx, window_cycle_token, &mut window_change_handlers);
+                    &mut execution_message_tx, window_cycle_token, state.script_generation, &mut window_change_handlers);
             }
-            Some(ev) = ev_writer_rx.recv() => {
+            Some((ev, device)) = ev_writer_rx.recv() => {
                 event_handlers::handle_stdin_ev(
-                    &mut state, ev,
+                    &mut state, ev, device,
                     &mut mappings,
                     &mut ev_reader_tx,
                     &mut execution_message_tx,
                     window_cycle_token,
-                    &configuration,
+                    configuration.verbosity,
                 ).await.unwrap();
+
+                event_handlers::apply_pending_script(&mut state, &mut mappings, &mut window_change_handlers,
+                    &mut window_cycle_token, &ev_reader_tx, &execution_message_tx).await;
+            }
+            Some(request) = control_rx.recv() => {
+                let ControlRequest { command, response_tx } = request;
+                let response = event_handlers::handle_control_command(command, &configuration, &mut state, &mut mappings,
+                    &mut window_change_handlers, &mut window_cycle_token, &ev_reader_tx, &execution_message_tx).await;
+                let _ = response_tx.send(response);
+            }
+            Some(()) = script_change_rx.recv() => {
+                match script::load_script(&configuration.script_path) {
+                    Ok(script_ast) => {
+                        event_handlers::handle_script_change(script_ast, &mut state, &mut mappings, &mut window_change_handlers,
+                            &mut window_cycle_token, &ev_reader_tx, &execution_message_tx).await;
+                    }
+                    Err(err) => eprintln!("{}, the previous version of the script keeps running", err),
+                }
             }
             Some(msg) = message_rx.recv() => {
-                event_handlers::handle_execution_message(&mut stdout, window_cycle_token, msg, &mut state,
-                    &mut mappings, &mut window_change_handlers).await;
+                event_handlers::handle_execution_message(&mut stdout, &mut stderr, window_cycle_token, msg, &mut state,
+                    &mut mappings, &mut window_change_handlers, &mut ev_reader_tx, &mut execution_message_tx).await;
             }
         }
     }
@@ -5,13 +5,39 @@ use crate::*;
 #[derive(Debug)]
 pub enum ExecutionMessage {
     // EatEv(KeyAction),
-    AddMapping(usize, KeyActionWithMods, Block, GuardedVarMap),
+    AddMapping(usize, Option<String>, Option<String>, KeyActionWithMods, Block, GuardedVarMap),
+    PushLayer(usize, String),
+    PopLayer(usize),
+    ToggleLayer(usize, String),
+    IsLayerActive(String, mpsc::Sender<bool>),
+    AddTapHoldMapping(usize, TapHoldMapping),
+    TapHoldTimeout(Key, usize),
+    AddChordMapping(usize, Vec<Key>, Block, GuardedVarMap),
+    SetChordWindow(time::Duration),
+    ChordTimeout(usize),
+    AddSequenceMapping(usize, Vec<Key>, Block, GuardedVarMap),
+    SetSequenceTimeout(time::Duration),
+    SequenceTimeout(usize),
+    RegisterSequencePrefixCallback(Vec<String>, Block, GuardedVarMap),
+    AddMouseKeyMapping(usize, Option<String>, MouseKeyMapping),
+    SetMouseKeysConfig(MouseKeysSetting),
+    MouseKeysTick(usize),
+    AddAbsMapping(usize, Option<String>, Option<String>, AbsMapping, GuardedVarMap),
+    AddHotstring(usize, Hotstring),
+    /// adds a timer of the given script generation and window cycle and replies with its handle, no reply if the
+    /// timer was dropped because the script was replaced or the window cycle is stale. Timers set by a window change
+    /// callback only last for the window cycle they were set in.
+    AddTimer(usize, usize, bool, time::Duration, bool, Block, GuardedVarMap, mpsc::Sender<usize>),
+    ClearTimer(usize),
+    TimerTimeout(usize),
     GetFocusedWindowInfo(mpsc::Sender<Option<ActiveWindowInfo>>),
     RegisterWindowChangeCallback(Block, GuardedVarMap),
     Write(String),
     UpdateModifiers(KeyAction),
     Exit(i32),
     FatalError(Error, i32),
+    /// an error in a callback, the script keeps running
+    RuntimeError(RuntimeError),
 }
 
 pub type ExecutionMessageSender = tokio::sync::mpsc::Sender<ExecutionMessage>;
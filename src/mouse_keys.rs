@@ -0,0 +1,328 @@
+use evdev_rs::enums::EV_REL;
+
+use crate::*;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum MouseKeyAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    /// holds down a mouse button while the key is held
+    Click(Key),
+}
+
+impl MouseKeyAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "up" => Some(MouseKeyAction::Up),
+            "down" => Some(MouseKeyAction::Down),
+            "left" => Some(MouseKeyAction::Left),
+            "right" => Some(MouseKeyAction::Right),
+            "scroll_up" => Some(MouseKeyAction::ScrollUp),
+            "scroll_down" => Some(MouseKeyAction::ScrollDown),
+            "scroll_left" => Some(MouseKeyAction::ScrollLeft),
+            "scroll_right" => Some(MouseKeyAction::ScrollRight),
+            _ => None,
+        }
+    }
+
+    fn motion(&self) -> (f64, f64) {
+        match self {
+            MouseKeyAction::Up => (0.0, -1.0),
+            MouseKeyAction::Down => (0.0, 1.0),
+            MouseKeyAction::Left => (-1.0, 0.0),
+            MouseKeyAction::Right => (1.0, 0.0),
+            _ => (0.0, 0.0),
+        }
+    }
+
+    fn scroll(&self) -> (f64, f64) {
+        match self {
+            MouseKeyAction::ScrollUp => (0.0, 1.0),
+            MouseKeyAction::ScrollDown => (0.0, -1.0),
+            MouseKeyAction::ScrollLeft => (-1.0, 0.0),
+            MouseKeyAction::ScrollRight => (1.0, 0.0),
+            _ => (0.0, 0.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MouseKeyMapping {
+    pub key: Key,
+    pub action: MouseKeyAction,
+}
+
+#[derive(Clone, Debug)]
+pub struct MouseKeysConfig {
+    /// pointer speed in pixels per second when a key is pressed
+    pub initial_speed: f64,
+    /// pointer speed in pixels per second once fully accelerated
+    pub max_speed: f64,
+    /// time it takes to accelerate from the initial speed to the max speed
+    pub acceleration_time: time::Duration,
+    /// exponent of the acceleration curve, 1 accelerates linearly
+    pub acceleration_curve: f64,
+    pub tick_rate: time::Duration,
+    /// scroll speed in wheel notches per second
+    pub scroll_speed: f64,
+}
+
+impl MouseKeysConfig {
+    pub fn new() -> Self {
+        MouseKeysConfig {
+            initial_speed: 200.0,
+            max_speed: 1500.0,
+            acceleration_time: time::Duration::from_millis(1000),
+            acceleration_curve: 2.0,
+            tick_rate: time::Duration::from_millis(16),
+            scroll_speed: 10.0,
+        }
+    }
+
+    pub fn apply(&mut self, setting: MouseKeysSetting) {
+        match setting {
+            MouseKeysSetting::Speed(initial_speed, max_speed) => {
+                self.initial_speed = initial_speed;
+                self.max_speed = max_speed;
+            }
+            MouseKeysSetting::Acceleration(acceleration_time, acceleration_curve) => {
+                self.acceleration_time = acceleration_time;
+                self.acceleration_curve = acceleration_curve;
+            }
+            MouseKeysSetting::TickRate(tick_rate) => self.tick_rate = tick_rate,
+            MouseKeysSetting::ScrollSpeed(scroll_speed) => self.scroll_speed = scroll_speed,
+        }
+    }
+
+    fn speed(&self, elapsed: time::Duration) -> f64 {
+        let progress = if self.acceleration_time.as_secs_f64() > 0.0 {
+            (elapsed.as_secs_f64() / self.acceleration_time.as_secs_f64()).min(1.0)
+        } else { 1.0 };
+
+        self.initial_speed + (self.max_speed - self.initial_speed) * progress.powf(self.acceleration_curve)
+    }
+}
+
+impl Default for MouseKeysConfig {
+    fn default() -> Self { Self::new() }
+}
+
+#[derive(Clone, Debug)]
+pub enum MouseKeysSetting {
+    Speed(f64, f64),
+    Acceleration(time::Duration, f64),
+    TickRate(time::Duration),
+    ScrollSpeed(f64),
+}
+
+/// Requests a tick message after the given duration, movement is emitted on every tick while keys are held.
+pub struct MouseKeysTimer {
+    pub id: usize,
+    pub duration: time::Duration,
+}
+
+pub struct MouseKeysState {
+    pub config: MouseKeysConfig,
+    mappings: HashMap<(Option<String>, Key), MouseKeyAction>,
+    held: Vec<(Key, MouseKeyAction)>,
+    /// when the pointer started moving, used for acceleration
+    since: Option<time::Instant>,
+    motion_remainder: (f64, f64),
+    scroll_remainder: (f64, f64),
+    /// hi-res scroll units that didn't add up to a full wheel notch yet
+    scroll_notch_remainder: (i32, i32),
+    tick_id: Option<usize>,
+    next_id: usize,
+}
+
+impl MouseKeysState {
+    pub fn new() -> Self {
+        MouseKeysState {
+            config: MouseKeysConfig::new(),
+            mappings: Default::default(),
+            held: vec![],
+            since: None,
+            motion_remainder: (0.0, 0.0),
+            scroll_remainder: (0.0, 0.0),
+            scroll_notch_remainder: (0, 0),
+            tick_id: None,
+            next_id: 0,
+        }
+    }
+
+    /// Stops all movement and forgets the held keys, their releases won't be seen, i.e. while the script is paused.
+    pub fn forget_held(&mut self) {
+        self.held.clear();
+        self.tick_id = None;
+        self.since = None;
+        self.motion_remainder = (0.0, 0.0);
+        self.scroll_remainder = (0.0, 0.0);
+        self.scroll_notch_remainder = (0, 0);
+    }
+
+    pub fn insert(&mut self, layer: Option<String>, mapping: MouseKeyMapping) {
+        self.mappings.insert((layer, mapping.key), mapping.action);
+    }
+
+    /// Handles key events of mouse keys, returns `None` if the event should be processed normally.
+    pub fn process(&mut self, from: &KeyActionWithMods, device: Option<&DeviceInfo>, mappings: &CompiledKeyMappings) -> Option<(Vec<InputEvent>, Option<MouseKeysTimer>)> {
+        let key = from.key;
+        let mut out = vec![];
+
+        // releases resolve through the held keys so that layer changes don't leave the pointer moving
+        if let Some(idx) = self.held.iter().position(|(held, _)| *held == key) {
+            if from.value == TYPE_UP {
+                let (_, action) = self.held.remove(idx);
+                if let MouseKeyAction::Click(button) = action {
+                    out.push(KeyAction::new(button, TYPE_UP).to_input_ev());
+                    out.push(SYN_REPORT.clone());
+                }
+            }
+            return Some((out, None));
+        }
+
+        if from.value != TYPE_DOWN { return None; }
+
+        // the top-most layer that maps the key wins, no matter whether it maps it to a mouse key or not
+        let mut action = None;
+        for layer in mappings.active_layers().map(|layer| Some(layer.clone())).chain(std::iter::once(None)) {
+            if let Some(mouse_key_action) = self.mappings.get(&(layer.clone(), key)) {
+                action = Some(*mouse_key_action);
+                break;
+            }
+            if mappings.is_mapped_in_layer(layer, from, device) { return None; }
+        }
+        let action = action?;
+
+        self.held.push((key, action));
+
+        if let MouseKeyAction::Click(button) = action {
+            out.push(KeyAction::new(button, TYPE_DOWN).to_input_ev());
+            out.push(SYN_REPORT.clone());
+            return Some((out, None));
+        }
+
+        if self.tick_id.is_some() { return Some((out, None)); }
+
+        // start ticking right away so that the first movement isn't delayed
+        Some((out, Some(self.schedule_tick(time::Duration::from_millis(0)))))
+    }
+
+    /// Emits the movement for a single tick and schedules the next one while movement keys are held.
+    pub fn tick(&mut self, id: usize) -> (Vec<InputEvent>, Option<MouseKeysTimer>) {
+        let mut out = vec![];
+        if self.tick_id != Some(id) { return (out, None); }
+
+        let motion = self.held.iter().fold((0.0, 0.0), |acc, (_, action)| (acc.0 + action.motion().0, acc.1 + action.motion().1));
+        let scroll = self.held.iter().fold((0.0, 0.0), |acc, (_, action)| (acc.0 + action.scroll().0, acc.1 + action.scroll().1));
+
+        if motion == (0.0, 0.0) && scroll == (0.0, 0.0) {
+            self.tick_id = None;
+            self.since = None;
+            self.motion_remainder = (0.0, 0.0);
+            self.scroll_remainder = (0.0, 0.0);
+            self.scroll_notch_remainder = (0, 0);
+            return (out, None);
+        }
+
+        let tick_secs = self.config.tick_rate.as_secs_f64();
+        let since = *self.since.get_or_insert_with(time::Instant::now);
+
+        if motion != (0.0, 0.0) {
+            let distance = self.config.speed(since.elapsed()) * tick_secs;
+            let (dx, dy) = take_whole(&mut self.motion_remainder, (motion.0 * distance, motion.1 * distance));
+
+            if dx != 0 { out.push(rel_ev(EV_REL::REL_X, dx)); }
+            if dy != 0 { out.push(rel_ev(EV_REL::REL_Y, dy)); }
+        }
+
+        if scroll != (0.0, 0.0) {
+            // one wheel notch equals 120 hi-res units
+            let distance = self.config.scroll_speed * 120.0 * tick_secs;
+            let (hi_res_x, hi_res_y) = take_whole(&mut self.scroll_remainder, (scroll.0 * distance, scroll.1 * distance));
+
+            if hi_res_y != 0 {
+                out.push(rel_ev(EV_REL::REL_WHEEL_HI_RES, hi_res_y));
+                self.scroll_notch_remainder.1 += hi_res_y;
+                let notches = self.scroll_notch_remainder.1 / 120;
+                if notches != 0 {
+                    out.push(rel_ev(EV_REL::REL_WHEEL, notches));
+                    self.scroll_notch_remainder.1 -= notches * 120;
+                }
+            }
+            if hi_res_x != 0 {
+                out.push(rel_ev(EV_REL::REL_HWHEEL_HI_RES, hi_res_x));
+                self.scroll_notch_remainder.0 += hi_res_x;
+                let notches = self.scroll_notch_remainder.0 / 120;
+                if notches != 0 {
+                    out.push(rel_ev(EV_REL::REL_HWHEEL, notches));
+                    self.scroll_notch_remainder.0 -= notches * 120;
+                }
+            }
+        }
+
+        if !out.is_empty() { out.push(SYN_REPORT.clone()); }
+
+        let timer = self.schedule_tick(self.config.tick_rate);
+        (out, Some(timer))
+    }
+
+    fn schedule_tick(&mut self, duration: time::Duration) -> MouseKeysTimer {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.tick_id = Some(id);
+        MouseKeysTimer { id, duration }
+    }
+}
+
+impl Default for MouseKeysState {
+    fn default() -> Self { Self::new() }
+}
+
+/// Adds the delta to the remainder and takes out the whole units.
+fn take_whole(remainder: &mut (f64, f64), delta: (f64, f64)) -> (i32, i32) {
+    remainder.0 += delta.0;
+    remainder.1 += delta.1;
+    let whole = (remainder.0.trunc(), remainder.1.trunc());
+    remainder.0 -= whole.0;
+    remainder.1 -= whole.1;
+    (whole.0 as i32, whole.1 as i32)
+}
+
+fn rel_ev(code: EV_REL, value: i32) -> InputEvent {
+    KeyAction::new(Key { event_code: EventCode::EV_REL(code) }, value).to_input_ev()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_priority() {
+        let mut mouse_keys = MouseKeysState::new();
+        mouse_keys.insert(None, MouseKeyMapping { key: *KEY_H, action: MouseKeyAction::Left });
+
+        let mut mappings = CompiledKeyMappings::new();
+        let block = Arc::new((Block::new(), GuardedVarMap::new(Mutex::new(VarMap::new(None)))));
+        mappings.insert(Some("nav".to_string()), None, KeyActionWithMods::new(*KEY_H, TYPE_DOWN, KeyModifierFlags::new()), block);
+
+        let press = KeyActionWithMods::new(*KEY_H, TYPE_DOWN, KeyModifierFlags::new());
+        let release = KeyActionWithMods::new(*KEY_H, TYPE_UP, KeyModifierFlags::new());
+
+        // the active layer maps the key, the global mouse key doesn't apply
+        mappings.push_layer("nav".to_string());
+        assert!(mouse_keys.process(&press, None, &mappings).is_none());
+        assert!(mouse_keys.process(&release, None, &mappings).is_none());
+
+        mappings.pop_layer();
+        assert!(mouse_keys.process(&press, None, &mappings).is_some());
+        assert!(mouse_keys.process(&release, None, &mappings).is_some());
+    }
+}
@@ -12,22 +12,29 @@ use nom::multi::many0;
 use nom::sequence::tuple;
 
 use crate::parsing::error::FromTagError;
+use crate::parsing::span::comment;
 
 fn line_comment<'a, E>(input: &'a str) -> IResult<&str, (), E>
     where E: ParseError<&'a str>,
 {
-    value((), tuple((
+    let (next, _) = tuple((
         tag("//"),
         is_not("\r\n")
-    )))(input)
+    ))(input)?;
+
+    comment(input, &input[..input.len() - next.len()]);
+    Ok((next, ()))
 }
 
 fn inline_comment<'a, E>(input: &'a str) -> IResult<&str, (), E> where E: ParseError<&'a str> {
-    value((), tuple((
+    let (next, _) = tuple((
         tag("/*"),
         take_until("*/"),
         tag("*/"),
-    )))(input)
+    ))(input)?;
+
+    comment(input, &input[..input.len() - next.len()]);
+    Ok((next, ()))
 }
 
 pub fn ws0<'a, E>(input: &'a str) -> IResult<&str, (), E> where E: ParseError<&'a str> {
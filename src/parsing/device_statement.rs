@@ -0,0 +1,48 @@
+use super::*;
+
+pub(super) fn device_statement(input: &str) -> ResNew<&str, Stmt> {
+    tuple((
+        tag_custom("device"), ws0,
+        tag_custom("("), ws0,
+        string, ws0,
+        tag_custom(")"), ws0,
+        |input| scoped_block(MappingBlockScope::Device, input),
+    ))(input)
+        .map(|(next, v)| {
+            let pattern = match v.4.0 {
+                Expr::Value(ValueType::String(pattern)) => pattern,
+                _ => unreachable!(),
+            };
+            let stmt = Stmt::Device(pattern, v.8.0);
+            (next, (stmt, v.8.1))
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_statement() {
+        assert_eq!(
+            device_statement("device(\"Logitech G700s\") { btn_extra::^c; }"),
+            nom_ok(Stmt::Device("Logitech G700s".to_string(), nom_eval(block("{ btn_extra::^c; }")))),
+        );
+
+        assert_eq!(device_statement("device( \"046d:c07c\" ){}"), nom_ok(Stmt::Device("046d:c07c".to_string(), Block::new())));
+        assert!(matches!(device_statement("device() {}"), Err(..)));
+        assert!(matches!(device_statement("device(\"pad\");"), Err(..)));
+    }
+
+    #[test]
+    fn test_device_statement_unscoped_mappings() {
+        let errors = with_source("device(\"pad\") {\n  layer nav { h::mouse(left); }\n}\n", global_block).unwrap_err();
+        let errors: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(errors, vec!["err: at line 2:\n  layer nav { h::mouse(left); }\n              ^\nmouse key mappings can't be used inside a device block".to_string()]);
+
+        assert!(matches!(device_statement("device(\"pad\") { g,g::home; }"), Err(..)));
+        assert!(matches!(device_statement("device(\"pad\") { capslock::tap_hold(esc, ctrl); }"), Err(..)));
+        assert!(device_statement("device(\"pad\") { layer nav { h::left; } }").is_ok());
+    }
+}
@@ -62,78 +62,12 @@ impl<Input> FromTagError<Input> for CustomError<Input> {
 }
 
 
-pub(super) fn convert_custom_error<I: core::ops::Deref<Target=str>>(
-    input: I,
-    err: &CustomError<I>,
-) -> String {
-    use std::fmt::Write;
-    use nom::Offset;
-
-    let mut result = String::new();
-
-    let expected;
-    if err.expected.is_empty() {
-        expected = "valid token (no suggestion)".to_string();
-    } else {
-        let mut options = err.expected.clone();
-        options.sort();
-        options.dedup();
-
-        expected = format!("[ {} ]", options.join(", "));
-    }
-
-    let substring = &err.input;
-
-    let offset = input.offset(&substring);
-
-    if input.is_empty() {
-        // TODO handle EOF
-        // match kind {
-        //     VerboseErrorKind::Char(c) => {
-        //         write!(&mut result, "{}: expected '{}', got empty input\n\n", i, c)
-        //     }
-        //     VerboseErrorKind::Context(s) => write!(&mut result, "{}: in {}, got empty input\n\n", i, s),
-        //     VerboseErrorKind::Nom(e) => write!(&mut result, "{}: in {:?}, got empty input\n\n", i, e),
-        // }
-    } else {
-        let prefix = &input.as_bytes()[..offset];
-
-        // Count the number of newlines in the first `offset` bytes of input
-        let line_number = prefix.iter().filter(|&&b| b == b'\n').count() + 1;
-
-        // Find the line that includes the subslice:
-        // Find the *last* newline before the substring starts
-        let line_begin = prefix
-            .iter()
-            .rev()
-            .position(|&b| b == b'\n')
-            .map(|pos| offset - pos)
-            .unwrap_or(0);
-
-        // Find the full line after that newline
-        let line = input[line_begin..]
-            .lines()
-            .next()
-            .unwrap_or(&input[line_begin..])
-            .trim_end();
-
-        // The (1-indexed) column number is the offset of our substring into that line
-        let column_number: usize = line.offset(&substring) + 1;
-
-        write!(
-            &mut result,
-            "err: at line {line_number}:\n\
-               {line}\n\
-               {caret:>column$}\n\
-               expected {expected}\n\n",
-            // i = i,
-            line_number = line_number,
-            line = line,
-            caret = '^',
-            column = column_number,
-            expected = expected,
-        ).unwrap();
-    }
-
-    result
+/// Describes what the parser expected at the location of the error.
+pub(super) fn expected_message<I>(err: &CustomError<I>) -> String {
+    if err.expected.is_empty() { return "expected valid token (no suggestion)".to_string(); }
+
+    let mut options = err.expected.clone();
+    options.sort();
+    options.dedup();
+    format!("expected [ {} ]", options.join(", "))
 }
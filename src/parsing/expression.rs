@@ -8,23 +8,38 @@ pub(super) fn expr_4(input: &str) -> ResNew<&str, Expr> {
         boolean,
         string,
         number,
+        list,
+        map_literal,
         lambda,
         variable_initialization,
         variable_assignment,
+        index_assignment,
         function_call,
-        key_mapping,
-        key_mapping_inline,
+        any_key_mapping,
         variable,
     ))(input)
 }
 
+/// An expression followed by any number of index operations, i.e. 'xs[0][1]'.
+fn indexed(input: &str) -> ResNew<&str, Expr> {
+    let (input, (init, last_err)) = expr_4(input)?;
+    let (input, (expr, _)) = fold_many0_once_err(
+        // the position of '[' is kept to point at it if the index is out of range
+        |input| index(input).map(|(next, (index, last_err))| (next, ((input, index), last_err))),
+        init,
+        |acc, ((input, index), _)| spanned(input, Expr::Index(Box::new(acc), Box::new(index))),
+    )(input)?;
+
+    Ok((input, (expr, last_err)))
+}
+
 pub(super) fn expr_3(input: &str) -> ResNew<&str, Expr> {
     // TODO fold this
     let (input, expr) = alt((
-        expr_4,
+        indexed,
         map(
             tuple((tag_custom("!"), not(tag("{")), expr_3)),
-            |(_, _, (expr, last_err))| (Expr::Neg(Box::new(expr)), last_err),
+            |(op, _, (expr, last_err))| (spanned(op, Expr::Neg(Box::new(expr))), last_err),
         ),
     ))(input)?;
 
@@ -44,11 +59,11 @@ pub(super) fn expr_2(i: &str) -> ResNew<&str, Expr> {
         },
         init.0,
         |acc, (_, op, _, (val, _))| {
-            match op {
+            spanned(op, match op {
                 "*" => Expr::Mul(Box::new(acc), Box::new(val)),
                 "/" => Expr::Div(Box::new(acc), Box::new(val)),
                 _ => unreachable!()
-            }
+            })
         },
     )(input);
 
@@ -71,11 +86,11 @@ pub(super) fn expr_1(i: &str) -> ResNew<&str, Expr> {
         },
         init.0,
         |acc, (_, op, _, (val, _))| {
-            match op {
+            spanned(op, match op {
                 "+" => Expr::Add(Box::new(acc), Box::new(val)),
                 "-" => Expr::Sub(Box::new(acc), Box::new(val)),
                 _ => unreachable!()
-            }
+            })
         },
     )(input);
 
@@ -103,7 +118,7 @@ pub(super) fn expr(input: &str) -> ResNew<&str, Expr> {
         },
         init.0,
         |acc, (_, op, _, (val, _))| {
-            match op {
+            spanned(op, match op {
                 "==" => Expr::Eq(Box::new(acc), Box::new(val)),
                 "!=" => Expr::Neq(Box::new(acc), Box::new(val)),
                 "&&" => Expr::And(Box::new(acc), Box::new(val)),
@@ -111,7 +126,7 @@ pub(super) fn expr(input: &str) -> ResNew<&str, Expr> {
                 ">" => Expr::GT(Box::new(acc), Box::new(val)),
                 "<" => Expr::LT(Box::new(acc), Box::new(val)),
                 _ => unreachable!()
-            }
+            })
         },
     )(input);
 
@@ -18,6 +18,22 @@ pub(super) fn for_loop(input: &str) -> ResNew<&str, Stmt> {
         })
 }
 
+pub(super) fn for_in_loop(input: &str) -> ResNew<&str, Stmt> {
+    tuple((
+        tag_custom("for"), ws0,
+        tag_custom("("), ws0,
+        ident, ws1,
+        tag_custom("in"), ws1,
+        expr, ws0,
+        tag_custom(")"), ws0,
+        block,
+    ))(input)
+        .map(|(next, v)| {
+            let stmt = Stmt::ForIn(v.4.0, v.8.0, v.12.0);
+            (next, (stmt, None))
+        })
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -35,4 +51,17 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn test_for_in_loop() {
+        assert_eq!(
+            nom_no_last_err(for_in_loop("for (x in [1, 2]) {}")),
+            nom_ok(Stmt::ForIn(
+                "x".to_string(),
+                nom_eval(expr("[1, 2]")),
+                Block::new(),
+            ))
+        );
+        assert!(matches!(for_in_loop("for (xin xs) {}"), Err(..)));
+    }
 }
@@ -5,6 +5,7 @@ pub(super) fn function_arg(input: &str) -> ResNew<&str, Expr> {
 }
 
 pub(super) fn function_call(input: &str) -> ResNew<&str, Expr> {
+    let start = input;
     let (input, (ident_res,_)) = tuple((ident, tag_custom("(")))(input)
         .map_err(|_: NomErr<CustomError<_>>| make_generic_nom_err_options(input, vec!["function call".to_string()]))?;
 
@@ -30,7 +31,7 @@ pub(super) fn function_call(input: &str) -> ResNew<&str, Expr> {
             }
             _ => Expr::FunctionCall(ident_res.0, vec![])
         };
-        (next, (expr,None))
+        (next, (spanned(start, expr), None))
     })
 }
 
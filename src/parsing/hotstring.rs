@@ -0,0 +1,60 @@
+use super::*;
+use super::parser::parse_key_sequence;
+
+/// Parses a hotstring, i.e. '::btw::by the way' or ':*:btw::by the way', the replacement ends at the ';'.
+pub(super) fn hotstring(input: &str) -> ResNew<&str, Expr> {
+    tuple((
+        tag_custom(":"),
+        take_while(|ch| "*?cC".contains(ch)),
+        tag_custom(":"),
+        take_till1(|ch: char| ch.is_whitespace() || ch == ':'),
+        tag_custom("::"),
+        take_till1(|ch| ch == ';' || ch == '\n'),
+    ))(input).and_then(|(next, v)| {
+        let options = v.1;
+        let replacement = v.5.to_string();
+
+        if parse_key_sequence(&replacement).is_err() {
+            return Err(make_generic_nom_err_options(input, vec!["replacement text".to_string()]));
+        }
+
+        let hotstring = Hotstring {
+            abbreviation: v.3.to_string(),
+            replacement,
+            immediate: options.contains('*'),
+            inside_word: options.contains('?'),
+            case_sensitive: options.contains('c') || options.contains('C'),
+        };
+
+        Ok((next, (Expr::Hotstring(hotstring), None)))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hotstring() {
+        assert_eq!(hotstring("::btw::by the way"), nom_ok(Expr::Hotstring(Hotstring {
+            abbreviation: "btw".to_string(),
+            replacement: "by the way".to_string(),
+            immediate: false,
+            inside_word: false,
+            case_sensitive: false,
+        })));
+
+        assert_eq!(hotstring(":*?c:e.g::for example;"), nom_ok_rest(";", Expr::Hotstring(Hotstring {
+            abbreviation: "e.g".to_string(),
+            replacement: "for example".to_string(),
+            immediate: true,
+            inside_word: true,
+            case_sensitive: true,
+        })));
+
+        assert!(matches!(hotstring("::by the way::btw"), Err(..)));
+        assert!(matches!(hotstring("::btw::"), Err(..)));
+        assert!(matches!(hotstring(":x:btw::by the way"), Err(..)));
+    }
+}
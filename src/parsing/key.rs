@@ -1,3 +1,5 @@
+use evdev_rs::enums::EV_REL;
+
 use super::*;
 
 pub(super) fn key_flags(input: &str) -> ResNew<&str, KeyModifierFlags> {
@@ -33,7 +35,10 @@ pub(super) fn key(input: &str) -> ResNew<&str, (Key, KeyModifierFlags)> {
                     }
 
                     let key = Key::from_str(&EventType::EV_KEY, key_name.as_str())
-                        .map_err(|_| make_generic_nom_err_new(input))?;
+                        .map_err(|_| {
+                            note_unknown_key(&input[input.len() - next.len() - val.len()..], next, &val);
+                            make_generic_nom_err_new(input)
+                        })?;
 
                     (key, KeyModifierFlags::new())
                 }
@@ -49,6 +54,29 @@ pub(super) fn key(input: &str) -> ResNew<&str, (Key, KeyModifierFlags)> {
         })
 }
 
+/// Records a suggestion for an unknown key name if it's somewhere only a key makes sense, i.e. right before or after
+/// '::' or inside braces. Anywhere else the name is more likely a variable or a function.
+fn note_unknown_key(input: &str, next: &str, name: &str) {
+    if name.chars().count() < 2 { return; }
+
+    let before = text_before(input);
+    let after = next.trim_start();
+    let is_braced = before.trim_end().ends_with('{') && (
+        after.starts_with('}') ||
+            ["down", "up"].iter().any(|state| after.strip_prefix(state).is_some_and(|rest| rest.trim_start().starts_with('}')))
+    );
+    let is_key_position = is_braced ||
+        before.trim_end_matches(|ch: char| "^!+#{".contains(ch) || ch.is_whitespace()).ends_with("::") ||
+        after.starts_with("::");
+    if !is_key_position { return; }
+
+    let message = match crate::suggestion::suggest_key(name) {
+        Some(suggestion) => format!("unknown key '{}', did you mean '{}'?", name, suggestion),
+        None => format!("unknown key '{}'", name),
+    };
+    note(input, message);
+}
+
 fn key_state(input: &str) -> ResNew<&str, i32> {
     alt((
         tag("down"), tag("up"),
@@ -59,6 +87,42 @@ fn key_state(input: &str) -> ResNew<&str, i32> {
     }))
 }
 
+/// Parses a relative axis with a direction, i.e. 'rel_wheel up', the value is the sign of matching events.
+pub(super) fn rel_with_direction(input: &str) -> ResNew<&str, (Key, i32)> {
+    tuple((
+        ident,
+        ws1,
+        alt((tag("up"), tag("down"), tag("left"), tag("right"))),
+    ))(input)
+        .and_then(|(next, (name, _, direction))| {
+            let name = name.0.to_uppercase();
+            if !name.starts_with("REL_") { return Err(make_generic_nom_err_new(input)); }
+
+            let key = Key::from_str(&EventType::EV_REL, &name)
+                .map_err(|_| make_generic_nom_err_new(input))?;
+
+            let value = match (key.event_code, direction) {
+                (EventCode::EV_REL(EV_REL::REL_Y), "up") => -1,
+                (EventCode::EV_REL(EV_REL::REL_Y), "down") => 1,
+                (EventCode::EV_REL(EV_REL::REL_X), "left") |
+                (EventCode::EV_REL(EV_REL::REL_HWHEEL), "left") |
+                (EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES), "left") => -1,
+                (EventCode::EV_REL(EV_REL::REL_X), "right") |
+                (EventCode::EV_REL(EV_REL::REL_HWHEEL), "right") |
+                (EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES), "right") => 1,
+                (EventCode::EV_REL(EV_REL::REL_X), _) |
+                (EventCode::EV_REL(EV_REL::REL_Y), _) |
+                (EventCode::EV_REL(EV_REL::REL_HWHEEL), _) |
+                (EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES), _) => return Err(make_generic_nom_err_options(input, vec!["direction".to_string()])),
+                (_, "up") => 1,
+                (_, "down") => -1,
+                _ => return Err(make_generic_nom_err_options(input, vec!["'up'".to_string(), "'down'".to_string()])),
+            };
+
+            Ok((next, ((key, value), None)))
+        })
+}
+
 pub(super) fn key_with_state(input: &str) -> ResNew<&str, ((Key, KeyModifierFlags), i32)> {
     tuple((
         key,
@@ -81,6 +145,18 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn test_rel_with_direction() {
+        assert_eq!(rel_with_direction("rel_wheel up"), nom_ok((Key::from_str(&EventType::EV_REL, "REL_WHEEL").unwrap(), 1)));
+        assert_eq!(rel_with_direction("rel_wheel_hi_res down"), nom_ok((Key::from_str(&EventType::EV_REL, "REL_WHEEL_HI_RES").unwrap(), -1)));
+        assert_eq!(rel_with_direction("rel_y up"), nom_ok((Key::from_str(&EventType::EV_REL, "REL_Y").unwrap(), -1)));
+        assert_eq!(rel_with_direction("rel_x right"), nom_ok((Key::from_str(&EventType::EV_REL, "REL_X").unwrap(), 1)));
+
+        assert!(matches!(rel_with_direction("rel_x up"), Err(..)));
+        assert!(matches!(rel_with_direction("rel_wheel left"), Err(..)));
+        assert!(matches!(rel_with_direction("a down"), Err(..)));
+    }
+
     #[test]
     fn test_key() {
         assert_eq!(key("d"), nom_ok((
@@ -47,6 +47,7 @@ impl ParsedKeyActionVecExt for Vec<ParsedKeyAction> {
 
 pub(super) fn key_action(input: &str) -> ResNew<&str, ParsedKeyAction> {
     alt((
+        map(tuple((tag_custom("{"), rel_with_direction, tag_custom("}"))), |(_, ((key, value), _), _)| ((key, KeyModifierFlags::new()), Some(value))),
         map(tuple((tag_custom("{"), key_with_state, tag_custom("}"))), |(_, (v, _), _)| (v.0, Some(v.1))),
         map(
             alt((
@@ -100,6 +101,10 @@ mod tests {
         assert_eq!(key_action("{btn_forward down}"), nom_ok(ParsedKeyAction::KeyAction(
             KeyActionWithMods::new(Key::from_str(&EventType::EV_KEY, "BTN_FORWARD").unwrap(), 1, KeyModifierFlags::new())
         )));
+
+        assert_eq!(key_action("{rel_wheel down}"), nom_ok(ParsedKeyAction::KeyAction(
+            KeyActionWithMods::new(Key::from_str(&EventType::EV_REL, "REL_WHEEL").unwrap(), -1, KeyModifierFlags::new())
+        )));
     }
 
     #[test]
@@ -1,5 +1,79 @@
+use std::cell::RefCell;
+
+use nom::multi::many1;
+
 use super::*;
 
+/// A block statement that restricts the mappings inside of it.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(super) enum MappingBlockScope {
+    Layer,
+    Device,
+}
+
+impl MappingBlockScope {
+    fn name(self) -> &'static str {
+        match self {
+            MappingBlockScope::Layer => "layer",
+            MappingBlockScope::Device => "device",
+        }
+    }
+}
+
+thread_local! {
+    /// the scopes of the blocks around the input that is being parsed, innermost last
+    static SCOPES: RefCell<Vec<MappingBlockScope>> = const { RefCell::new(vec![]) };
+}
+
+/// Parses the block of a scope statement, mappings inside of it that can't be restricted to the scope are rejected.
+pub(super) fn scoped_block(scope: MappingBlockScope, input: &str) -> ResNew<&str, Block> {
+    SCOPES.with(|scopes| scopes.borrow_mut().push(scope));
+    let res = block(input);
+    SCOPES.with(|scopes| scopes.borrow_mut().pop());
+    res
+}
+
+/// Fails if the mapping is inside a block whose scope the mapping doesn't support, it would silently apply
+/// globally otherwise.
+fn check_mapping_scope<'a>(input: &'a str, mapping: &Expr) -> Result<(), NomErr<CustomError<&'a str>>> {
+    let (kind, supported): (&str, &[MappingBlockScope]) = match mapping {
+        Expr::TapHoldMapping(_) => ("tap-hold", &[]),
+        Expr::ChordMapping(_) => ("chord", &[]),
+        Expr::SequenceMapping(_) => ("sequence", &[]),
+        Expr::MouseKeyMapping(_) => ("mouse key", &[MappingBlockScope::Layer]),
+        Expr::Hotstring(_) => ("hotstring", &[]),
+        _ => return Ok(()),
+    };
+
+    let unsupported = SCOPES.with(|scopes| scopes.borrow().iter().copied().find(|scope| !supported.contains(scope)));
+    let name = match unsupported {
+        Some(scope) => scope.name(),
+        None => return Ok(()),
+    };
+
+    note(input, format!("{} mappings can't be used inside a {} block", kind, name));
+    Err(NomErr::Failure(CustomError { input, expected: vec![format!("mapping that can be used inside a {} block", name)] }))
+}
+
+/// Any kind of key mapping, the source text of the mapping gets recorded since the parsed mapping doesn't resemble
+/// what was written anymore.
+pub(super) fn any_key_mapping(input: &str) -> ResNew<&str, Expr> {
+    let (next, res) = alt((
+        key_mapping_chord,
+        key_mapping_sequence,
+        key_mapping,
+        key_mapping_tap_hold,
+        key_mapping_mouse,
+        key_mapping_abs,
+        hotstring,
+        key_mapping_inline,
+    ))(input)?;
+    check_mapping_scope(input, &res.0)?;
+
+    mapping(input, next);
+    Ok((next, res))
+}
+
 pub(super) fn key_mapping_inline(input: &str) -> ResNew<&str, Expr> {
     tuple((
         key_action_with_flags,
@@ -62,12 +136,205 @@ pub(super) fn key_mapping_inline(input: &str) -> ResNew<&str, Expr> {
     })
 }
 
+pub(super) fn key_mapping_tap_hold(input: &str) -> ResNew<&str, Expr> {
+    tuple((
+        key_action_with_flags,
+        tag_custom("::"),
+        tag_custom("tap_hold"), ws0,
+        tag_custom("("), ws0,
+        key_action_with_flags, ws0,
+        tag_custom(","), ws0,
+        key_action_with_flags,
+        opt(tuple((ws0, tag_custom(","), ws0, number))),
+        opt(tuple((ws0, tag_custom(","), ws0, string))),
+        ws0,
+        tag_custom(")"),
+    ))(input).and_then(|(next, v)| {
+        let (from, tap, hold) = match (v.0.0, v.6.0, v.10.0) {
+            (
+                ParsedKeyAction::KeyClickAction(from),
+                ParsedKeyAction::KeyClickAction(tap),
+                ParsedKeyAction::KeyClickAction(hold),
+            ) if from.modifiers == KeyModifierFlags::new() => (from, tap, hold),
+            _ => return Err(make_generic_nom_err_options(input, vec!["tap-hold mapping".to_string()])),
+        };
+
+        let tapping_term = match v.11 {
+            Some((_, _, _, (Expr::Value(ValueType::Number(millis)), _))) => time::Duration::from_millis(millis as u64),
+            _ => TAP_HOLD_DEFAULT_TAPPING_TERM,
+        };
+
+        let interrupt = match v.12 {
+            Some((_, _, _, (Expr::Value(ValueType::String(interrupt)), _))) => TapHoldInterrupt::parse(&interrupt)
+                .ok_or_else(|| make_generic_nom_err_options(input, vec!["'permissive'".to_string(), "'hold'".to_string(), "'tap'".to_string()]))?,
+            _ => TapHoldInterrupt::Permissive,
+        };
+
+        let expr = Expr::TapHoldMapping(TapHoldMapping { key: from.key, tap, hold, tapping_term, interrupt });
+        Ok((next, (expr, None)))
+    })
+}
+
+pub(super) fn key_mapping_mouse(input: &str) -> ResNew<&str, Expr> {
+    tuple((
+        plain_key,
+        tag_custom("::"),
+        tag_custom("mouse"), ws0,
+        tag_custom("("), ws0,
+        ident, ws0,
+        tag_custom(")"),
+    ))(input).and_then(|(next, v)| {
+        let name = v.6.0;
+        let action = match MouseKeyAction::parse(&name) {
+            Some(action) => action,
+            None if name.to_uppercase().starts_with("BTN_") => {
+                let button = Key::from_str(&EventType::EV_KEY, &name.to_uppercase())
+                    .map_err(|_| make_generic_nom_err_options(input, vec!["mouse button".to_string()]))?;
+                MouseKeyAction::Click(button)
+            }
+            None => return Err(make_generic_nom_err_options(input, vec!["mouse action".to_string()])),
+        };
+
+        let expr = Expr::MouseKeyMapping(MouseKeyMapping { key: v.0.0, action });
+        Ok((next, (expr, None)))
+    })
+}
+
+fn abs_trigger(input: &str) -> ResNew<&str, AbsTrigger> {
+    tuple((
+        tag_custom("{"), ws0,
+        ident, ws0,
+        alt((tag(">"), tag("<"))), ws0,
+        number, ws0,
+        tag_custom("}"),
+    ))(input).and_then(|(next, v)| {
+        let name = v.2.0.to_uppercase();
+        if !name.starts_with("ABS_") { return Err(make_generic_nom_err_options(input, vec!["absolute axis".to_string()])); }
+
+        let axis = Key::from_str(&EventType::EV_ABS, &name)
+            .map_err(|_| make_generic_nom_err_options(input, vec!["absolute axis".to_string()]))?
+            .event_code;
+
+        let comparison = if v.4 == ">" { AbsComparison::Greater } else { AbsComparison::Less };
+
+        let threshold = match v.6.0 {
+            Expr::Value(ValueType::Number(threshold)) => threshold as i32,
+            _ => unreachable!(),
+        };
+
+        Ok((next, (AbsTrigger { axis, comparison, threshold }, None)))
+    })
+}
+
+pub(super) fn key_mapping_abs(input: &str) -> ResNew<&str, Expr> {
+    tuple((
+        abs_trigger,
+        tag_custom("::"),
+        alt((
+            map(tuple((ws0, block)), |(_, (v, last_err))| ((v, Block::new()), last_err)),
+            map(key_sequence, |(v, last_err)| ((key_action_block(v), Block::new()), last_err)),
+            map(key_action_with_flags, |(v, last_err)| {
+                let blocks = match v {
+                    // hold the key while the threshold is crossed
+                    ParsedKeyAction::KeyClickAction(action) => {
+                        let mut enter = modifier_actions(&action.modifiers, TYPE_DOWN);
+                        enter.push(KeyAction::new(action.key, TYPE_DOWN));
+
+                        let mut leave = vec![KeyAction::new(action.key, TYPE_UP)];
+                        leave.append(&mut modifier_actions(&action.modifiers, TYPE_UP));
+
+                        (
+                            Block::new().tap_mut(|b| b.statements = enter.into_iter().map(|v| Stmt::Expr(Expr::KeyAction(v))).collect()),
+                            Block::new().tap_mut(|b| b.statements = leave.into_iter().map(|v| Stmt::Expr(Expr::KeyAction(v))).collect()),
+                        )
+                    }
+                    action => (key_action_block(vec![action]), Block::new()),
+                };
+                (blocks, last_err)
+            }),
+        )),
+    ))(input).and_then(|(next, v)| {
+        let ((enter, leave), last_err) = v.2;
+        let expr = Expr::AbsMapping(AbsMapping { trigger: v.0.0, enter, leave });
+        Ok((next, (expr, last_err)))
+    })
+}
+
+/// The block a mapping maps to, blocks marked 'async' don't wait for other mappings to finish.
+fn mapping_block(input: &str) -> ResNew<&str, Block> {
+    let (input, is_async) = opt(tuple((tag_custom("async"), ws1)))(input)?;
+    let (input, (mut block, last_err)) = block(input)?;
+    block.is_async = is_async.is_some();
+    Ok((input, (block, last_err)))
+}
+
+fn key_action_block(actions: Vec<ParsedKeyAction>) -> Block {
+    Block::new().tap_mut(|b| b.statements = actions
+        .to_key_actions()
+        .into_iter()
+        .map(|v| Stmt::Expr(Expr::KeyAction(v)))
+        .collect())
+}
+
+fn plain_key(input: &str) -> ResNew<&str, Key> {
+    alt((
+        map(tuple((tag_custom("{"), key, tag_custom("}"))), |v| v.1.0),
+        map(key, |v| v.0),
+    ))(input).and_then(|(next, (key, flags))| {
+        if flags != KeyModifierFlags::new() {
+            return Err(make_generic_nom_err_options(input, vec!["key without modifiers".to_string()]));
+        }
+        Ok((next, (key, None)))
+    })
+}
+
+pub(super) fn key_mapping_chord(input: &str) -> ResNew<&str, Expr> {
+    tuple((
+        plain_key,
+        many1(tuple((tag_custom("+"), plain_key))),
+        tag_custom("::"),
+        alt((
+            map(tuple((ws0, mapping_block)), |(_, v)| v),
+            map(key_sequence, |(v, last_err)| (key_action_block(v), last_err)),
+            map(key_action_with_flags, |(v, last_err)| (key_action_block(vec![v]), last_err)),
+        )),
+    ))(input).and_then(|(next, v)| {
+        let mut keys = vec![v.0.0];
+        for (_, (key, _)) in v.1 {
+            if keys.contains(&key) { return Err(make_generic_nom_err_options(input, vec!["distinct chord keys".to_string()])); }
+            keys.push(key);
+        }
+
+        let expr = Expr::ChordMapping(ChordMapping { keys, to: v.3.0 });
+        Ok((next, (expr, v.3.1)))
+    })
+}
+
+pub(super) fn key_mapping_sequence(input: &str) -> ResNew<&str, Expr> {
+    tuple((
+        plain_key,
+        many1(tuple((ws0, tag_custom(","), ws0, plain_key))),
+        tag_custom("::"),
+        alt((
+            map(tuple((ws0, mapping_block)), |(_, v)| v),
+            map(key_sequence, |(v, last_err)| (key_action_block(v), last_err)),
+            map(key_action_with_flags, |(v, last_err)| (key_action_block(vec![v]), last_err)),
+        )),
+    ))(input).and_then(|(next, v)| {
+        let mut keys = vec![v.0.0];
+        for (_, _, _, (key, _)) in v.1 { keys.push(key); }
+
+        let expr = Expr::SequenceMapping(SequenceMapping { keys, to: v.3.0 });
+        Ok((next, (expr, v.3.1)))
+    })
+}
+
 pub(super) fn key_mapping(input: &str) -> ResNew<&str, Expr> {
     tuple((
         key_action_with_flags,
         tag_custom("::"),
         ws0,
-        block,
+        mapping_block,
     ))(input).and_then(|(next, v)| {
         let ((from, _), (to, last_err)) = (v.0, v.3);
 
@@ -106,6 +373,19 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn test_key_mapping_async() {
+        assert_eq!(key_mapping("a::async { sleep(100); }"), nom_ok( Expr::map_key_click_block(
+            KeyClickActionWithMods::new(*KEY_A),
+            nom_eval(block("{ sleep(100); }")).tap_mut(|b| b.is_async = true),
+        )));
+
+        assert_eq!(key_mapping_chord("j+k::async {}"), nom_ok( Expr::ChordMapping(ChordMapping {
+            keys: vec![*KEY_J, *KEY_K],
+            to: Block::new().tap_mut(|b| b.is_async = true),
+        })));
+    }
+
     #[test]
     fn test_key_sequence() {
         assert_eq!(key_mapping_inline("a::\"ab\""), nom_ok( Expr::KeyMapping(vec![
@@ -126,6 +406,107 @@ mod tests {
         ])));
     }
 
+    #[test]
+    fn test_key_mapping_tap_hold() {
+        assert_eq!(key_mapping_tap_hold("capslock::tap_hold(esc, ctrl)"), nom_ok(Expr::TapHoldMapping(TapHoldMapping {
+            key: *KEY_CAPSLOCK,
+            tap: KeyClickActionWithMods::new(*KEY_ESC),
+            hold: KeyClickActionWithMods::new(*KEY_LEFT_CTRL),
+            tapping_term: TAP_HOLD_DEFAULT_TAPPING_TERM,
+            interrupt: TapHoldInterrupt::Permissive,
+        })));
+
+        assert_eq!(key_mapping_tap_hold("a::tap_hold(a, ^b, 150, \"hold\")"), nom_ok(Expr::TapHoldMapping(TapHoldMapping {
+            key: *KEY_A,
+            tap: KeyClickActionWithMods::new(*KEY_A),
+            hold: KeyClickActionWithMods::new(*KEY_B).tap_mut(|v| { v.modifiers.ctrl(); }),
+            tapping_term: time::Duration::from_millis(150),
+            interrupt: TapHoldInterrupt::Hold,
+        })));
+
+        assert!(matches!(key_mapping_tap_hold("a::tap_hold(a, b, 150, \"foo\")"), Err(..)));
+        assert!(matches!(key_mapping_tap_hold("{a down}::tap_hold(a, b)"), Err(..)));
+    }
+
+    #[test]
+    fn test_key_mapping_chord() {
+        assert_eq!(key_mapping_chord("j+k::esc"), nom_ok(Expr::ChordMapping(ChordMapping {
+            keys: vec![*KEY_J, *KEY_K],
+            to: Block::new().tap_mut(|b| {
+                b.statements = vec![
+                    Stmt::Expr(Expr::KeyAction(KeyAction::new(*KEY_ESC, TYPE_DOWN))),
+                    Stmt::Expr(Expr::KeyAction(KeyAction::new(*KEY_ESC, TYPE_UP))),
+                ];
+            }),
+        })));
+
+        assert_eq!(nom_no_last_err(key_mapping_chord("a+{f13}+c::{ print(\"hi\"); }")), nom_ok(Expr::ChordMapping(ChordMapping {
+            keys: vec![*KEY_A, Key::from_str(&EventType::EV_KEY, "KEY_F13").unwrap(), *KEY_C],
+            to: nom_eval(block("{ print(\"hi\"); }")),
+        })));
+
+        assert!(matches!(key_mapping_chord("j::k"), Err(..)));
+        assert!(matches!(key_mapping_chord("j+j::k"), Err(..)));
+        assert!(matches!(key_mapping_chord("j+K::k"), Err(..)));
+    }
+
+    #[test]
+    fn test_key_mapping_sequence() {
+        assert_eq!(nom_no_last_err(key_mapping_sequence("{f13},g,c::{ print(\"hi\"); }")), nom_ok(Expr::SequenceMapping(SequenceMapping {
+            keys: vec![Key::from_str(&EventType::EV_KEY, "KEY_F13").unwrap(), *KEY_G, *KEY_C],
+            to: nom_eval(block("{ print(\"hi\"); }")),
+        })));
+
+        assert_eq!(key_mapping_sequence("g, g::esc"), nom_ok(Expr::SequenceMapping(SequenceMapping {
+            keys: vec![*KEY_G, *KEY_G],
+            to: Block::new().tap_mut(|b| {
+                b.statements = vec![
+                    Stmt::Expr(Expr::KeyAction(KeyAction::new(*KEY_ESC, TYPE_DOWN))),
+                    Stmt::Expr(Expr::KeyAction(KeyAction::new(*KEY_ESC, TYPE_UP))),
+                ];
+            }),
+        })));
+
+        assert!(matches!(key_mapping_sequence("g::c"), Err(..)));
+        assert!(matches!(key_mapping_sequence("^g,c::c"), Err(..)));
+    }
+
+    #[test]
+    fn test_key_mapping_mouse() {
+        assert_eq!(key_mapping_mouse("h::mouse(left)"), nom_ok(Expr::MouseKeyMapping(MouseKeyMapping {
+            key: *KEY_H,
+            action: MouseKeyAction::Left,
+        })));
+
+        assert_eq!(key_mapping_mouse("space::mouse( btn_left )"), nom_ok(Expr::MouseKeyMapping(MouseKeyMapping {
+            key: *KEY_SPACE,
+            action: MouseKeyAction::Click(Key::from_str(&EventType::EV_KEY, "BTN_LEFT").unwrap()),
+        })));
+
+        assert!(matches!(key_mapping_mouse("h::mouse(sideways)"), Err(..)));
+        assert!(matches!(key_mapping_mouse("^h::mouse(left)"), Err(..)));
+    }
+
+    #[test]
+    fn test_key_mapping_abs() {
+        let abs_x = Key::from_str(&EventType::EV_ABS, "ABS_X").unwrap().event_code;
+
+        assert_eq!(key_mapping_abs("{abs_x > 200}::right"), nom_ok(Expr::AbsMapping(AbsMapping {
+            trigger: AbsTrigger { axis: abs_x, comparison: AbsComparison::Greater, threshold: 200 },
+            enter: Block::new().tap_mut(|b| b.statements = vec![Stmt::Expr(Expr::KeyAction(KeyAction::new(*KEY_RIGHT, TYPE_DOWN)))]),
+            leave: Block::new().tap_mut(|b| b.statements = vec![Stmt::Expr(Expr::KeyAction(KeyAction::new(*KEY_RIGHT, TYPE_UP)))]),
+        })));
+
+        assert_eq!(nom_no_last_err(key_mapping_abs("{ abs_x < -200 }::{ print(\"left\"); }")), nom_ok(Expr::AbsMapping(AbsMapping {
+            trigger: AbsTrigger { axis: abs_x, comparison: AbsComparison::Less, threshold: -200 },
+            enter: nom_eval(block("{ print(\"left\"); }")),
+            leave: Block::new(),
+        })));
+
+        assert!(matches!(key_mapping_abs("{abs_x = 200}::right"), Err(..)));
+        assert!(matches!(key_mapping_abs("{rel_x > 200}::right"), Err(..)));
+    }
+
     #[test]
     fn test_key_mapping_complex() {
         // TODO add when implemented
@@ -0,0 +1,42 @@
+use super::*;
+
+pub(super) fn layer_statement(input: &str) -> ResNew<&str, Stmt> {
+    tuple((
+        tag_custom("layer"), ws1,
+        ident, ws0,
+        |input| scoped_block(MappingBlockScope::Layer, input),
+    ))(input)
+        .map(|(next, v)| {
+            let stmt = Stmt::Layer(v.2.0, v.4.0);
+            (next, (stmt, v.4.1))
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_statement() {
+        assert_eq!(
+            layer_statement("layer nav { h::left; }"),
+            nom_ok(Stmt::Layer("nav".to_string(), nom_eval(block("{ h::left; }")))),
+        );
+
+        assert_eq!(layer_statement("layer nav {}"), nom_ok(Stmt::Layer("nav".to_string(), Block::new())));
+        assert!(matches!(layer_statement("layer {}"), Err(..)));
+        assert!(matches!(layer_statement("layers nav {}"), Err(..)));
+    }
+
+    #[test]
+    fn test_layer_statement_unscoped_mappings() {
+        let errors = with_source("layer nav {\n  h::left;\n  j+k::esc;\n}\n", global_block).unwrap_err();
+        let errors: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(errors, vec!["err: at line 3:\n  j+k::esc;\n  ^\nchord mappings can't be used inside a layer block".to_string()]);
+
+        assert!(matches!(layer_statement("layer nav { if (true) { capslock::tap_hold(esc, ctrl); } }"), Err(..)));
+        assert!(matches!(layer_statement("layer nav { ::btw::by the way; }"), Err(..)));
+        assert!(layer_statement("layer nav { h::mouse(left); }").is_ok());
+    }
+}
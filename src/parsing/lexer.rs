@@ -0,0 +1,59 @@
+/// The bytes of a script that are code rather than part of a string or comment, along with their offset. Used where
+/// the script can't be parsed, i.e. while it's incomplete or has mistakes in it, strings and comments are skipped
+/// since they can contain anything, i.e. a '}' that doesn't close a block.
+pub struct CodeBytes<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+pub fn code_bytes(text: &str) -> CodeBytes<'_> {
+    CodeBytes { text, offset: 0 }
+}
+
+impl Iterator for CodeBytes<'_> {
+    type Item = (usize, u8);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let offset = self.offset;
+            let ch = *self.text.as_bytes().get(offset)?;
+            // the offset can be inside a multi-byte character, its bytes never start a string or comment
+            let rest = self.text.get(offset..).unwrap_or("");
+
+            self.offset = match ch {
+                // unterminated strings and comments run until the end of the text
+                b'"' => offset + rest[1..].find('"').map_or(rest.len(), |end| end + 2),
+                b'/' if rest.starts_with("//") => offset + rest.find('\n').unwrap_or(rest.len()),
+                b'/' if rest.starts_with("/*") => offset + rest.find("*/").map_or(rest.len(), |end| end + 2),
+                _ => {
+                    self.offset += 1;
+                    return Some((offset, ch));
+                }
+            };
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(text: &str) -> String {
+        code_bytes(text).map(|(_, ch)| ch as char).collect()
+    }
+
+    #[test]
+    fn test_code_bytes() {
+        assert_eq!(code("a::{ print(\"}\"); }"), "a::{ print(); }");
+        assert_eq!(code("a // }\nb /* { */ c"), "a \nb  c");
+        assert_eq!(code("a \"b"), "a ");
+        assert_eq!(code("a /* b"), "a ");
+
+        let offsets: Vec<usize> = code_bytes("\"x\"y").map(|(offset, _)| offset).collect();
+        assert_eq!(offsets, vec![3]);
+
+        let offsets: Vec<usize> = code_bytes("é\"x\"").map(|(offset, _)| offset).collect();
+        assert_eq!(offsets, vec![0, 1]);
+    }
+}
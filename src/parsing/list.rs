@@ -0,0 +1,106 @@
+use nom::combinator::not;
+use nom::multi::many1;
+
+use super::*;
+
+pub(super) fn list(input: &str) -> ResNew<&str, Expr> {
+    let (input, _) = tag_custom("[")(input)
+        .map_err(|_: NomErr<CustomError<_>>| make_generic_nom_err_options(input, vec!["list".to_string()]))?;
+
+    tuple((
+        ws0,
+        opt(tuple((
+            expr,
+            ws0,
+            many0(tuple((
+                tag_custom(","),
+                ws0,
+                expr,
+                ws0,
+            ))),
+            opt(tag_custom(",")),
+            ws0,
+        ))),
+        tag_custom("]"),
+    ))(input).map(|(next, parts)| {
+        let items = match parts.1 {
+            Some(items) => {
+                let mut rest: Vec<Expr> = items.2.into_iter().map(|x| x.2.0).collect();
+                rest.insert(0, items.0.0);
+                rest
+            }
+            None => vec![],
+        };
+        (next, (Expr::List(items), None))
+    })
+}
+
+/// Parses a single index operation, i.e. '[2]'.
+pub(super) fn index(input: &str) -> ResNew<&str, Expr> {
+    tuple((tag_custom("["), ws0, expr, ws0, tag_custom("]")))(input)
+        .map(|(next, (_, _, (index, _), _, _))| (next, (index, None)))
+}
+
+pub(super) fn index_assignment(input: &str) -> ResNew<&str, Expr> {
+    tuple((
+        ident,
+        many1(index),
+        ws0,
+        tag_custom("="),
+        not(tag("=")),
+        ws0,
+        expr,
+    ))(input).map(|(next, parts)| {
+        let mut indices: Vec<Expr> = parts.1.into_iter().map(|(index, _)| index).collect();
+        let last = indices.pop().unwrap();
+
+        // all but the last index select the collection that gets modified
+        let target = indices.into_iter()
+            .fold(Expr::Name(parts.0.0), |acc, index| Expr::Index(Box::new(acc), Box::new(index)));
+
+        (next, (spanned(input, Expr::AssignIndex(Box::new(target), Box::new(last), Box::new(parts.6.0))), None))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(value: f64) -> Expr { Expr::Value(ValueType::Number(value)) }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(list("[]"), nom_ok(Expr::List(vec![])));
+        assert_eq!(nom_no_last_err(list("[1, \"a\", [2]]")), nom_ok(Expr::List(vec![
+            num(1.0),
+            Expr::Value(ValueType::String("a".to_string())),
+            Expr::List(vec![num(2.0)]),
+        ])));
+        assert_eq!(nom_no_last_err(list("[\n  1,\n  2,\n]")), nom_ok(Expr::List(vec![num(1.0), num(2.0)])));
+        assert!(matches!(list("[1 2]"), Err(..)));
+    }
+
+    #[test]
+    fn test_index() {
+        assert_eq!(nom_no_last_err(expr("xs[0][i]")), nom_ok(Expr::Index(
+            Box::new(Expr::Index(Box::new(Expr::Name("xs".to_string())), Box::new(num(0.0)))),
+            Box::new(Expr::Name("i".to_string())),
+        )));
+    }
+
+    #[test]
+    fn test_index_assignment() {
+        assert_eq!(nom_no_last_err(index_assignment("xs[0] = 5")), nom_ok(Expr::AssignIndex(
+            Box::new(Expr::Name("xs".to_string())),
+            Box::new(num(0.0)),
+            Box::new(num(5.0)),
+        )));
+        assert_eq!(nom_no_last_err(index_assignment("xs[0][1] = 5")), nom_ok(Expr::AssignIndex(
+            Box::new(Expr::Index(Box::new(Expr::Name("xs".to_string())), Box::new(num(0.0)))),
+            Box::new(num(1.0)),
+            Box::new(num(5.0)),
+        )));
+        assert!(matches!(index_assignment("xs[0] == 5"), Err(..)));
+    }
+}
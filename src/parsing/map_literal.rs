@@ -0,0 +1,68 @@
+use super::*;
+
+/// Parses a map literal, i.e. '{"h": "left", "l": "right"}'. Keys need to be string literals so that maps can be
+/// told apart from blocks and key actions right after the opening brace.
+pub(super) fn map_literal(input: &str) -> ResNew<&str, Expr> {
+    let (input, _) = tag_custom("{")(input)
+        .map_err(|_: NomErr<CustomError<_>>| make_generic_nom_err_options(input, vec!["map".to_string()]))?;
+
+    tuple((
+        ws0,
+        opt(tuple((
+            map_entry,
+            ws0,
+            many0(tuple((
+                tag_custom(","),
+                ws0,
+                map_entry,
+                ws0,
+            ))),
+            opt(tag_custom(",")),
+            ws0,
+        ))),
+        tag_custom("}"),
+    ))(input).map(|(next, parts)| {
+        let entries = match parts.1 {
+            Some(entries) => {
+                let mut rest: Vec<(String, Expr)> = entries.2.into_iter().map(|x| x.2.0).collect();
+                rest.insert(0, entries.0.0);
+                rest
+            }
+            None => vec![],
+        };
+        (next, (Expr::Map(entries), None))
+    })
+}
+
+fn map_entry(input: &str) -> ResNew<&str, (String, Expr)> {
+    tuple((
+        string,
+        ws0,
+        tag_custom(":"),
+        ws0,
+        expr,
+    ))(input).and_then(|(next, parts)| {
+        let key = match parts.0.0 {
+            Expr::Value(ValueType::String(key)) => key,
+            _ => return Err(make_generic_nom_err_new(input)),
+        };
+        Ok((next, ((key, parts.4.0), None)))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_literal() {
+        assert_eq!(map_literal("{}"), nom_ok(Expr::Map(vec![])));
+        assert_eq!(nom_no_last_err(map_literal("{\"h\": \"left\", \"n\": 1,}")), nom_ok(Expr::Map(vec![
+            ("h".to_string(), Expr::Value(ValueType::String("left".to_string()))),
+            ("n".to_string(), Expr::Value(ValueType::Number(1.0))),
+        ])));
+        assert!(matches!(map_literal("{ a::b; }"), Err(..)));
+        assert!(matches!(map_literal("{a down}"), Err(..)));
+    }
+}
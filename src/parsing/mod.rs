@@ -11,12 +11,14 @@ use nom::multi::many0;
 use nom::sequence::*;
 use tap::Tap;
 
+use break_statement::*;
 use continue_statement::*;
 use custom_combinators::*;
 use error::*;
 use expression::*;
 use for_loop::*;
 use function::*;
+use hotstring::*;
 use identifier::*;
 use if_statement::*;
 use key::*;
@@ -24,20 +26,29 @@ use key_action::*;
 use key_mapping::*;
 use key_sequence::*;
 use lambda::*;
+use list::*;
+use map_literal::*;
+use layer_statement::*;
+use device_statement::*;
 use primitives::*;
 use return_statement::*;
+use span::*;
 #[cfg(test)]
 use tests::*;
 use variable::*;
+use while_loop::*;
 
 use crate::*;
 
 pub mod parser;
+pub mod lexer;
 mod return_statement;
 mod continue_statement;
+mod break_statement;
 mod custom_combinators;
 mod expression;
 mod function;
+mod hotstring;
 mod identifier;
 mod if_statement;
 mod key;
@@ -45,9 +56,15 @@ mod key_action;
 mod key_mapping;
 mod key_sequence;
 mod lambda;
+mod list;
+mod map_literal;
+mod layer_statement;
+mod device_statement;
 mod primitives;
+mod span;
 mod variable;
 mod for_loop;
+mod while_loop;
 mod error;
 
 
@@ -55,8 +72,14 @@ fn stmt(input: &str) -> ResNew<&str, Stmt> {
     alt((
         return_statement,
         continue_statement,
+        break_statement,
         if_stmt,
+        for_in_loop,
         for_loop,
+        while_loop,
+        loop_statement,
+        layer_statement,
+        device_statement,
         map(
             tuple((expr, tag_custom(";"))),
             |(v, _)| (Stmt::Expr(v.0), v.1),
@@ -65,8 +88,14 @@ fn stmt(input: &str) -> ResNew<&str, Stmt> {
     ))(input)
 }
 
+/// A statement along with its location in the script.
+fn spanned_stmt(input: &str) -> ResNew<&str, (Option<Span>, Stmt)> {
+    let (next, (stmt, last_err)) = stmt(input)?;
+    Ok((next, ((span_at(input), stmt), last_err)))
+}
+
 fn block_body(input: &str) -> ResNew<&str, Block> {
-    let res = stmt(input);
+    let res = spanned_stmt(input);
 
     let (input, (first_stmt, initial_last_err)) = match res {
         Ok(v) => v,
@@ -74,16 +103,18 @@ fn block_body(input: &str) -> ResNew<&str, Block> {
         Err(_) => return Ok((input, (Block::new(), None))),
     };
 
-    let (input, (pairs, mut last_err)) = many0_err(tuple((ws0, stmt)))(input)?;
+    let (input, (pairs, mut last_err)) = many0_err(tuple((ws0, spanned_stmt)))(input)?;
 
     if let Some(err) = initial_last_err {
         last_err = last_err.or(err);
     }
 
     let block = Block::new().tap_mut(|b| {
-        let mut statements: Vec<Stmt> = pairs.into_iter().map(|x| x.1.0).collect();
-        statements.insert(0, first_stmt);
+        let (spans, statements) = std::iter::once(first_stmt)
+            .chain(pairs.into_iter().map(|x| x.1.0))
+            .unzip();
         b.statements = statements;
+        b.spans = spans;
     });
 
     Ok((input, (block, Some(last_err))))
@@ -94,8 +125,9 @@ fn block(input: &str) -> ResNew<&str, Block> {
         .map_err(|_: NomErr<CustomError<_>>| make_generic_nom_err_options(input, vec!["block".to_string()]))?;
 
     let (input, _) = ws0(input)?;
-    let (input, (block, last_err)) = block_body(input)?;
+    let (input, (mut block, last_err)) = block_body(input)?;
     let (input, _) = ws0(input)?;
+    block.end = span_at(input);
     let (input, _) = match tag_custom("}")(input) {
         Ok(v) => v,
         Err(NomErr::Error(err)) => return Err(NomErr::Error(match last_err {
@@ -108,20 +140,78 @@ fn block(input: &str) -> ResNew<&str, Block> {
     Ok((input, (block, None)))
 }
 
-fn global_block(input: &str) -> ResNew<&str, Block> {
-    tuple((ws0, block_body, ws0),
-    )(input)
-        .and_then(|(next, v)| {
-            let body_res = v.1;
-            if !next.is_empty() {
-                return match body_res.1 {
-                    Some(err) => Err(NomErr::Error(err)),
-                    None => Err(make_generic_nom_err_new(input)),
+/// Parses the statements of the script one at a time. A statement that fails to parse is skipped up to the next
+/// statement boundary so that all errors in the script get reported at once.
+fn global_block(input: &str) -> std::result::Result<Block, Vec<parser::ParseError>> {
+    let mut block = Block::new();
+    let mut errors = vec![];
+
+    let mut rest = skip_ws(input);
+    while !rest.is_empty() {
+        take_notes();
+
+        match spanned_stmt(rest) {
+            Ok((next, ((span, stmt), _))) => {
+                block.statements.push(stmt);
+                block.spans.push(span);
+                rest = next;
+            }
+            Err(err) => {
+                let err = match err {
+                    NomErr::Error(err) | NomErr::Failure(err) => err,
+                    NomErr::Incomplete(_) => CustomError { input: rest, expected: vec!["statement".to_string()] },
                 };
+
+                // a note points at the actual mistake, the parse error is often just where the parser gave up
+                let mut notes = take_notes();
+                notes.sort();
+                notes.dedup();
+                if notes.is_empty() {
+                    errors.push(parser::ParseError { span: span_at(err.input), message: expected_message(&err) });
+                } else {
+                    errors.extend(notes.into_iter()
+                        .map(|(offset, message)| parser::ParseError { span: span_at(&input[offset..]), message }));
+                }
+
+                rest = skip_statement(rest);
+            }
+        }
+
+        rest = skip_ws(rest);
+    }
+
+    if !errors.is_empty() { return Err(errors); }
+    Ok(block)
+}
+
+fn skip_ws(input: &str) -> &str {
+    match ws0::<CustomError<&str>>(input) {
+        Ok((next, _)) => next,
+        Err(_) => input,
+    }
+}
+
+/// Skips past the end of the statement at the start of the input, which is the next ';' outside of braces or the '}'
+/// that closes the outermost brace. Braces can also surround keys, i.e. '{a down}::b;', so a closing brace that's
+/// followed by '::' or ';' doesn't end the statement. Strings and comments are skipped over since they can contain
+/// either.
+fn skip_statement(input: &str) -> &str {
+    let mut depth: usize = 0;
+
+    for (i, ch) in lexer::code_bytes(input) {
+        match ch {
+            b'{' => depth += 1,
+            b'}' => {
+                depth = depth.saturating_sub(1);
+                let after = input[i + 1..].trim_start();
+                if depth == 0 && !after.starts_with("::") && !after.starts_with(';') { return &input[i + 1..]; }
             }
+            b';' if depth == 0 => return &input[i + 1..],
+            _ => {}
+        }
+    }
 
-            Ok((next, body_res))
-        })
+    ""
 }
 
 
@@ -207,4 +297,34 @@ mod tests {
                        b.statements = vec![nom_eval(if_stmt("if(true){a::b;}"))];
                    })));
     }
+
+    #[test]
+    fn test_skip_statement() {
+        assert_eq!(skip_statement("let a = ;\nb::c;"), "\nb::c;");
+        assert_eq!(skip_statement("if (x) { a::b; c:: }\nd::e;"), "\nd::e;");
+        assert_eq!(skip_statement("{a down}::b; c::d;"), " c::d;");
+        assert_eq!(skip_statement("print(\"};\"); a::b;"), " a::b;");
+        assert_eq!(skip_statement("a::b // ; }\n; c::d;"), " c::d;");
+        assert_eq!(skip_statement("a::b"), "");
+    }
+
+    #[test]
+    fn test_global_block_reports_all_errors() {
+        let errors = with_source("a::b;\nfoo(;\nc::d;\nbar(1 2);\ne::f;\n", global_block).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|err| err.span.as_ref().unwrap().line).collect();
+        assert_eq!(lines, vec![2, 4]);
+    }
+
+    #[test]
+    fn test_unknown_key_suggestion() {
+        let errors = with_source("a::b;\nentert::a;\n{capslok down}::b;\n", global_block).unwrap_err();
+        let errors: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+        assert_eq!(errors, vec![
+            "err: at line 2:\nentert::a;\n^\nunknown key 'entert', did you mean 'enter'?".to_string(),
+            "err: at line 3:\n{capslok down}::b;\n ^\nunknown key 'capslok', did you mean 'capslock'?".to_string(),
+        ]);
+
+        // not a key position, 'entert' could be a variable
+        assert!(with_source("let a = entert;", global_block).is_ok());
+    }
 }
\ No newline at end of file
@@ -1,32 +1,59 @@
+use std::fmt;
+use std::fmt::Formatter;
 
 use super::*;
 
-pub(crate) fn parse_script<>(raw_script: &str) -> Result<Block> {
-    match global_block(raw_script) {
-        Ok((v, (block, last_err))) => {
-            if v.is_empty() {
-                Ok(block)
-            } else {
-                if let Some(last_err) = last_err {
-                    let err = convert_custom_error(raw_script, &last_err);
-                    eprintln!("{}", &*err);
-                } else {
-                    eprintln!("generic error");
-                }
-
-                Err(anyhow!("parsing failed unwrapping..."))
-                // Err(anyhow!("parsing failed, remaining input:\n'{}'\n", v))
+/// An error in the script that prevents it from being parsed.
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => {
+                writeln!(f, "err: at line {}:", span.line)?;
+                span.write_snippet(f)?;
+                write!(f, "\n{}", self.message)
             }
+            None => write!(f, "err: {}", self.message),
         }
-        Err(err) => {
-            if let NomErr::Error(err) = &err {
-                let err = convert_custom_error(raw_script, err);
-                eprintln!("{}", &*err);
-            }
+    }
+}
+
+/// The parts of a script that the parsed [Block] doesn't keep, which are needed to print the script back.
+#[derive(Clone, Debug, Default)]
+pub struct Trivia {
+    /// the byte offset and text of each comment
+    pub comments: Vec<(usize, String)>,
+    /// the byte range of each key mapping, mappings are desugared when they're parsed so they can only be printed
+    /// the way they were written
+    pub mappings: Vec<(usize, usize)>,
+}
+
+/// Parses the script, a script with mistakes in several statements results in an error for each of them.
+pub fn parse(raw_script: &str) -> std::result::Result<Block, Vec<ParseError>> {
+    with_source(raw_script, global_block)
+}
 
-            Err(anyhow!("parsing failed: {}", err))
+/// Parses the script along with its comments and the source text of its key mappings.
+pub fn parse_with_trivia(raw_script: &str) -> std::result::Result<(Block, Trivia), Vec<ParseError>> {
+    with_source(raw_script, |input| {
+        let block = global_block(input)?;
+        Ok((block, take_trivia()))
+    })
+}
+
+/// Parses the script, all parse errors are reported on stderr.
+pub(crate) fn parse_script(raw_script: &str) -> Result<Block> {
+    parse(raw_script).map_err(|errors| {
+        for err in &errors {
+            eprintln!("{}\n", err);
         }
-    }
+        anyhow!("parsing failed with {} error(s)", errors.len())
+    })
 }
 
 pub(crate) fn parse_key_sequence(raw: &str) -> Result<Vec<KeyAction>> {
@@ -45,7 +72,7 @@ pub(crate) fn parse_key_sequence(raw: &str) -> Result<Vec<KeyAction>> {
 }
 
 pub(crate) fn parse_key_action_with_mods(from: &str, to: Block) -> Result<Expr> {
-    let from = key_action_with_flags(from).expect("failed to parse mapping trigger");
+    let from = key_action_with_flags(from).map_err(|_| anyhow!("failed to parse mapping trigger '{}'", from))?;
     if !from.0.is_empty() { return Err(anyhow!("failed to parse mapping trigger")); }
     let from = from.1;
 
@@ -57,6 +84,23 @@ pub(crate) fn parse_key_action_with_mods(from: &str, to: Block) -> Result<Expr>
     Ok(expr)
 }
 
+/// Parses keys joined by '+', i.e. 'ctrl+alt+backspace'.
+pub fn parse_key_combination(raw: &str) -> Result<Vec<Key>> {
+    let mut keys = vec![];
+    for name in raw.split('+') {
+        let name = name.trim().to_lowercase();
+        let key = match key(&name) {
+            Ok(("", ((key, _), _))) => key,
+            _ => return Err(match crate::suggestion::suggest_key(&name) {
+                Some(suggestion) => anyhow!("unknown key '{}', did you mean '{}'?", name, suggestion),
+                None => anyhow!("unknown key '{}'", name),
+            }),
+        };
+        if !keys.contains(&key) { keys.push(key); }
+    }
+    Ok(keys)
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -68,4 +112,12 @@ mod tests {
                    nom_eval(key_sequence("\"hello{enter}world\"")).to_key_actions(),
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_key_combination() {
+        assert_eq!(parse_key_combination("ctrl+alt+backspace").unwrap(), vec![*KEY_LEFT_CTRL, *KEY_LEFT_ALT, *KEY_BACKSPACE]);
+        assert_eq!(parse_key_combination("KEY_LEFTCTRL + Esc").unwrap(), vec![*KEY_LEFT_CTRL, *KEY_ESC]);
+        assert_eq!(parse_key_combination("ctrl+bakspace").unwrap_err().to_string(), "unknown key 'bakspace', did you mean 'backspace'?");
+        assert!(parse_key_combination("ctrl+").is_err());
+    }
+}
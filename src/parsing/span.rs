@@ -0,0 +1,223 @@
+use std::cell::RefCell;
+
+use super::*;
+
+/// The script that is currently being parsed, the parser only sees the remaining input and uses this to work out
+/// where in the script it is.
+struct Source {
+    start: usize,
+    len: usize,
+    /// byte offset of the start of each line along with the line itself
+    lines: Vec<(usize, Arc<str>)>,
+    /// likely mistakes that were found while parsing, see [note]
+    notes: Vec<(usize, String)>,
+    /// see [parser::Trivia]
+    trivia: parser::Trivia,
+}
+
+thread_local! {
+    static SOURCE: RefCell<Option<Source>> = const { RefCell::new(None) };
+}
+
+/// Runs the parser with the given script as the source that spans refer to.
+pub(super) fn with_source<'a, T>(raw_script: &'a str, parse: impl FnOnce(&'a str) -> T) -> T {
+    let mut offset = 0;
+    let lines = raw_script.split('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len() + 1;
+            (start, Arc::from(line))
+        })
+        .collect();
+
+    SOURCE.with(|source| *source.borrow_mut() = Some(Source {
+        start: raw_script.as_ptr() as usize,
+        len: raw_script.len(),
+        lines,
+        notes: vec![],
+        trivia: parser::Trivia::default(),
+    }));
+    let res = parse(raw_script);
+    SOURCE.with(|source| *source.borrow_mut() = None);
+    res
+}
+
+impl Source {
+    /// The byte offset of the given input in the script along with the index of the line it's on.
+    fn locate(&self, input: &str) -> Option<(usize, usize)> {
+        let offset = (input.as_ptr() as usize).checked_sub(self.start)?;
+        if offset > self.len { return None; }
+
+        let line_idx = match self.lines.binary_search_by_key(&offset, |(start, _)| *start) {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        };
+        Some((offset, line_idx))
+    }
+}
+
+/// The location of the given input in the script, `None` if the input isn't part of the script that is being parsed.
+pub(super) fn span_at(input: &str) -> Option<Span> {
+    SOURCE.with(|source| {
+        let source = source.borrow();
+        let source = source.as_ref()?;
+
+        let (offset, line_idx) = source.locate(input)?;
+        let (line_start, line) = &source.lines[line_idx];
+        let column = line.get(..offset - line_start).map(|prefix| prefix.chars().count()).unwrap_or(0) + 1;
+
+        Some(Span { line: line_idx + 1, column, source_line: line.clone() })
+    })
+}
+
+/// Attaches the location of the given input to an expression that can fail at runtime, expressions that aren't
+/// parsed from a script are returned as they are.
+pub(super) fn spanned(input: &str, expr: Expr) -> Expr {
+    match span_at(input) {
+        Some(span) => Expr::Spanned(Box::new(expr), span),
+        None => expr,
+    }
+}
+
+/// The part of the line before the given input, empty if the input isn't part of the script that is being parsed.
+pub(super) fn text_before(input: &str) -> String {
+    SOURCE.with(|source| {
+        let source = source.borrow();
+        source.as_ref()
+            .and_then(|source| {
+                let (offset, line_idx) = source.locate(input)?;
+                let (line_start, line) = &source.lines[line_idx];
+                line.get(..offset - line_start).map(|prefix| prefix.to_string())
+            })
+            .unwrap_or_default()
+    })
+}
+
+/// Records a likely mistake at the given input, i.e. a misspelled key name. The parser backtracks a lot so a note
+/// only gets reported if the statement it was found in fails to parse.
+pub(super) fn note(input: &str, message: String) {
+    SOURCE.with(|source| {
+        if let Some(source) = source.borrow_mut().as_mut() {
+            if let Some((offset, _)) = source.locate(input) {
+                source.notes.push((offset, message));
+            }
+        }
+    })
+}
+
+/// Records a comment that starts at the given input.
+pub(super) fn comment(input: &str, text: &str) {
+    SOURCE.with(|source| {
+        if let Some(source) = source.borrow_mut().as_mut() {
+            if let Some((offset, _)) = source.locate(input) {
+                source.trivia.comments.push((offset, text.to_string()));
+            }
+        }
+    })
+}
+
+/// Records the source text of a key mapping, from the given input up to where the rest of the input starts.
+pub(super) fn mapping(input: &str, next: &str) {
+    SOURCE.with(|source| {
+        if let Some(source) = source.borrow_mut().as_mut() {
+            if let (Some((start, _)), Some((end, _))) = (source.locate(input), source.locate(next)) {
+                source.trivia.mappings.push((start, end));
+            }
+        }
+    })
+}
+
+/// Removes the trivia that was recorded so far. The parser backtracks, so the same comment or mapping can be
+/// recorded more than once.
+pub(super) fn take_trivia() -> parser::Trivia {
+    SOURCE.with(|source| {
+        let mut trivia = source.borrow_mut().as_mut()
+            .map(|source| std::mem::take(&mut source.trivia))
+            .unwrap_or_default();
+
+        trivia.comments.sort();
+        trivia.comments.dedup();
+        trivia.mappings.sort();
+        trivia.mappings.dedup();
+        trivia
+    })
+}
+
+/// Removes the notes that were recorded so far, along with the byte offset in the script they refer to.
+pub(super) fn take_notes() -> Vec<(usize, String)> {
+    SOURCE.with(|source| source.borrow_mut().as_mut().map(|source| std::mem::take(&mut source.notes)).unwrap_or_default())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_at() {
+        let script = "let a = 1;\n  print(a);\n";
+        with_source(script, |input| {
+            let span = span_at(&input[13..]).unwrap();
+            assert_eq!((span.line, span.column, &*span.source_line), (2, 3, "  print(a);"));
+
+            let span = span_at(input).unwrap();
+            assert_eq!((span.line, span.column), (1, 1));
+        });
+
+        assert!(span_at(script).is_none());
+    }
+
+    #[test]
+    fn test_statement_spans() {
+        let block = parser::parse_script("let a = 1;\n\nif (a == 1) {\n  print(a);\n}\n").unwrap();
+
+        let lines: Vec<usize> = block.spans.iter().map(|span| span.as_ref().unwrap().line).collect();
+        assert_eq!(lines, vec![1, 3]);
+
+        let if_block = match &block.statements[1] {
+            Stmt::If(pairs, _) => &pairs[0].1,
+            _ => unreachable!(),
+        };
+        let span = if_block.spans[0].as_ref().unwrap();
+        assert_eq!((span.line, span.column), (4, 3));
+    }
+    #[test]
+    fn test_expression_spans() {
+        let block = parser::parse_script("let a = 1 +\n  f(b) * c[0];\n").unwrap();
+
+        let value = match &block.statements[0] {
+            Stmt::Expr(Expr::Init(_, value)) => value,
+            _ => unreachable!(),
+        };
+        let (add, add_span) = match &**value {
+            Expr::Spanned(add, span) => (add, span),
+            _ => unreachable!(),
+        };
+        assert_eq!((add_span.line, add_span.column), (1, 11));
+
+        let (call, index) = match &**add {
+            Expr::Add(_, right) => match right.without_span() {
+                Expr::Mul(call, index) => (call, index),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let columns: Vec<(usize, usize)> = [call, index].iter()
+            .map(|expr| match &***expr {
+                Expr::Spanned(_, span) => (span.line, span.column),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(columns, vec![(2, 3), (2, 11)]);
+    }
+
+    #[test]
+    fn test_expressions_compare_without_spans() {
+        let block = parser::parse_script("let a = 1 + f(b) * c[0];\n").unwrap();
+        let moved = parser::parse_script("\n  let a = 1 +\n    f(b) * c[0];\n").unwrap();
+        assert_eq!(block, moved);
+
+        let changed = parser::parse_script("let a = 1 + f(b) * c[1];\n").unwrap();
+        assert_ne!(block, changed);
+    }
+}
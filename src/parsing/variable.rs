@@ -18,10 +18,10 @@ pub(super) fn variable_initialization(input: &str) -> ResNew<&str, Expr> {
             let (input_before_expr, ident, expr) = (parts.0.0, parts.0.1.1, parts.2);
             let ((name, _), (expr, last_err)) = (ident, expr);
 
-            match expr {
+            match expr.without_span() {
                 Expr::Name(_) | Expr::Value(_) | Expr::Lambda(_, _) | Expr::FunctionCall(_, _) | Expr::Eq(_, _) | Expr::Neq(_, _) |
                 Expr::LT(_, _) | Expr::GT(_, _) | Expr::Add(_, _) | Expr::Sub(_, _) | Expr::Div(_, _) |
-                Expr::Mul(_, _) | Expr::Neg(_) | Expr::And(_, _) | Expr::Or(_, _)
+                Expr::Mul(_, _) | Expr::Neg(_) | Expr::And(_, _) | Expr::Or(_, _) | Expr::List(_) | Expr::Map(_) | Expr::Index(_, _)
                 => {}
                 _ => { return Err(make_generic_nom_err_options(input_before_expr, vec!["valid initialization expression".to_string()])); }
             };
@@ -39,7 +39,7 @@ pub(super) fn variable_assignment(input: &str) -> ResNew<&str, Expr> {
         ws0,
         expr,
     ))(input).map(|(next, parts)|
-        (next, (Expr::Assign(parts.0.0, Box::new(parts.4.0)), None))
+        (next, (spanned(input, Expr::Assign(parts.0.0, Box::new(parts.4.0))), None))
     )
 }
 
@@ -0,0 +1,51 @@
+use super::*;
+
+pub(super) fn while_loop(input: &str) -> ResNew<&str, Stmt> {
+    tuple((
+        tag_custom("while"), ws0,
+        tag_custom("("), ws0,
+        expr, ws0,
+        tag_custom(")"), ws0,
+        block,
+    ))(input)
+        .map(|(next, v)| {
+            let stmt = Stmt::While(v.4.0, v.8.0);
+            (next, (stmt, None))
+        })
+}
+
+pub(super) fn loop_statement(input: &str) -> ResNew<&str, Stmt> {
+    tuple((
+        tag_custom("loop"), ws0,
+        block,
+    ))(input)
+        .map(|(next, v)| {
+            let stmt = Stmt::Loop(v.2.0);
+            (next, (stmt, None))
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_while_loop() {
+        assert_eq!(
+            while_loop("while(i < 20){ i = i+1; }"),
+            nom_ok(Stmt::While(
+                Expr::LT(Box::new(Expr::Name("i".to_string())), Box::new(Expr::Value(ValueType::Number(20.0)))),
+                nom_eval(block("{ i = i+1; }")),
+            ))
+        );
+
+        assert!(matches!(while_loop("while {}"), Err(..)));
+    }
+
+    #[test]
+    fn test_loop_statement() {
+        assert_eq!(loop_statement("loop { break; }"), nom_ok(Stmt::Loop(nom_eval(block("{ break; }")))));
+        assert!(matches!(loop_statement("loop_count = 1;"), Err(..)));
+    }
+}
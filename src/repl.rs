@@ -0,0 +1,239 @@
+use std::io::BufRead;
+use std::thread;
+
+use crate::*;
+use crate::device::output_sink::Output;
+use crate::messaging::ExecutionMessage;
+use crate::parsing::{lexer, parser};
+
+const PROMPT: &str = "map2> ";
+const CONTINUATION_PROMPT: &str = "  ... ";
+
+/// Evaluates statements one at a time in a global scope that persists between them.
+pub struct Repl {
+    var_map: GuardedVarMap,
+    /// lines that don't form a complete input yet
+    pending: String,
+}
+
+/// How many more braces, brackets and parentheses the input opens than it closes, strings and comments are skipped.
+fn unclosed_brackets(input: &str) -> isize {
+    lexer::code_bytes(input)
+        .map(|(_, ch)| match ch {
+            b'{' | b'[' | b'(' => 1,
+            b'}' | b']' | b')' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Repl { var_map: GuardedVarMap::new(Mutex::new(VarMap::new(None))), pending: String::new() }
+    }
+
+    /// The prompt for the next line, which differs while the input is incomplete.
+    pub fn prompt(&self) -> &'static str {
+        if self.pending.is_empty() { PROMPT } else { CONTINUATION_PROMPT }
+    }
+
+    /// Adds a line of input, returns the input once it's complete, i.e. once every brace that was opened is closed.
+    pub fn push_line(&mut self, line: &str) -> Option<String> {
+        self.pending.push_str(line);
+        self.pending.push('\n');
+
+        if unclosed_brackets(&self.pending) > 0 { return None; }
+
+        let input = std::mem::take(&mut self.pending);
+        if input.trim().is_empty() { return None; }
+        Some(input)
+    }
+
+    /// Evaluates a complete input. The value of a trailing expression is returned so it can be shown, its ';' is
+    /// optional. Errors are already formatted for display.
+    pub async fn eval(&mut self, input: &str, amb: &mut Ambient<'_>) -> Result<Option<ValueType>> {
+        let block = match parser::parse(input) {
+            Ok(block) => block,
+            Err(errors) => {
+                let trimmed = input.trim_end();
+                match parser::parse(&format!("{};", trimmed)) {
+                    Ok(block) if !trimmed.ends_with(';') && !trimmed.ends_with('}') => block,
+                    _ => return Err(anyhow!("{}", errors.iter().map(|err| err.to_string()).collect::<Vec<_>>().join("\n\n"))),
+                }
+            }
+        };
+
+        let mut value = None;
+        for (idx, stmt) in block.statements.iter().enumerate() {
+            let span = block.spans.get(idx).and_then(Option::as_ref);
+
+            // statements are evaluated in the global scope so the variables they define are kept
+            let res = match stmt {
+                Stmt::Expr(expr) => eval_expr(expr, &self.var_map, amb).await.map(Some),
+                _ => eval_stmt(stmt, &self.var_map, amb).await.map(|_| None),
+            };
+            value = res.map_err(|err| anyhow!("error: {}", err.at(span)))?;
+        }
+
+        Ok(value.filter(|value| !matches!(value, ValueType::Void)))
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self { Self::new() }
+}
+
+/// Reads statements from stdin and evaluates them, mappings take effect on the given devices if there are any. Runs
+/// until stdin is closed or the script exits.
+pub async fn run(devices: Option<Vec<String>>, verbosity: i32, panic_chord: Vec<Key>, output: Output) -> Result<()> {
+    let (mut message_tx, mut message_rx) = mpsc::channel(128);
+    let (ev_writer_init_tx, mut ev_writer_init_rx) = oneshot::channel();
+    let (device_ev_tx, mut device_ev_rx) = mpsc::channel(128);
+
+    // without devices, events that the script sends go nowhere unless they are traced or recorded
+    let mut ev_writer_tx = match &devices {
+        Some(devices) => {
+            bind_udev_inputs(devices, panic_chord, output, ev_writer_init_tx, device_ev_tx).await?;
+            (&mut ev_writer_init_rx).await??
+        }
+        None => {
+            drop(device_ev_tx);
+            let (ev_writer_tx, mut ev_writer_rx) = mpsc::channel(128);
+            match output {
+                Output::Uinput => { task::spawn(async move { while ev_writer_rx.recv().await.is_some() {} }); }
+                _ => device::init_virtual_output_device(ev_writer_rx, &[], &output).await?,
+            }
+            ev_writer_tx
+        }
+    };
+
+    let (line_tx, mut line_rx) = mpsc::channel(1);
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if futures::executor::block_on(line_tx.send(line)).is_err() { return; }
+        }
+    });
+
+    // output goes through the message loop so it shows up in order with the output of 'print'
+    {
+        let mut message_tx = message_tx.clone();
+        let ev_writer_tx = ev_writer_tx.clone();
+        task::spawn(async move {
+            let mut repl = Repl::new();
+            let modifier_state = KeyModifierState::new();
+            let _ = message_tx.send(ExecutionMessage::Write(repl.prompt().to_string())).await;
+
+            while let Some(line) = line_rx.recv().await {
+                if let Some(input) = repl.push_line(&line) {
+                    let mut amb = Ambient {
+                        ev_writer_tx: ev_writer_tx.clone(),
+                        message_tx: Some(&mut message_tx),
+                        window_cycle_token: 0,
+                        in_window_change_handler: false,
+                        script_generation: 0,
+                        modifier_state: &modifier_state,
+                        layer: None,
+                        device: None,
+                        event_device: None,
+                        turn: None,
+                    };
+
+                    match repl.eval(&input, &mut amb).await {
+                        Ok(Some(value)) => { let _ = message_tx.send(ExecutionMessage::Write(format!("{}\n", value))).await; }
+                        Ok(None) => {}
+                        Err(err) => { let _ = message_tx.send(ExecutionMessage::Write(format!("{}\n", err))).await; }
+                    }
+                }
+                let _ = message_tx.send(ExecutionMessage::Write(repl.prompt().to_string())).await;
+            }
+
+            let _ = message_tx.send(ExecutionMessage::Write("\n".to_string())).await;
+            let _ = message_tx.send(ExecutionMessage::Exit(0)).await;
+        });
+    }
+
+    let mut stdout = io::stdout();
+    let mut stderr = io::stderr();
+    let mut state = State::new();
+    let mut mappings = CompiledKeyMappings::new();
+    let mut window_change_handlers = vec![];
+
+    loop {
+        tokio::select! {
+            Some((ev, device)) = device_ev_rx.recv() => {
+                event_handlers::handle_stdin_ev(&mut state, ev, device, &mut mappings, &mut ev_writer_tx,
+                    &mut message_tx, 0, verbosity).await?;
+            }
+            Some(msg) = message_rx.recv() => {
+                event_handlers::handle_execution_message(&mut stdout, &mut stderr, 0, msg, &mut state, &mut mappings,
+                    &mut window_change_handlers, &mut ev_writer_tx, &mut message_tx).await;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_line() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.prompt(), PROMPT);
+
+        assert_eq!(repl.push_line("let f = |x| {"), None);
+        assert_eq!(repl.prompt(), CONTINUATION_PROMPT);
+        assert_eq!(repl.push_line("  print(\"}\"); // }"), None);
+        assert_eq!(repl.push_line("};"), Some("let f = |x| {\n  print(\"}\"); // }\n};\n".to_string()));
+        assert_eq!(repl.prompt(), PROMPT);
+
+        assert_eq!(repl.push_line("  "), None);
+        assert_eq!(repl.push_line("1 + 2"), Some("1 + 2\n".to_string()));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_eval() -> Result<()> {
+        let (mut message_tx, mut message_rx) = mpsc::channel(128);
+        let (ev_writer_tx, _ev_writer_rx) = mpsc::channel(128);
+        let modifier_state = KeyModifierState::new();
+        let mut amb = Ambient {
+            ev_writer_tx,
+            message_tx: Some(&mut message_tx),
+            window_cycle_token: 0,
+            in_window_change_handler: false,
+            script_generation: 0,
+            modifier_state: &modifier_state,
+            layer: None,
+            device: None,
+            event_device: None,
+            turn: None,
+        };
+
+        let mut repl = Repl::new();
+        assert_eq!(repl.eval("let a = 2;", &mut amb).await?, None);
+        assert_eq!(repl.eval("let double = |x| { return x * 2; };", &mut amb).await?, None);
+        assert_eq!(repl.eval("double(a) + 1", &mut amb).await?, Some(ValueType::Number(5.0)));
+        assert_eq!(repl.eval("a = 3; a;", &mut amb).await?, Some(ValueType::Number(3.0)));
+        assert_eq!(repl.eval("if (a == 3) { print(\"three\"); }", &mut amb).await?, None);
+        assert_eq!(repl.eval("[a, \"b\"]", &mut amb).await?.unwrap().to_string(), "[3, \"b\"]");
+
+        let err = repl.eval("a +", &mut amb).await.unwrap_err();
+        assert!(err.to_string().starts_with("err: at line 1:"));
+
+        let err = repl.eval("a + \"b\" * 2", &mut amb).await.unwrap_err();
+        assert!(err.to_string().starts_with("error: at line 1:"), "{}", err);
+
+        drop(amb);
+        match message_rx.try_recv() {
+            Ok(ExecutionMessage::Write(out)) => assert_eq!(out, "three\n"),
+            _ => panic!("expected output of 'print'"),
+        }
+        Ok(())
+    }
+}
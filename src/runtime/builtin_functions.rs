@@ -1,29 +1,68 @@
-use evdev_rs::enums::int_to_ev_key;
+use evdev_rs::enums::{EV_REL, int_to_ev_key};
 use tokio::process::Command;
 
 use crate::*;
 use crate::messaging::ExecutionMessage;
 use crate::parsing::parser::{parse_key_action_with_mods, parse_key_sequence};
-
-pub async fn throw_error<'a>(err: anyhow::Error, exit_code: i32, amb: &mut Ambient<'a>) -> ValueType {
-    amb.message_tx.borrow_mut().as_ref().unwrap()
-        .send(ExecutionMessage::FatalError(err, exit_code))
-        .await.unwrap();
-    return ValueType::Void;
+use crate::suggestion::closest_match;
+
+/// The built-in functions along with the least and the most arguments they take, `None` if there's no upper limit.
+pub(crate) const BUILTIN_FUNCTIONS: &[(&str, usize, Option<usize>)] = &[
+    ("exit", 0, Some(1)),
+    ("send", 1, Some(1)),
+    ("send_modifier", 1, Some(1)),
+    ("active_window_class", 0, Some(0)),
+    ("event_device", 0, Some(1)),
+    ("on_window_change", 1, Some(1)),
+    ("set_chord_window", 1, Some(1)),
+    ("set_sequence_timeout", 1, Some(1)),
+    ("on_sequence_prefix", 1, Some(1)),
+    ("layer_push", 1, Some(1)),
+    ("layer_toggle", 1, Some(1)),
+    ("layer_pop", 0, Some(0)),
+    ("layer_active", 1, Some(1)),
+    ("mouse_move", 2, Some(2)),
+    ("scroll", 1, Some(1)),
+    ("set_mouse_keys_speed", 2, Some(2)),
+    ("set_mouse_keys_acceleration", 1, Some(2)),
+    ("set_mouse_keys_tick_rate", 1, Some(1)),
+    ("set_mouse_keys_scroll_speed", 1, Some(1)),
+    ("sleep", 1, Some(1)),
+    ("set_timeout", 2, Some(2)),
+    ("set_interval", 2, Some(2)),
+    ("clear_timer", 1, Some(1)),
+    ("print", 1, Some(1)),
+    ("len", 1, Some(1)),
+    ("push", 2, Some(2)),
+    ("pop", 1, Some(1)),
+    ("contains", 2, Some(2)),
+    ("keys", 1, Some(1)),
+    ("values", 1, Some(1)),
+    ("has", 2, Some(2)),
+    ("number_to_key", 1, Some(1)),
+    ("number_to_char", 1, Some(1)),
+    ("char_to_number", 1, Some(1)),
+    ("map_key", 2, Some(2)),
+    ("execute", 1, None),
+];
+
+/// Gets the argument at the given position, a missing argument is an error rather than `Void`.
+fn arg(name: &str, args: &[ValueType], idx: usize) -> Result<ValueType> {
+    args.get(idx).cloned()
+        .ok_or_else(|| anyhow!("argument error: function '{}' expected at least {} argument(s)", name, idx + 1))
 }
 
 pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &GuardedVarMap, amb: &mut Ambient<'_>) -> Result<ValueType> {
     let mut parsed_args = vec![];
     for expr in args {
-        let arg = eval_expr(expr, var_map, amb).await;
+        let arg = eval_expr(expr, var_map, amb).await?;
         parsed_args.push(arg);
     }
 
     match &**name {
         "exit" => {
-            let arg = args.get(0);
-            let val = match arg {
-                Some(arg) => eval_expr(arg, var_map, amb).await,
+            let val = match parsed_args.get(0) {
+                Some(arg) => arg.clone(),
                 _ => ValueType::Number(0.0),
             };
 
@@ -35,13 +74,13 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
             amb.message_tx.as_ref().unwrap().send(ExecutionMessage::Exit(exit_code)).await.unwrap();
         }
         "send" => {
-            let val = eval_expr(args.get(0).unwrap(), var_map, amb).await;
+            let val = arg(name, &parsed_args, 0)?;
             let val = match val {
                 ValueType::String(val) => val,
                 _ => return Err(anyhow!("invalid parameter passed to function 'send'")),
             };
 
-            let actions = parse_key_sequence(&*val).unwrap();
+            let actions = parse_key_sequence(&*val)?;
 
             for action in actions {
                 amb.ev_writer_tx.send(action.to_input_ev()).await.unwrap();
@@ -49,13 +88,13 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
             }
         }
         "send_modifier" => {
-            let val = eval_expr(args.get(0).unwrap(), var_map, amb).await;
+            let val = arg(name, &parsed_args, 0)?;
             let val = match val {
                 ValueType::String(val) => val,
                 _ => return Err(anyhow!("invalid parameter passed to function 'send'")),
             };
 
-            let actions = parse_key_sequence(&*val).unwrap();
+            let actions = parse_key_sequence(&*val)?;
 
             if actions.len() != 1 {
                 return Err(anyhow!("expected a single key action, got {}", actions.len()));
@@ -80,6 +119,25 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
                 return Ok(ValueType::String(active_window.class));
             }
         }
+        "event_device" => {
+            let device = match &amb.event_device {
+                Some(device) => device,
+                None => return Ok(ValueType::Void),
+            };
+
+            let value = match parsed_args.get(0) {
+                None => device.name.clone(),
+                Some(ValueType::String(property)) => match &**property {
+                    "name" => device.name.clone(),
+                    "path" => device.path.clone(),
+                    "id" => device.id(),
+                    _ => return Err(anyhow!("unknown device property '{}', expected 'name', 'path' or 'id'", property)),
+                },
+                Some(_) => return Err(anyhow!("event_device expects a property name as argument")),
+            };
+
+            return Ok(ValueType::String(value));
+        }
         "on_window_change" => {
             if args.len() != 1 {
                 return Err(anyhow!("function takes 1 argument"));
@@ -87,7 +145,7 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
 
             let inner_block;
             let inner_var_map;
-            if let ValueType::Lambda(_, _block, _var_map) = eval_expr(args.get(0).unwrap(), var_map, amb).await {
+            if let ValueType::Lambda(_, _block, _var_map) = arg(name, &parsed_args, 0)? {
                 inner_block = _block;
                 inner_var_map = _var_map;
             } else {
@@ -96,35 +154,240 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
 
             amb.message_tx.as_ref().unwrap().send(ExecutionMessage::RegisterWindowChangeCallback(inner_block, inner_var_map)).await.unwrap();
         }
+        "set_chord_window" => {
+            let val = arg(name, &parsed_args, 0)?;
+            let millis = match val {
+                ValueType::Number(millis) => millis,
+                _ => return Err(anyhow!("set_chord_window expects a number argument")),
+            };
+
+            amb.message_tx.as_ref().unwrap()
+                .send(ExecutionMessage::SetChordWindow(time::Duration::from_millis(millis as u64))).await
+                .unwrap();
+        }
+        "set_sequence_timeout" => {
+            let val = arg(name, &parsed_args, 0)?;
+            let millis = match val {
+                ValueType::Number(millis) => millis,
+                _ => return Err(anyhow!("set_sequence_timeout expects a number argument")),
+            };
+
+            amb.message_tx.as_ref().unwrap()
+                .send(ExecutionMessage::SetSequenceTimeout(time::Duration::from_millis(millis as u64))).await
+                .unwrap();
+        }
+        "on_sequence_prefix" => {
+            if args.len() != 1 {
+                return Err(anyhow!("function takes 1 argument"));
+            }
+
+            let (params, block, lambda_var_map) = match arg(name, &parsed_args, 0)? {
+                ValueType::Lambda(params, block, lambda_var_map) => (params, block, lambda_var_map),
+                _ => return Err(anyhow!("type mismatch, function takes lambda argument")),
+            };
+
+            amb.message_tx.as_ref().unwrap()
+                .send(ExecutionMessage::RegisterSequencePrefixCallback(params, block, lambda_var_map)).await
+                .unwrap();
+        }
+        "layer_push" | "layer_toggle" => {
+            let layer = match arg(name, &parsed_args, 0)? {
+                ValueType::String(layer) => layer,
+                _ => return Err(anyhow!("{} expects a layer name as argument", name)),
+            };
+
+            let message = if name == "layer_push" {
+                ExecutionMessage::PushLayer(amb.window_cycle_token, layer)
+            } else {
+                ExecutionMessage::ToggleLayer(amb.window_cycle_token, layer)
+            };
+            amb.message_tx.as_ref().unwrap().send(message).await.unwrap();
+        }
+        "layer_pop" => {
+            amb.message_tx.as_ref().unwrap().send(ExecutionMessage::PopLayer(amb.window_cycle_token)).await.unwrap();
+        }
+        "layer_active" => {
+            let layer = match arg(name, &parsed_args, 0)? {
+                ValueType::String(layer) => layer,
+                _ => return Err(anyhow!("layer_active expects a layer name as argument")),
+            };
+
+            let (tx, mut rx) = mpsc::channel(1);
+            amb.message_tx.as_ref().unwrap().send(ExecutionMessage::IsLayerActive(layer, tx)).await.unwrap();
+            return Ok(ValueType::Bool(rx.recv().await.unwrap()));
+        }
+        "mouse_move" => {
+            let (dx, dy) = match (parsed_args.get(0), parsed_args.get(1)) {
+                (Some(ValueType::Number(dx)), Some(ValueType::Number(dy))) => (*dx as i32, *dy as i32),
+                _ => return Err(anyhow!("mouse_move expects 2 number arguments")),
+            };
+
+            if dx != 0 { amb.ev_writer_tx.send(KeyAction::new(Key { event_code: EventCode::EV_REL(EV_REL::REL_X) }, dx).to_input_ev()).await.unwrap(); }
+            if dy != 0 { amb.ev_writer_tx.send(KeyAction::new(Key { event_code: EventCode::EV_REL(EV_REL::REL_Y) }, dy).to_input_ev()).await.unwrap(); }
+            amb.ev_writer_tx.send(SYN_REPORT.clone()).await.unwrap();
+        }
+        "scroll" => {
+            let amount = match parsed_args.get(0) {
+                Some(ValueType::Number(amount)) => *amount as i32,
+                _ => return Err(anyhow!("scroll expects a number argument")),
+            };
+
+            // one wheel notch equals 120 hi-res units
+            amb.ev_writer_tx.send(KeyAction::new(Key { event_code: EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES) }, amount * 120).to_input_ev()).await.unwrap();
+            amb.ev_writer_tx.send(KeyAction::new(Key { event_code: EventCode::EV_REL(EV_REL::REL_WHEEL) }, amount).to_input_ev()).await.unwrap();
+            amb.ev_writer_tx.send(SYN_REPORT.clone()).await.unwrap();
+        }
+        "set_mouse_keys_speed" => {
+            let setting = match (parsed_args.get(0), parsed_args.get(1)) {
+                (Some(ValueType::Number(initial_speed)), Some(ValueType::Number(max_speed))) => MouseKeysSetting::Speed(*initial_speed, *max_speed),
+                _ => return Err(anyhow!("set_mouse_keys_speed expects 2 number arguments")),
+            };
+            amb.message_tx.as_ref().unwrap().send(ExecutionMessage::SetMouseKeysConfig(setting)).await.unwrap();
+        }
+        "set_mouse_keys_acceleration" => {
+            let setting = match (parsed_args.get(0), parsed_args.get(1)) {
+                (Some(ValueType::Number(millis)), None) => MouseKeysSetting::Acceleration(time::Duration::from_millis(*millis as u64), MouseKeysConfig::new().acceleration_curve),
+                (Some(ValueType::Number(millis)), Some(ValueType::Number(curve))) => MouseKeysSetting::Acceleration(time::Duration::from_millis(*millis as u64), *curve),
+                _ => return Err(anyhow!("set_mouse_keys_acceleration expects a duration and an optional curve exponent")),
+            };
+            amb.message_tx.as_ref().unwrap().send(ExecutionMessage::SetMouseKeysConfig(setting)).await.unwrap();
+        }
+        "set_mouse_keys_tick_rate" => {
+            let setting = match parsed_args.get(0) {
+                Some(ValueType::Number(millis)) if *millis >= 1.0 => MouseKeysSetting::TickRate(time::Duration::from_millis(*millis as u64)),
+                _ => return Err(anyhow!("set_mouse_keys_tick_rate expects a number argument of at least 1")),
+            };
+            amb.message_tx.as_ref().unwrap().send(ExecutionMessage::SetMouseKeysConfig(setting)).await.unwrap();
+        }
+        "set_mouse_keys_scroll_speed" => {
+            let setting = match parsed_args.get(0) {
+                Some(ValueType::Number(scroll_speed)) => MouseKeysSetting::ScrollSpeed(*scroll_speed),
+                _ => return Err(anyhow!("set_mouse_keys_scroll_speed expects a number argument")),
+            };
+            amb.message_tx.as_ref().unwrap().send(ExecutionMessage::SetMouseKeysConfig(setting)).await.unwrap();
+        }
         "sleep" => {
-            let val = eval_expr(args.get(0).unwrap(), var_map, amb).await;
+            let val = arg(name, &parsed_args, 0)?;
             match val {
-                ValueType::Number(millis) => tokio::time::sleep(time::Duration::from_millis(millis as u64)).await,
+                ValueType::Number(millis) => {
+                    let sleep = tokio::time::sleep(time::Duration::from_millis(millis as u64));
+                    match &mut amb.turn {
+                        // other mappings run in the meantime
+                        Some(turn) => turn.release_while(sleep).await,
+                        None => sleep.await,
+                    }
+                }
                 _ => return Err(anyhow!("sleep expects a number argument")),
             }
         }
+        "set_timeout" | "set_interval" => {
+            let (millis, block, lambda_var_map) = match (arg(name, &parsed_args, 0)?, arg(name, &parsed_args, 1)?) {
+                (ValueType::Number(millis), ValueType::Lambda(_, block, lambda_var_map)) => (millis, block, lambda_var_map),
+                _ => return Err(anyhow!("{} expects a number and a lambda as arguments", name)),
+            };
+
+            let repeat = name == "set_interval";
+            if repeat && millis < 1.0 { return Err(anyhow!("set_interval expects an interval of at least 1")); }
+
+            let (tx, mut rx) = mpsc::channel(1);
+            amb.message_tx.as_ref().unwrap()
+                .send(ExecutionMessage::AddTimer(amb.script_generation, amb.window_cycle_token, amb.in_window_change_handler,
+                                                 time::Duration::from_millis(millis.max(0.0) as u64), repeat, block,
+                                                 lambda_var_map, tx)).await
+                .unwrap();
+
+            // there's no handle if the script was replaced or the active window changed in the meantime
+            return Ok(match rx.recv().await {
+                Some(id) => ValueType::Number(id as f64),
+                None => ValueType::Void,
+            });
+        }
+        "clear_timer" => {
+            let id = match arg(name, &parsed_args, 0)? {
+                ValueType::Number(id) => id as usize,
+                // clearing a timer that was never set is fine, i.e. 'clear_timer(timer)' before the first 'set_timeout'
+                ValueType::Void => return Ok(ValueType::Void),
+                _ => return Err(anyhow!("clear_timer expects a timer handle as argument")),
+            };
+
+            amb.message_tx.as_ref().unwrap().send(ExecutionMessage::ClearTimer(id)).await.unwrap();
+        }
         "print" => {
-            let val = eval_expr(args.get(0).unwrap(), var_map, amb).await;
+            let val = arg(name, &parsed_args, 0)?;
             let val = format!("{}\n", val);
 
             amb.message_tx.borrow_mut().as_ref().unwrap()
                 .send(ExecutionMessage::Write(val)).await
                 .unwrap();
         }
+        "len" => {
+            let len = match parsed_args.get(0) {
+                Some(ValueType::List(items)) => items.lock().unwrap().len(),
+                Some(ValueType::Map(entries)) => entries.lock().unwrap().len(),
+                Some(ValueType::String(val)) => val.chars().count(),
+                _ => return Err(anyhow!("len expects a list, a map or a string argument")),
+            };
+            return Ok(ValueType::Number(len as f64));
+        }
+        "push" => {
+            match (parsed_args.get(0), parsed_args.get(1)) {
+                (Some(ValueType::List(items)), Some(value)) => items.lock().unwrap().push(value.clone()),
+                _ => return Err(anyhow!("push expects a list and a value as arguments")),
+            }
+        }
+        "pop" => {
+            let value = match parsed_args.get(0) {
+                Some(ValueType::List(items)) => items.lock().unwrap().pop(),
+                _ => return Err(anyhow!("pop expects a list argument")),
+            };
+            return Ok(value.unwrap_or(ValueType::Void));
+        }
+        "contains" => {
+            let found = match (parsed_args.get(0), parsed_args.get(1)) {
+                (Some(ValueType::List(items)), Some(value)) => {
+                    // the value might be the list itself, which can't be locked twice
+                    let items = items.lock().unwrap().clone();
+                    items.contains(value)
+                }
+                (Some(ValueType::String(val)), Some(ValueType::String(part))) => val.contains(&**part),
+                _ => return Err(anyhow!("contains expects a list and a value or two strings as arguments")),
+            };
+            return Ok(ValueType::Bool(found));
+        }
+        "keys" | "values" => {
+            let entries = match parsed_args.get(0) {
+                Some(ValueType::Map(entries)) => entries.lock().unwrap().clone(),
+                _ => return Err(anyhow!("{} expects a map argument", name)),
+            };
+
+            let items = if name == "keys" {
+                entries.into_keys().map(ValueType::String).collect()
+            } else {
+                entries.into_values().collect()
+            };
+            return Ok(ValueType::List(Arc::new(Mutex::new(items))));
+        }
+        "has" => {
+            let found = match (parsed_args.get(0), parsed_args.get(1)) {
+                (Some(ValueType::Map(entries)), Some(ValueType::String(key))) => entries.lock().unwrap().contains_key(key),
+                _ => return Err(anyhow!("has expects a map and a key as arguments")),
+            };
+            return Ok(ValueType::Bool(found));
+        }
         "number_to_key" => {
-            let val = eval_expr(args.get(0).unwrap(), var_map, amb).await;
+            let val = arg(name, &parsed_args, 0)?;
             let val = match val {
                 ValueType::Number(val) => val,
                 _ => return Err(anyhow!("only numbers can be converted to keys")),
             };
             let val = val as u32;
 
-            let key = int_to_ev_key(val).expect(&*format!("key for scan code '{}' not found", val));
+            let key = int_to_ev_key(val).ok_or_else(|| anyhow!("key for scan code '{}' not found", val))?;
 
             return Ok(ValueType::String(format!("{{{}}}", EventCode::EV_KEY(key).to_string())));
         }
         "number_to_char" => {
-            let val = eval_expr(args.get(0).unwrap(), var_map, amb).await;
+            let val = arg(name, &parsed_args, 0)?;
             let val = match val {
                 ValueType::Number(val) => val,
                 _ => return Err(anyhow!("only numbers can be converted to chars")),
@@ -134,12 +397,12 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
             return Ok(ValueType::String(format!("{}", val)));
         }
         "char_to_number" => {
-            let val = eval_expr(args.get(0).unwrap(), var_map, amb).await;
+            let val = arg(name, &parsed_args, 0)?;
             let val = match val {
                 ValueType::String(val) => val,
                 _ => return Err(anyhow!("only chars can be converted to chars")),
             };
-            if val.len() != 1 { panic!("string needs to contain exactly 1 character") }
+            if val.len() != 1 { return Err(anyhow!("string needs to contain exactly 1 character")); }
 
             let first_ch = val.chars().next().unwrap();
             let val = first_ch as u8 as f64;
@@ -147,15 +410,15 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
         }
         "map_key" => {
             let val = (
-                eval_expr(args.get(0).unwrap(), var_map, amb).await,
-                eval_expr(args.get(1).unwrap(), var_map, amb).await,
+                arg(name, &parsed_args, 0)?,
+                arg(name, &parsed_args, 1)?,
             );
             let (from, to) = match val {
                 (ValueType::String(from), ValueType::Lambda(_, to, var_map)) => (from, (to, var_map)),
                 _ => return Err(anyhow!("invalid arguments passed to 'map_key'")),
             };
 
-            let mappings = match parse_key_action_with_mods(&*from, to.0).unwrap() {
+            let mappings = match parse_key_action_with_mods(&*from, to.0)? {
                 Expr::KeyMapping(v) => v,
                 _ => unreachable!(),
             };
@@ -164,7 +427,7 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
                 let mapping = mapping.clone();
 
                 amb.message_tx.borrow_mut().as_ref().unwrap()
-                    .send(ExecutionMessage::AddMapping(amb.window_cycle_token, mapping.from, mapping.to, to.1.clone())).await
+                    .send(ExecutionMessage::AddMapping(amb.window_cycle_token, amb.layer.clone(), amb.device.clone(), mapping.from, mapping.to, to.1.clone())).await
                     .unwrap();
             }
         }
@@ -183,8 +446,12 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
             // append arguments to command
             for arg in parsed_args.iter().skip(1) { cmd.arg(arg); }
 
-            let child_process = cmd.output().await
-                .map_err(|err| anyhow!("failed to spawn child process: {}", err))?;
+            let output = cmd.output();
+            let child_process = match &mut amb.turn {
+                // other mappings run while the command runs, i.e. a program that keeps running until it's closed
+                Some(turn) => turn.release_while(output).await,
+                None => output.await,
+            }.map_err(|err| anyhow!("failed to spawn child process: {}", err))?;
 
             // trim trailing newline
             let mut output = String::from_utf8_lossy(&child_process.stdout).to_string();
@@ -198,9 +465,12 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
             return Ok(ValueType::String(output.to_string()));
         }
         name => {
-            let (lambda_params, lambda_block, lambda_var_map) = match eval_expr(&Expr::Name(name.to_string()), var_map, amb).await {
+            let (lambda_params, lambda_block, lambda_var_map) = match eval_expr(&Expr::Name(name.to_string()), var_map, amb).await? {
                 ValueType::Lambda(params, block, var_map) => (params, block, var_map),
-                ValueType::Void => return Err(anyhow!("function '{}' not found in this scope", name)),
+                ValueType::Void => return Err(match closest_match(name, BUILTIN_FUNCTIONS.iter().map(|(name, _, _)| *name)) {
+                    Some(suggestion) => anyhow!("function '{}' not found in this scope, did you mean '{}'?", name, suggestion),
+                    None => anyhow!("function '{}' not found in this scope", name),
+                }),
                 _ => return Err(anyhow!("variable '{}' is not a lambda function", name)),
             };
 
@@ -211,18 +481,17 @@ pub async fn evaluate_builtin<'a>(name: &String, args: &Vec<Expr>, var_map: &Gua
             )));
 
             for (idx, param) in lambda_params.iter().enumerate() {
-                let val = match args.get(idx) {
-                    Some(expr) => eval_expr(expr, var_map, amb).await,
-                    None => ValueType::Void,
-                };
+                let val = parsed_args.get(idx).cloned().unwrap_or(ValueType::Void);
 
-                eval_expr(&Expr::Init(param.clone(), Box::new(Expr::Value(val))), &lambda_var_map, amb).await;
+                eval_expr(&Expr::Init(param.clone(), Box::new(Expr::Value(val))), &lambda_var_map, amb).await?;
             }
 
-            let ret = eval_block(&lambda_block, &mut lambda_var_map, amb).await;
+            let ret = eval_block(&lambda_block, &mut lambda_var_map, amb).await
+                .map_err(|err| err.in_function(name))?;
             match ret {
                 BlockRet::Return(ret) => return Ok(ret),
                 BlockRet::Continue => return Err(anyhow!("function cannot return a continue statement")),
+                BlockRet::Break => return Err(anyhow!("function cannot return a break statement")),
                 BlockRet::None => {}
             }
         }
@@ -0,0 +1,105 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+use crate::*;
+
+/// The location of a statement or expression in the script.
+#[derive(Clone, Debug)]
+pub struct Span {
+    /// 1-indexed line number
+    pub line: usize,
+    /// 1-indexed column, counted in characters
+    pub column: usize,
+    /// the full line of the script the statement starts on
+    pub source_line: Arc<str>,
+}
+
+impl Span {
+    pub(crate) fn write_snippet(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n{caret:>column$}", self.source_line.trim_end(), caret = '^', column = self.column)
+    }
+
+    /// The byte offset of the span in the script it was parsed from.
+    pub(crate) fn offset_in(&self, script: &str) -> usize {
+        let line_start = match self.line {
+            1 => 0,
+            line => script.match_indices('\n').nth(line - 2).map_or(script.len(), |(idx, _)| idx + 1),
+        };
+        script[line_start..].char_indices().nth(self.column - 1).map_or(script.len(), |(idx, _)| line_start + idx)
+    }
+}
+
+/// An error that occurred while evaluating the script, i.e. a type mismatch or a call to an undefined function.
+#[derive(Clone, Debug)]
+pub struct RuntimeError {
+    pub message: String,
+    /// the expression or statement that failed, filled in by the innermost one that has a location
+    pub span: Option<Span>,
+    /// the functions that were being called when the error occurred along with the location they were called
+    /// from, starting with the innermost call
+    pub call_stack: Vec<(String, Option<Span>)>,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>) -> Self {
+        RuntimeError { message: message.into(), span: None, call_stack: vec![] }
+    }
+
+    /// Records the expression or statement that was being evaluated when the error propagates through it.
+    pub(crate) fn at(mut self, span: Option<&Span>) -> Self {
+        let span = match span {
+            Some(span) => span,
+            None => return self,
+        };
+
+        if self.span.is_none() {
+            self.span = Some(span.clone());
+        } else if let Some((_, call_site @ None)) = self.call_stack.last_mut() {
+            *call_site = Some(span.clone());
+        }
+        self
+    }
+
+    /// Records that the error propagated out of the function with the given name.
+    pub(crate) fn in_function(mut self, name: &str) -> Self {
+        self.call_stack.push((name.to_string(), None));
+        self
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => {
+                writeln!(f, "at line {}:", span.line)?;
+                span.write_snippet(f)?;
+                write!(f, "\n{}", self.message)?;
+            }
+            None => write!(f, "{}", self.message)?,
+        }
+
+        for (name, call_site) in &self.call_stack {
+            match call_site {
+                Some(span) => {
+                    write!(f, "\nin function '{}' called at line {}:\n", name, span.line)?;
+                    span.write_snippet(f)?;
+                }
+                None => write!(f, "\nin function '{}'", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Turns errors of built-in functions into runtime errors, errors of user defined functions are runtime errors
+/// already and keep their location.
+impl From<anyhow::Error> for RuntimeError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<RuntimeError>() {
+            Ok(err) => err,
+            Err(err) => RuntimeError::new(err.to_string()),
+        }
+    }
+}
@@ -1,13 +1,15 @@
 use std::borrow::BorrowMut;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::Formatter;
+use std::thread::LocalKey;
 
 use messaging::*;
 
 use crate::*;
 
 use super::builtin_functions::evaluate_builtin;
-use super::builtin_functions::throw_error;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub(crate) struct KeyActionCondition {
@@ -20,9 +22,16 @@ pub enum ValueType {
     String(String),
     Lambda(Vec<String>, Block, GuardedVarMap),
     Number(f64),
+    /// lists are shared by reference, modifying one through any variable is visible through all others
+    List(GuardedList),
+    /// maps are shared by reference the same way lists are, the keys are kept in sorted order
+    Map(GuardedMap),
     Void,
 }
 
+pub type GuardedList = Arc<Mutex<Vec<ValueType>>>;
+pub type GuardedMap = Arc<Mutex<BTreeMap<String, ValueType>>>;
+
 impl PartialEq for ValueType {
     fn eq(&self, other: &Self) -> bool {
         use ValueType::*;
@@ -30,6 +39,8 @@ impl PartialEq for ValueType {
             (String(l), String(r)) => l == r,
             (Bool(l), Bool(r)) => l == r,
             (Number(l), Number(r)) => l == r,
+            (List(l), List(r)) => arc_mutexes_are_equal(l, r),
+            (Map(l), Map(r)) => arc_mutexes_are_equal(l, r),
             (_, _) => false,
         }
     }
@@ -42,11 +53,46 @@ impl fmt::Display for ValueType {
             ValueType::String(v) => write!(f, "{}", v),
             ValueType::Number(v) => write!(f, "{}", v),
             ValueType::Lambda(_, _, _) => write!(f, "Lambda"),
+            ValueType::List(items) => {
+                // the lock isn't held while printing, a list might contain itself
+                let id = arc_address(items);
+                let items = items.lock().unwrap().clone();
+                visit(&PRINTING, id, || {
+                    write!(f, "[")?;
+                    for (idx, item) in items.iter().enumerate() {
+                        if idx > 0 { write!(f, ", ")?; }
+                        write_nested(f, item)?;
+                    }
+                    write!(f, "]")
+                }).unwrap_or_else(|| write!(f, "[...]"))
+            }
+            ValueType::Map(entries) => {
+                let id = arc_address(entries);
+                let entries = entries.lock().unwrap().clone();
+                visit(&PRINTING, id, || {
+                    write!(f, "{{")?;
+                    for (idx, (key, value)) in entries.iter().enumerate() {
+                        if idx > 0 { write!(f, ", ")?; }
+                        write!(f, "\"{}\": ", key)?;
+                        write_nested(f, value)?;
+                    }
+                    write!(f, "}}")
+                }).unwrap_or_else(|| write!(f, "{{...}}"))
+            }
             ValueType::Void => write!(f, "Void"),
         }
     }
 }
 
+/// Writes a value contained in a list or map, strings are quoted so that '["a, b"]' can be told apart from
+/// '["a", "b"]'.
+fn write_nested(f: &mut Formatter<'_>, value: &ValueType) -> fmt::Result {
+    match value {
+        ValueType::String(v) => write!(f, "\"{}\"", v),
+        v => write!(f, "{}", v),
+    }
+}
+
 #[derive(Debug)]
 pub struct VarMap {
     pub(crate) scope_values: HashMap<String, ValueType>,
@@ -64,7 +110,7 @@ impl PartialEq for VarMap {
         self.scope_values == other.scope_values &&
             match (&self.parent, &other.parent) {
                 (None, None) => true,
-                (Some(l), Some(r)) => arc_mutexes_are_equal(&*l, &*r),
+                (Some(l), Some(r)) => Arc::ptr_eq(l, r) || *l.lock().unwrap() == *r.lock().unwrap(),
                 (_, _) => false,
             }
     }
@@ -74,27 +120,34 @@ pub type GuardedVarMap = Arc<Mutex<VarMap>>;
 
 
 #[async_recursion]
-pub(crate) async fn eval_expr<'a>(expr: &Expr, var_map: &GuardedVarMap, amb: &mut Ambient<'_>) -> ValueType {
+pub(crate) async fn eval_expr<'a>(expr: &Expr, var_map: &GuardedVarMap, amb: &mut Ambient<'_>) -> Result<ValueType, RuntimeError> {
     use ValueType::*;
-    match expr {
+    let value = match expr {
+        Expr::Spanned(expr, span) => {
+            return eval_expr(expr, var_map, amb).await.map_err(|err| err.at(Some(span)));
+        }
         Expr::Eq(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Bool(left), Bool(right)) => Bool(left == right),
                 (String(left), String(right)) => Bool(left == right),
                 (Number(left), Number(right)) => Bool(left == right),
+                (List(left), List(right)) => Bool(arc_mutexes_are_equal(&left, &right)),
+                (Map(left), Map(right)) => Bool(arc_mutexes_are_equal(&left, &right)),
                 _ => Bool(false),
             }
         }
         Expr::Neq(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Bool(left), Bool(right)) => Bool(left != right),
                 (String(left), String(right)) => Bool(left != right),
                 (Number(left), Number(right)) => Bool(left != right),
+                (List(left), List(right)) => Bool(!arc_mutexes_are_equal(&left, &right)),
+                (Map(left), Map(right)) => Bool(!arc_mutexes_are_equal(&left, &right)),
                 _ => Bool(true),
             }
         }
         Expr::LT(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Bool(left), Bool(right)) => Bool(left < right),
                 (String(left), String(right)) => Bool(left < right),
                 (Number(left), Number(right)) => Bool(left < right),
@@ -102,7 +155,7 @@ pub(crate) async fn eval_expr<'a>(expr: &Expr, var_map: &GuardedVarMap, amb: &mu
             }
         }
         Expr::GT(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Bool(left), Bool(right)) => Bool(left > right),
                 (String(left), String(right)) => Bool(left > right),
                 (Number(left), Number(right)) => Bool(left > right),
@@ -110,60 +163,60 @@ pub(crate) async fn eval_expr<'a>(expr: &Expr, var_map: &GuardedVarMap, amb: &mu
             }
         }
         Expr::Add(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Number(left), Number(right)) => Number(left + right),
                 (String(left), right) => String(format!("{}{}", left, right)),
                 (left, String(right)) => String(format!("{}{}", left, right)),
-                _ => panic!("cannot add unsupported types"),
+                _ => return Err(RuntimeError::new("cannot add unsupported types")),
             }
         }
         Expr::Sub(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Number(left), Number(right)) => Number(left - right),
-                _ => panic!("cannot subtract unsupported types"),
+                _ => return Err(RuntimeError::new("cannot subtract unsupported types")),
             }
         }
         Expr::Mul(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Number(left), Number(right)) => Number(left * right),
-                _ => panic!("cannot multiply unsupported types"),
+                _ => return Err(RuntimeError::new("cannot multiply unsupported types")),
             }
         }
         Expr::Div(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Number(left), Number(right)) => {
-                    if right == 0.0 { panic!("error: division by zero"); }
+                    if right == 0.0 { return Err(RuntimeError::new("division by zero")); }
                     Number(left / right)
                 }
-                _ => panic!("cannot multiply unsupported types"),
+                _ => return Err(RuntimeError::new("cannot divide unsupported types")),
             }
         }
         Expr::Neg(expr) => {
-            match eval_expr(expr, var_map, amb).await {
+            match eval_expr(expr, var_map, amb).await? {
                 Bool(val) => { Bool(!val) }
-                _ => panic!("cannot negate unsupported type"),
+                _ => return Err(RuntimeError::new("cannot negate unsupported type")),
             }
         }
         Expr::And(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Bool(left), Bool(right)) => Bool(left == right),
-                _ => panic!("cannot perform \"and\" operation on unsupported types"),
+                _ => return Err(RuntimeError::new("cannot perform \"and\" operation on unsupported types")),
             }
         }
         Expr::Or(left, right) => {
-            match (eval_expr(left, var_map, amb).await, eval_expr(right, var_map, amb).await) {
+            match (eval_expr(left, var_map, amb).await?, eval_expr(right, var_map, amb).await?) {
                 (Bool(left), Bool(right)) => Bool(left || right),
-                _ => panic!("cannot perform \"or\" operation on unsupported types"),
+                _ => return Err(RuntimeError::new("cannot perform \"or\" operation on unsupported types")),
             }
         }
         Expr::Init(var_name, value) => {
-            let value = eval_expr(value, var_map, amb).await;
+            let value = eval_expr(value, var_map, amb).await?;
 
             var_map.lock().unwrap().scope_values.insert(var_name.clone(), value);
-            return ValueType::Void;
+            return Ok(ValueType::Void);
         }
         Expr::Assign(var_name, value) => {
-            let value = eval_expr(value, var_map, amb).await;
+            let value = eval_expr(value, var_map, amb).await?;
 
             let mut map = var_map.clone();
             loop {
@@ -176,7 +229,7 @@ pub(crate) async fn eval_expr<'a>(expr: &Expr, var_map: &GuardedVarMap, amb: &mu
                     }
                     None => match &map_guard.parent {
                         Some(parent) => tmp = parent.clone(),
-                        None => { panic!("variable '{}' does not exist", var_name); }
+                        None => { return Err(RuntimeError::new(format!("variable '{}' does not exist", var_name))); }
                     }
                 }
                 drop(map_guard);
@@ -184,16 +237,83 @@ pub(crate) async fn eval_expr<'a>(expr: &Expr, var_map: &GuardedVarMap, amb: &mu
             }
             ValueType::Void
         }
+        Expr::AssignIndex(target, index, value) => {
+            let (target, index, value) = (
+                eval_expr(target, var_map, amb).await?,
+                eval_expr(index, var_map, amb).await?,
+                eval_expr(value, var_map, amb).await?,
+            );
+
+            match target {
+                List(items) => {
+                    let mut items = items.lock().unwrap();
+                    let idx = list_index(&index, items.len())?;
+                    items[idx] = value;
+                }
+                Map(entries) => {
+                    entries.lock().unwrap().insert(map_key(index)?, value);
+                }
+                _ => return Err(RuntimeError::new("only lists and maps can be assigned to by index")),
+            }
+            ValueType::Void
+        }
         Expr::KeyMapping(mappings) => {
             for mapping in mappings {
                 let mapping = mapping.clone();
 
                 amb.message_tx.borrow_mut().as_ref().unwrap()
-                    .send(ExecutionMessage::AddMapping(amb.window_cycle_token, mapping.from, mapping.to, var_map.clone())).await
+                    .send(ExecutionMessage::AddMapping(amb.window_cycle_token, amb.layer.clone(), amb.device.clone(), mapping.from, mapping.to, var_map.clone())).await
                     .unwrap();
             }
 
-            return ValueType::Void;
+            return Ok(ValueType::Void);
+        }
+        Expr::TapHoldMapping(mapping) => {
+            check_mapping_scope("tap-hold", false, amb)?;
+            amb.message_tx.borrow_mut().as_ref().unwrap()
+                .send(ExecutionMessage::AddTapHoldMapping(amb.window_cycle_token, mapping.clone())).await
+                .unwrap();
+
+            return Ok(ValueType::Void);
+        }
+        Expr::ChordMapping(mapping) => {
+            check_mapping_scope("chord", false, amb)?;
+            amb.message_tx.borrow_mut().as_ref().unwrap()
+                .send(ExecutionMessage::AddChordMapping(amb.window_cycle_token, mapping.keys.clone(), mapping.to.clone(), var_map.clone())).await
+                .unwrap();
+
+            return Ok(ValueType::Void);
+        }
+        Expr::SequenceMapping(mapping) => {
+            check_mapping_scope("sequence", false, amb)?;
+            amb.message_tx.borrow_mut().as_ref().unwrap()
+                .send(ExecutionMessage::AddSequenceMapping(amb.window_cycle_token, mapping.keys.clone(), mapping.to.clone(), var_map.clone())).await
+                .unwrap();
+
+            return Ok(ValueType::Void);
+        }
+        Expr::MouseKeyMapping(mapping) => {
+            check_mapping_scope("mouse key", true, amb)?;
+            amb.message_tx.borrow_mut().as_ref().unwrap()
+                .send(ExecutionMessage::AddMouseKeyMapping(amb.window_cycle_token, amb.layer.clone(), mapping.clone())).await
+                .unwrap();
+
+            return Ok(ValueType::Void);
+        }
+        Expr::AbsMapping(mapping) => {
+            amb.message_tx.borrow_mut().as_ref().unwrap()
+                .send(ExecutionMessage::AddAbsMapping(amb.window_cycle_token, amb.layer.clone(), amb.device.clone(), mapping.clone(), var_map.clone())).await
+                .unwrap();
+
+            return Ok(ValueType::Void);
+        }
+        Expr::Hotstring(hotstring) => {
+            check_mapping_scope("hotstring", false, amb)?;
+            amb.message_tx.borrow_mut().as_ref().unwrap()
+                .send(ExecutionMessage::AddHotstring(amb.window_cycle_token, hotstring.clone())).await
+                .unwrap();
+
+            return Ok(ValueType::Void);
         }
         Expr::Name(var_name) => {
             let mut value = None;
@@ -222,37 +342,58 @@ pub(crate) async fn eval_expr<'a>(expr: &Expr, var_map: &GuardedVarMap, amb: &mu
             }
         }
         Expr::Value(value) => {
-            return value.clone();
+            return Ok(value.clone());
+        }
+        Expr::List(items) => {
+            let mut values = vec![];
+            for item in items {
+                values.push(eval_expr(item, var_map, amb).await?);
+            }
+            return Ok(ValueType::List(Arc::new(Mutex::new(values))));
+        }
+        Expr::Map(entries) => {
+            let mut values = BTreeMap::new();
+            for (key, value) in entries {
+                values.insert(key.clone(), eval_expr(value, var_map, amb).await?);
+            }
+            return Ok(ValueType::Map(Arc::new(Mutex::new(values))));
+        }
+        Expr::Index(target, index) => {
+            let (target, index) = (eval_expr(target, var_map, amb).await?, eval_expr(index, var_map, amb).await?);
+
+            match target {
+                List(items) => {
+                    let items = items.lock().unwrap();
+                    items[list_index(&index, items.len())?].clone()
+                }
+                // missing keys read as 'Void', the same way undefined variables do
+                Map(entries) => entries.lock().unwrap().get(&map_key(index)?).cloned().unwrap_or(ValueType::Void),
+                _ => return Err(RuntimeError::new("only lists and maps can be indexed")),
+            }
         }
         Expr::Lambda(params, block) => {
             let lambda_var_map = GuardedVarMap::new(Mutex::new(VarMap::new(Some(var_map.clone()))));
-            return ValueType::Lambda(params.clone(), block.clone(), lambda_var_map);
+            return Ok(ValueType::Lambda(params.clone(), block.clone(), lambda_var_map));
         }
         Expr::KeyAction(action) => {
             amb.ev_writer_tx.send(action.to_input_ev()).await.unwrap();
             amb.ev_writer_tx.send(SYN_REPORT.clone()).await.unwrap();
 
-            return ValueType::Void;
+            return Ok(ValueType::Void);
         }
         // Expr::EatKeyAction(action) => {
         //     match &amb.message_tx {
         //         Some(tx) => { tx.send(ExecutionMessage::EatEv(action.clone())).await.unwrap(); }
-        //         None => panic!("need message tx"),
+        //         None => return Err(RuntimeError::new("need message tx")),
         //     }
-        //     return ValueType::Void;
+        //     return Ok(ValueType::Void);
         // }
         Expr::SleepAction(duration) => {
             tokio::time::sleep(*duration).await;
-            return ValueType::Void;
+            return Ok(ValueType::Void);
         }
         Expr::FunctionCall(name, args) => {
-            match evaluate_builtin(name, args, var_map, amb).await {
-                Ok(v) => v,
-                Err(err) => {
-                    throw_error(err, 1, amb).await;
-                    ValueType::Void
-                }
-            }
+            evaluate_builtin(name, args, var_map, amb).await?
         }
         Expr::ReleaseRestoreModifiers(from_flags, to_flags, to_type) => {
             let actual_state = &amb.modifier_state;
@@ -293,9 +434,11 @@ pub(crate) async fn eval_expr<'a>(expr: &Expr, var_map: &GuardedVarMap, amb: &mu
 
             // TODO eat keys we just released, un-eat keys we just restored
 
-            return ValueType::Void;
+            return Ok(ValueType::Void);
         }
-    }
+    };
+
+    Ok(value)
 }
 
 pub type SleepSender = tokio::sync::mpsc::Sender<Block>;
@@ -304,95 +447,240 @@ pub struct Ambient<'a> {
     pub ev_writer_tx: mpsc::Sender<InputEvent>,
     pub message_tx: Option<&'a mut ExecutionMessageSender>,
     pub window_cycle_token: usize,
+    /// set while a window change callback runs, the timers it sets are dropped once the active window changes again
+    pub in_window_change_handler: bool,
+    /// counts the loaded versions of the script, timers set by a replaced script are dropped
+    pub script_generation: usize,
     pub modifier_state: &'a KeyModifierState,
+    /// the layer that key mappings get added to, the global scope if not set
+    pub layer: Option<String>,
+    /// the device pattern that key mappings are restricted to, all devices if not set
+    pub device: Option<String>,
+    /// the device that sent the event which triggered the evaluation
+    pub event_device: Option<Arc<DeviceInfo>>,
+    /// the place in the execution queue of a mapping that is running, not set for blocks that run concurrently
+    pub turn: Option<ExecutionTurn>,
 }
 
 pub enum BlockRet {
     None,
     Continue,
+    Break,
     Return(ValueType),
 }
 
 #[async_recursion]
-pub async fn eval_block<'a>(block: &Block, var_map: &GuardedVarMap, amb: &mut Ambient<'a>) -> BlockRet {
-    let mut var_map = GuardedVarMap::new(Mutex::new(VarMap::new(Some(var_map.clone()))));
-
-    'outer: for stmt in &block.statements {
-        match stmt {
-            Stmt::Expr(expr) => { eval_expr(expr, &var_map, amb).await; }
-            Stmt::Block(nested_block) => {
-                let ret = eval_block(nested_block, &mut var_map, amb).await;
-                match ret {
-                    BlockRet::None => {}
-                    _ => return ret,
-                };
-            }
-            Stmt::If(if_else_if_pairs, else_pair) => {
-                for (expr, block) in if_else_if_pairs {
-                    if eval_expr(expr, &mut var_map, amb).await == ValueType::Bool(true) {
-                        let ret = eval_block(block, &mut var_map, amb).await;
-                        match ret {
-                            BlockRet::None => {}
-                            _ => return ret,
-                        };
-                        continue 'outer;
-                    }
-                }
-                if let Some(block) = else_pair {
-                    let ret = eval_block(block, &mut var_map, amb).await;
-                    match ret {
-                        BlockRet::None => {}
-                        _ => return ret,
-                    };
+pub async fn eval_block<'a>(block: &Block, var_map: &GuardedVarMap, amb: &mut Ambient<'a>) -> Result<BlockRet, RuntimeError> {
+    let var_map = GuardedVarMap::new(Mutex::new(VarMap::new(Some(var_map.clone()))));
+
+    for (idx, stmt) in block.statements.iter().enumerate() {
+        let ret = eval_stmt(stmt, &var_map, amb).await
+            .map_err(|err| err.at(block.spans.get(idx).and_then(Option::as_ref)))?;
+
+        match ret {
+            BlockRet::None => {}
+            _ => return Ok(ret),
+        };
+    }
+
+    Ok(BlockRet::None)
+}
+
+#[async_recursion]
+pub(crate) async fn eval_stmt<'a>(stmt: &Stmt, var_map: &GuardedVarMap, amb: &mut Ambient<'a>) -> Result<BlockRet, RuntimeError> {
+    match stmt {
+        Stmt::Expr(expr) => { eval_expr(expr, var_map, amb).await?; }
+        Stmt::Block(nested_block) => {
+            return eval_block(nested_block, var_map, amb).await;
+        }
+        Stmt::If(if_else_if_pairs, else_pair) => {
+            for (expr, block) in if_else_if_pairs {
+                if eval_expr(expr, var_map, amb).await? == ValueType::Bool(true) {
+                    return eval_block(block, var_map, amb).await;
                 }
             }
-            Stmt::For(init_expr, termination_expr, advance_expr, block) => {
-                eval_expr(init_expr, &var_map, amb).await;
+            if let Some(block) = else_pair {
+                return eval_block(block, var_map, amb).await;
+            }
+        }
+        Stmt::For(init_expr, termination_expr, advance_expr, block) => {
+            eval_expr(init_expr, var_map, amb).await?;
 
-                loop {
-                    let should_continue = match eval_expr(termination_expr, &var_map, amb).await {
-                        ValueType::Bool(v) => v,
-                        _ => panic!("termination condition in for loop needs to return a boolean"),
-                    };
-                    if !should_continue { break; }
+            loop {
+                let should_continue = match eval_expr(termination_expr, var_map, amb).await? {
+                    ValueType::Bool(v) => v,
+                    _ => return Err(RuntimeError::new("termination condition in for loop needs to return a boolean")),
+                };
+                if !should_continue { break; }
 
-                    let ret = eval_block(block, &mut var_map, amb).await;
-                    match ret {
-                        BlockRet::Return(_) => return ret,
-                        _ => {}
-                    };
+                let ret = eval_block(block, var_map, amb).await?;
+                match ret {
+                    BlockRet::Return(_) => return Ok(ret),
+                    BlockRet::Break => break,
+                    BlockRet::None | BlockRet::Continue => {}
+                };
 
-                    eval_expr(advance_expr, &var_map, amb).await;
-                }
+                eval_expr(advance_expr, var_map, amb).await?;
             }
-            Stmt::Return(expr) => {
-                return BlockRet::Return(eval_expr(expr, &var_map, amb).await);
+        }
+        Stmt::ForIn(name, iterable_expr, block) => {
+            // changes to the collection while iterating don't affect the iteration
+            let items = match eval_expr(iterable_expr, var_map, amb).await? {
+                ValueType::List(items) => items.lock().unwrap().clone(),
+                ValueType::Map(entries) => entries.lock().unwrap().keys().cloned().map(ValueType::String).collect(),
+                _ => return Err(RuntimeError::new("only lists and maps can be iterated over")),
+            };
+
+            for item in items {
+                let loop_var_map = GuardedVarMap::new(Mutex::new(VarMap::new(Some(var_map.clone()))));
+                loop_var_map.lock().unwrap().scope_values.insert(name.clone(), item);
+
+                let ret = eval_block(block, &loop_var_map, amb).await?;
+                match ret {
+                    BlockRet::Return(_) => return Ok(ret),
+                    BlockRet::Break => break,
+                    BlockRet::None | BlockRet::Continue => {}
+                };
             }
-            Stmt::Continue => {
-                return BlockRet::Continue;
+        }
+        Stmt::While(condition_expr, block) => {
+            loop {
+                let should_continue = match eval_expr(condition_expr, var_map, amb).await? {
+                    ValueType::Bool(v) => v,
+                    _ => return Err(RuntimeError::new("condition in while loop needs to return a boolean")),
+                };
+                if !should_continue { break; }
+
+                let ret = eval_block(block, var_map, amb).await?;
+                match ret {
+                    BlockRet::Return(_) => return Ok(ret),
+                    BlockRet::Break => break,
+                    BlockRet::None | BlockRet::Continue => {}
+                };
             }
         }
+        Stmt::Loop(block) => {
+            loop {
+                let ret = eval_block(block, var_map, amb).await?;
+                match ret {
+                    BlockRet::Return(_) => return Ok(ret),
+                    BlockRet::Break => break,
+                    BlockRet::None | BlockRet::Continue => {}
+                };
+            }
+        }
+        Stmt::Layer(name, layer_block) => {
+            let parent_layer = amb.layer.replace(name.clone());
+            let ret = eval_block(layer_block, var_map, amb).await;
+            amb.layer = parent_layer;
+            return ret;
+        }
+        Stmt::Device(pattern, device_block) => {
+            let parent_device = amb.device.replace(pattern.clone());
+            let ret = eval_block(device_block, var_map, amb).await;
+            amb.device = parent_device;
+            return ret;
+        }
+        Stmt::Return(expr) => {
+            return Ok(BlockRet::Return(eval_expr(expr, var_map, amb).await?));
+        }
+        Stmt::Continue => {
+            return Ok(BlockRet::Continue);
+        }
+        Stmt::Break => {
+            return Ok(BlockRet::Break);
+        }
     }
 
-    BlockRet::None
+    Ok(BlockRet::None)
 }
 
-fn arc_mutexes_are_equal<T>(first: &Arc<Mutex<T>>, second: &Arc<Mutex<T>>) -> bool
-    where T: PartialEq { Arc::ptr_eq(first, second) || *first.lock().unwrap() == *second.lock().unwrap() }
+/// The parser rejects mappings inside of layer and device blocks that they can't be restricted to, but a function
+/// that defines one can still be called from inside of such a block.
+fn check_mapping_scope(kind: &str, supports_layers: bool, amb: &Ambient<'_>) -> Result<(), RuntimeError> {
+    if let (Some(layer), false) = (&amb.layer, supports_layers) {
+        return Err(RuntimeError::new(format!("{} mappings can't be added to layer '{}'", kind, layer)));
+    }
+    if let Some(device) = &amb.device {
+        return Err(RuntimeError::new(format!("{} mappings can't be restricted to device '{}'", kind, device)));
+    }
+    Ok(())
+}
 
-#[derive(Clone, Debug, PartialEq)]
+/// Turns a script value into a position in a list of the given length.
+fn list_index(index: &ValueType, len: usize) -> Result<usize, RuntimeError> {
+    match index {
+        ValueType::Number(idx) if idx.fract() == 0.0 && *idx >= 0.0 && (*idx as usize) < len => Ok(*idx as usize),
+        ValueType::Number(idx) => Err(RuntimeError::new(format!("index {} is out of bounds for a list of length {}", idx, len))),
+        _ => Err(RuntimeError::new("list index needs to be a number")),
+    }
+}
+
+fn map_key(key: ValueType) -> Result<String, RuntimeError> {
+    match key {
+        ValueType::String(key) => Ok(key),
+        _ => Err(RuntimeError::new("map keys need to be strings")),
+    }
+}
+
+thread_local! {
+    /// the lists and maps that are being printed further up the stack
+    static PRINTING: RefCell<Vec<usize>> = const { RefCell::new(vec![]) };
+    /// the pairs of lists and maps that are being compared further up the stack
+    static COMPARING: RefCell<Vec<(usize, usize)>> = const { RefCell::new(vec![]) };
+}
+
+fn arc_address<T>(value: &Arc<Mutex<T>>) -> usize { Arc::as_ptr(value) as *const () as usize }
+
+/// Runs `f` unless `id` is already being visited further up the stack, which happens when a list or map
+/// contains itself.
+fn visit<T: PartialEq, R>(stack: &'static LocalKey<RefCell<Vec<T>>>, id: T, f: impl FnOnce() -> R) -> Option<R> {
+    if stack.with(|stack| stack.borrow().contains(&id)) { return None; }
+
+    stack.with(|stack| stack.borrow_mut().push(id));
+    let res = f();
+    stack.with(|stack| stack.borrow_mut().pop());
+    Some(res)
+}
+
+fn arc_mutexes_are_equal<T>(first: &Arc<Mutex<T>>, second: &Arc<Mutex<T>>) -> bool where T: PartialEq + Clone {
+    if Arc::ptr_eq(first, second) { return true; }
+
+    // the locks aren't held while comparing, the values might contain themselves. A pair that is already
+    // being compared is assumed to be equal, any difference shows up further up the stack.
+    let id = (arc_address(first), arc_address(second));
+    let (first, second) = (first.lock().unwrap().clone(), second.lock().unwrap().clone());
+    visit(&COMPARING, id, || first == second).unwrap_or(true)
+}
+
+#[derive(Clone, Debug)]
 pub struct Block {
     pub(crate) statements: Vec<Stmt>,
+    /// the location of each statement, blocks that weren't parsed from the script don't have any
+    pub(crate) spans: Vec<Option<Span>>,
+    /// the location of the closing brace, blocks that weren't parsed from the script don't have one
+    pub(crate) end: Option<Span>,
+    /// mapped to as 'async { ... }', runs concurrently rather than waiting for its turn in the execution queue
+    pub(crate) is_async: bool,
 }
 
 impl Block {
     pub(crate) fn new() -> Self {
-        Block { statements: vec![] }
+        Block { statements: vec![], spans: vec![], end: None, is_async: false }
+    }
+
+    pub fn is_async(&self) -> bool { self.is_async }
+}
+
+/// Blocks are the same if they do the same thing, regardless of where they are located in the script.
+impl PartialEq for Block {
+    fn eq(&self, other: &Self) -> bool {
+        self.statements == other.statements && self.is_async == other.is_async
     }
 }
 
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Expr {
     Eq(Box<Expr>, Box<Expr>),
     Neq(Box<Expr>, Box<Expr>),
@@ -409,11 +697,22 @@ pub enum Expr {
     Or(Box<Expr>, Box<Expr>),
     Init(String, Box<Expr>),
     Assign(String, Box<Expr>),
+    /// assigns the third expression to the element at the index of the first expression
+    AssignIndex(Box<Expr>, Box<Expr>, Box<Expr>),
     KeyMapping(Vec<KeyMapping>),
+    TapHoldMapping(TapHoldMapping),
+    ChordMapping(ChordMapping),
+    SequenceMapping(SequenceMapping),
+    MouseKeyMapping(MouseKeyMapping),
+    AbsMapping(AbsMapping),
+    Hotstring(Hotstring),
 
     Name(String),
     Value(ValueType),
     Lambda(Vec<String>, Block),
+    List(Vec<Expr>),
+    Map(Vec<(String, Expr)>),
+    Index(Box<Expr>, Box<Expr>),
 
     FunctionCall(String, Vec<Expr>),
 
@@ -424,6 +723,51 @@ pub enum Expr {
 
     // internal
     ReleaseRestoreModifiers(KeyModifierFlags, KeyModifierFlags, i32),
+    /// an expression that can fail along with where it is in the script, i.e. the operator of a binary operation
+    Spanned(Box<Expr>, Span),
+}
+
+/// Expressions are the same regardless of where they are located in the script, the same way blocks are, so the
+/// locations they are wrapped in are skipped when comparing them.
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        use Expr::*;
+        match (self.without_span(), other.without_span()) {
+            (Eq(a, b), Eq(c, d)) | (Neq(a, b), Neq(c, d)) | (LT(a, b), LT(c, d)) | (GT(a, b), GT(c, d)) |
+            (Add(a, b), Add(c, d)) | (Sub(a, b), Sub(c, d)) | (Div(a, b), Div(c, d)) | (Mul(a, b), Mul(c, d)) |
+            (And(a, b), And(c, d)) | (Or(a, b), Or(c, d)) | (Index(a, b), Index(c, d)) => a == c && b == d,
+            (Neg(a), Neg(b)) => a == b,
+            (Init(a, b), Init(c, d)) | (Assign(a, b), Assign(c, d)) => a == c && b == d,
+            (AssignIndex(a, b, c), AssignIndex(d, e, f)) => a == d && b == e && c == f,
+            (KeyMapping(a), KeyMapping(b)) => a == b,
+            (TapHoldMapping(a), TapHoldMapping(b)) => a == b,
+            (ChordMapping(a), ChordMapping(b)) => a == b,
+            (SequenceMapping(a), SequenceMapping(b)) => a == b,
+            (MouseKeyMapping(a), MouseKeyMapping(b)) => a == b,
+            (AbsMapping(a), AbsMapping(b)) => a == b,
+            (Hotstring(a), Hotstring(b)) => a == b,
+            (Name(a), Name(b)) => a == b,
+            (Value(a), Value(b)) => a == b,
+            (Lambda(a, b), Lambda(c, d)) => a == c && b == d,
+            (List(a), List(b)) => a == b,
+            (Map(a), Map(b)) => a == b,
+            (FunctionCall(a, b), FunctionCall(c, d)) => a == c && b == d,
+            (KeyAction(a), KeyAction(b)) => a == b,
+            (SleepAction(a), SleepAction(b)) => a == b,
+            (ReleaseRestoreModifiers(a, b, c), ReleaseRestoreModifiers(d, e, f)) => a == d && b == e && c == f,
+            _ => false,
+        }
+    }
+}
+
+impl Expr {
+    /// The expression without the location it might be wrapped in.
+    pub(crate) fn without_span(&self) -> &Expr {
+        match self {
+            Expr::Spanned(expr, _) => expr.without_span(),
+            expr => expr,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -432,7 +776,12 @@ pub(crate) enum Stmt {
     Block(Block),
     If(Vec<(Expr, Block)>, Option<Block>),
     For(Expr, Expr, Expr, Block),
-    // While
+    ForIn(String, Expr, Block),
+    While(Expr, Block),
+    Loop(Block),
+    Layer(String, Block),
+    Device(String, Block),
     Return(Expr),
     Continue,
+    Break,
 }
\ No newline at end of file
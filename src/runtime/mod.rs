@@ -1,3 +1,6 @@
 pub mod evaluation;
+pub mod error;
 mod builtin_functions;
 
+pub(crate) use builtin_functions::BUILTIN_FUNCTIONS;
+
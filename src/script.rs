@@ -1,9 +1,13 @@
 use crate::*;
 use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+use notify::{DebouncedEvent, Watcher};
 use crate::messaging::ExecutionMessage;
 
 
-pub fn parse_script(script_file: &mut fs::File) -> Block {
+/// Reads and parses the script file, parse errors are reported on stderr.
+pub fn parse_script(script_file: &mut fs::File) -> Result<Block> {
     let script_file_length = script_file.seek(SeekFrom::End(0))
         .map_err(|err| anyhow!("failed seek operation on script file: {}", err))
         .unwrap();
@@ -18,9 +22,52 @@ pub fn parse_script(script_file: &mut fs::File) -> Block {
         .map_err(|err| anyhow!("failed to read script file: {}", err))
         .unwrap();
 
-    let global = parsing::parser::parse_script(&*raw).unwrap();
+    parsing::parser::parse_script(&*raw)
+}
+
+/// Reads and parses the script at the given path, parse errors are reported on stderr.
+pub fn load_script(script_path: &Path) -> Result<Block> {
+    let raw = fs::read_to_string(script_path)
+        .map_err(|err| anyhow!("failed to read script file '{}': {}", script_path.display(), err))?;
+
+    parsing::parser::parse_script(&*raw)
+}
+
+/// A changed script waits for all keys to be released, but a key whose release got lost would keep it waiting
+/// forever. After this long it gets loaded with the next input event regardless.
+pub const RELOAD_TIMEOUT: time::Duration = time::Duration::from_secs(2);
+
+/// Sends a message whenever the script file changes. Editors often replace the file instead of writing to it,
+/// so the parent directory is watched rather than the file itself.
+pub fn watch_script(script_path: &Path, change_tx: mpsc::Sender<()>) -> Result<()> {
+    let script_path = fs::canonicalize(script_path)
+        .map_err(|err| anyhow!("failed to resolve script path '{}': {}", script_path.display(), err))?;
+    let script_dir = script_path.parent()
+        .ok_or_else(|| anyhow!("the script file '{}' has no parent directory", script_path.display()))?
+        .to_path_buf();
+
+    let (watch_tx, watch_rx) = std::sync::mpsc::channel();
+    let mut watcher: notify::RecommendedWatcher = notify::Watcher::new(watch_tx, time::Duration::from_millis(200))
+        .map_err(|err| anyhow!("failed to watch the script file: {}", err))?;
+    watcher.watch(&script_dir, notify::RecursiveMode::NonRecursive)
+        .map_err(|err| anyhow!("failed to watch the script file: {}", err))?;
+
+    thread::spawn(move || {
+        // the watcher stops watching once it's dropped
+        let _watcher = watcher;
+
+        while let Ok(event) = watch_rx.recv() {
+            let path = match event {
+                DebouncedEvent::Write(path) | DebouncedEvent::Create(path) | DebouncedEvent::Rename(_, path) => path,
+                _ => continue,
+            };
+            if path != script_path { continue; }
+
+            if futures::executor::block_on(change_tx.send(())).is_err() { return; }
+        }
+    });
 
-    global
+    Ok(())
 }
 
 
@@ -29,13 +76,21 @@ pub async fn evaluate_script(
     mut execution_message_tx: mpsc::Sender<ExecutionMessage>,
     ev_reader_tx: mpsc::Sender<InputEvent>,
     window_cycle_token: usize,
-) {
+    script_generation: usize,
+) -> Result<(), RuntimeError> {
     let mut amb = Ambient {
         ev_writer_tx: ev_reader_tx,
         window_cycle_token,
+        in_window_change_handler: false,
+        script_generation,
         message_tx: Some(&mut execution_message_tx),
         modifier_state: &KeyModifierState::new(),
+        layer: None,
+        device: None,
+        event_device: None,
+        turn: None,
     };
 
-    eval_block(&script_ast, &mut GuardedVarMap::new(Mutex::new(VarMap::new(None))), &mut amb).await;
+    eval_block(&script_ast, &mut GuardedVarMap::new(Mutex::new(VarMap::new(None))), &mut amb).await?;
+    Ok(())
 }
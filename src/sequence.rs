@@ -0,0 +1,214 @@
+use crate::*;
+
+pub const SEQUENCE_DEFAULT_TIMEOUT: time::Duration = time::Duration::from_millis(1000);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceMapping {
+    pub keys: Vec<Key>,
+    pub to: Block,
+}
+
+pub enum SequenceAction {
+    /// the event is not part of a sequence and should be processed normally
+    Forward(InputEvent),
+    /// all keys of a sequence were clicked in order
+    Fire(Arc<(Block, GuardedVarMap)>),
+    /// the typed keys are a prefix of at least one sequence, contains the prefix and the keys that continue it
+    Pending(Vec<Key>, Vec<Key>),
+}
+
+/// Requests a timeout message once the current sequence step ran out of time.
+pub struct SequenceTimer {
+    pub id: usize,
+    pub duration: time::Duration,
+}
+
+#[derive(Default)]
+struct SequenceNode {
+    children: HashMap<Key, SequenceNode>,
+    block: Option<Arc<(Block, GuardedVarMap)>>,
+}
+
+impl SequenceNode {
+    fn find(&self, keys: &[Key]) -> Option<&SequenceNode> {
+        let mut node = self;
+        for key in keys { node = node.children.get(key)?; }
+        Some(node)
+    }
+}
+
+struct PendingSequence {
+    id: usize,
+    keys: Vec<Key>,
+    buffered: Vec<InputEvent>,
+}
+
+pub struct SequenceState {
+    pub timeout: time::Duration,
+    /// called with the typed prefix and the possible continuations whenever a sequence is pending
+    pub prefix_callback: Option<(Vec<String>, Block, GuardedVarMap)>,
+    root: SequenceNode,
+    pending: Option<PendingSequence>,
+    /// keys of fired sequences that are still held down
+    consumed: Vec<Key>,
+    next_id: usize,
+}
+
+impl SequenceState {
+    pub fn new() -> Self {
+        SequenceState {
+            timeout: SEQUENCE_DEFAULT_TIMEOUT,
+            prefix_callback: None,
+            root: Default::default(),
+            pending: None,
+            consumed: vec![],
+            next_id: 0,
+        }
+    }
+
+    pub fn insert(&mut self, keys: Vec<Key>, block: Block, var_map: GuardedVarMap) {
+        let mut node = &mut self.root;
+        for key in keys { node = node.children.entry(key).or_default(); }
+        node.block = Some(Arc::new((block, var_map)));
+    }
+
+    pub fn process(&mut self, ev: InputEvent) -> (Vec<SequenceAction>, Option<SequenceTimer>) {
+        let mut out = vec![];
+        let key = Key { event_code: ev.event_code };
+
+        // swallow the remaining events of keys that completed a sequence
+        if let Some(idx) = self.consumed.iter().position(|consumed| *consumed == key) {
+            if ev.value == TYPE_UP { self.consumed.remove(idx); }
+            return (out, None);
+        }
+
+        if let Some(pending) = &mut self.pending {
+            let is_buffered = pending.buffered.iter().any(|buffered| buffered.event_code == ev.event_code);
+
+            if ev.value != TYPE_DOWN {
+                if !is_buffered {
+                    out.push(SequenceAction::Forward(ev));
+                } else if ev.value == TYPE_UP {
+                    pending.buffered.push(ev);
+                }
+                return (out, None);
+            }
+
+            let mut keys = pending.keys.clone();
+            keys.push(key);
+
+            if self.root.find(&keys).is_some() {
+                let mut pending = self.pending.take().unwrap();
+                pending.buffered.push(ev);
+                pending.keys = keys;
+                let timer = self.advance(pending, &mut out);
+                return (out, timer);
+            }
+
+            // the sequence can't be continued, fire a complete prefix or replay everything in the original order
+            let pending = self.pending.take().unwrap();
+            self.finish(pending, &mut out);
+        }
+
+        if ev.value == TYPE_DOWN && self.root.children.contains_key(&key) {
+            let pending = PendingSequence { id: 0, keys: vec![key], buffered: vec![ev] };
+            let timer = self.advance(pending, &mut out);
+            return (out, timer);
+        }
+
+        out.push(SequenceAction::Forward(ev));
+        (out, None)
+    }
+
+    /// Called once a sequence step ran out of time, fires the sequence if the typed keys match one and replays
+    /// them otherwise.
+    pub fn timeout(&mut self, id: usize) -> Vec<SequenceAction> {
+        let mut out = vec![];
+
+        let is_current = self.pending.as_ref().map(|p| p.id == id).unwrap_or(false);
+        if !is_current { return out; }
+
+        let pending = self.pending.take().unwrap();
+        self.finish(pending, &mut out);
+        out
+    }
+
+    /// Gives up on the pending sequence and replays its keys, i.e. before the script gets replaced.
+    pub fn flush(&mut self) -> Vec<SequenceAction> {
+        self.pending.take().into_iter()
+            .flat_map(|pending| pending.buffered)
+            .map(SequenceAction::Forward)
+            .collect()
+    }
+
+    /// Prepares the prefix callback to be called with the given prefix and continuations as string arguments.
+    pub fn prefix_callback_block(&self, prefix: &[Key], continuations: &[Key]) -> Option<Arc<(Block, GuardedVarMap)>> {
+        let (params, block, var_map) = self.prefix_callback.as_ref()?;
+
+        let prefix = prefix.iter().map(format_sequence_key).collect::<Vec<_>>().join(",");
+        let continuations = continuations.iter().map(format_sequence_key).collect::<Vec<_>>().join(" ");
+
+        // each call gets its own scope so that the arguments don't leak into the next call
+        let mut callback_var_map = VarMap::new(var_map.lock().unwrap().parent.clone());
+        for (param, value) in params.iter().zip(vec![prefix, continuations]) {
+            callback_var_map.scope_values.insert(param.clone(), ValueType::String(value));
+        }
+
+        Some(Arc::new((block.clone(), Arc::new(Mutex::new(callback_var_map)))))
+    }
+
+    fn advance(&mut self, mut pending: PendingSequence, out: &mut Vec<SequenceAction>) -> Option<SequenceTimer> {
+        let node = self.root.find(&pending.keys).unwrap();
+
+        if node.children.is_empty() {
+            self.finish(pending, out);
+            return None;
+        }
+
+        let mut continuations: Vec<Key> = node.children.keys().copied().collect();
+        continuations.sort_by_key(format_sequence_key);
+        out.push(SequenceAction::Pending(pending.keys.clone(), continuations));
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        pending.id = id;
+        self.pending = Some(pending);
+        Some(SequenceTimer { id, duration: self.timeout })
+    }
+
+    fn finish(&mut self, pending: PendingSequence, out: &mut Vec<SequenceAction>) {
+        let block = self.root.find(&pending.keys).and_then(|node| node.block.clone());
+
+        match block {
+            Some(block) => {
+                self.consumed.append(&mut held_keys(&pending.buffered));
+                out.push(SequenceAction::Fire(block));
+            }
+            None => {
+                for buffered in pending.buffered { out.push(SequenceAction::Forward(buffered)); }
+            }
+        }
+    }
+}
+
+impl Default for SequenceState {
+    fn default() -> Self { Self::new() }
+}
+
+/// Formats a key the way it's written in a sequence trigger, i.e. 'g' or '{f13}'.
+pub fn format_sequence_key(key: &Key) -> String {
+    let name = key.event_code.to_string();
+    let name = name.strip_prefix("KEY_").unwrap_or(&name).to_lowercase();
+
+    if name.chars().count() == 1 { name } else { format!("{{{}}}", name) }
+}
+
+fn held_keys(buffered: &[InputEvent]) -> Vec<Key> {
+    let mut keys = vec![];
+    for ev in buffered {
+        let key = Key { event_code: ev.event_code };
+        if ev.value == TYPE_DOWN && !keys.contains(&key) { keys.push(key); }
+        if ev.value == TYPE_UP { keys.retain(|held| *held != key); }
+    }
+    keys
+}
@@ -0,0 +1,30 @@
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::*;
+
+/// Exits the process without leaving any keys stuck, every way of stopping a script ends up here.
+pub fn exit(exit_code: i32) -> ! {
+    clean_up();
+    std::process::exit(exit_code)
+}
+
+/// Releases the held keys and removes the control socket.
+pub fn clean_up() {
+    device::close_output_device();
+    control::remove_socket();
+}
+
+/// Makes sure held keys get released when the process gets terminated by a signal. A panicking task only stops
+/// that task, the keys stay as they are since the rest of the script might still be holding them down.
+pub fn install_handlers() -> Result<()> {
+    // the exit code of a process killed by a signal is 128 + the signal number
+    for &(kind, exit_code) in &[(SignalKind::hangup(), 129), (SignalKind::interrupt(), 130), (SignalKind::terminate(), 143)] {
+        let mut stream = signal(kind).map_err(|err| anyhow!("failed to listen for signals: {}", err))?;
+        task::spawn(async move {
+            stream.recv().await;
+            exit(exit_code);
+        });
+    }
+
+    Ok(())
+}
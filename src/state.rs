@@ -1,17 +1,197 @@
+use evdev_rs::enums::EV_REL;
 use ignore_list::*;
 
 use crate::*;
 
+pub type KeyMappingTable = HashMap<KeyActionWithMods, Arc<(Block, GuardedVarMap)>>;
+
+/// The layer and device pattern a mapping was defined in, `None` stands for the global scope and all devices.
+pub type MappingScope = (Option<String>, Option<String>);
+
+/// Key mappings of the global scope, all named layers and device blocks, lookups go through the active layers
+/// first and prefer mappings of the device an event came from.
 #[derive(Clone, Debug)]
-pub struct CompiledKeyMappings(pub HashMap<KeyActionWithMods, Arc<(Block, GuardedVarMap)>>);
+pub struct CompiledKeyMappings {
+    tables: HashMap<MappingScope, KeyMappingTable>,
+    /// scopes of device blocks in the order they were defined, the first matching one takes precedence
+    device_scopes: Vec<MappingScope>,
+    /// names of the active layers, the last one is on top
+    layer_stack: Vec<String>,
+    /// scopes that handled the press of keys that are still held down
+    held: HashMap<Key, MappingScope>,
+}
+
+impl CompiledKeyMappings {
+    pub fn new() -> Self {
+        CompiledKeyMappings { tables: Default::default(), device_scopes: vec![], layer_stack: vec![], held: Default::default() }
+    }
+
+    pub fn insert(&mut self, layer: Option<String>, device: Option<String>, from: KeyActionWithMods, to: Arc<(Block, GuardedVarMap)>) {
+        let scope = (layer, device);
+        if scope.1.is_some() && !self.device_scopes.contains(&scope) { self.device_scopes.push(scope.clone()); }
+        self.tables.entry(scope).or_default().insert(from, to);
+    }
+
+    /// Finds the mapping for a key action, key repeats and releases resolve through the scope that handled
+    /// the key press so that changing layers while a key is held doesn't leave keys stuck.
+    pub fn get(&mut self, from: &KeyActionWithMods, device: Option<&DeviceInfo>) -> Option<Arc<(Block, GuardedVarMap)>> {
+        let held_scope = if from.value == TYPE_UP {
+            self.held.remove(&from.key)
+        } else if from.value == TYPE_REPEAT {
+            self.held.get(&from.key).cloned()
+        } else {
+            self.held.remove(&from.key);
+            None
+        };
+
+        if let Some(scope) = held_scope {
+            if let Some(block) = self.tables.get(&scope).and_then(|table| table.get(from)) { return Some(block.clone()); }
+        }
+
+        let (scope, block) = self.layer_stack.iter().rev()
+            .map(|layer| Some(layer.clone()))
+            .chain(std::iter::once(None))
+            .find_map(|layer| self.find(layer, device, from))?;
+
+        if from.value == TYPE_DOWN && matches!(from.key.event_code, EventCode::EV_KEY(_)) { self.held.insert(from.key, scope); }
+        Some(block)
+    }
+
+    /// Checks whether a single layer maps a key action, regardless of which layers are active.
+    pub fn is_mapped_in_layer(&self, layer: Option<String>, from: &KeyActionWithMods, device: Option<&DeviceInfo>) -> bool {
+        self.find(layer, device, from).is_some()
+    }
+
+    /// Looks up a key action in a single layer, mappings of the matching device blocks take precedence. If several
+    /// device blocks match, the one that was defined first wins.
+    fn find(&self, layer: Option<String>, device: Option<&DeviceInfo>, from: &KeyActionWithMods) -> Option<(MappingScope, Arc<(Block, GuardedVarMap)>)> {
+        if let Some(device) = device {
+            let found = self.device_scopes.iter()
+                .filter(|(table_layer, pattern)| {
+                    *table_layer == layer && pattern.as_ref().map(|pattern| device.matches(pattern)).unwrap_or(false)
+                })
+                .find_map(|scope| self.tables.get(scope)?.get(from).map(|block| (scope.clone(), block.clone())));
+            if found.is_some() { return found; }
+        }
+
+        let scope = (layer, None);
+        let block = self.tables.get(&scope)?.get(from)?.clone();
+        Some((scope, block))
+    }
+
+    /// Describes the triggers of all key mappings in script syntax, one line per mapping.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![];
+
+        for ((layer, device), table) in &self.tables {
+            let mut scope = String::new();
+            if let Some(layer) = layer { scope.push_str(&format!("[layer {}] ", layer)); }
+            if let Some(device) = device { scope.push_str(&format!("[device \"{}\"] ", device)); }
+
+            for from in table.keys() {
+                let is_click = [TYPE_DOWN, TYPE_UP, TYPE_REPEAT].iter()
+                    .all(|value| table.contains_key(&KeyActionWithMods { value: *value, ..*from }));
+
+                // click mappings consist of a mapping for each key state, only list them once
+                if is_click && from.value != TYPE_DOWN { continue; }
+
+                lines.push(format!("{}{}", scope, format_trigger(from, is_click)));
+            }
+        }
 
-impl CompiledKeyMappings { pub fn new() -> Self { CompiledKeyMappings(Default::default()) } }
+        lines.sort();
+        lines
+    }
+
+    pub fn push_layer(&mut self, layer: String) {
+        self.layer_stack.retain(|active| *active != layer);
+        self.layer_stack.push(layer);
+    }
+
+    pub fn pop_layer(&mut self) -> Option<String> {
+        self.layer_stack.pop()
+    }
+
+    pub fn toggle_layer(&mut self, layer: String) {
+        if self.is_layer_active(&layer) {
+            self.layer_stack.retain(|active| *active != layer);
+        } else {
+            self.layer_stack.push(layer);
+        }
+    }
+
+    /// Names of the active layers, starting with the top-most one.
+    pub fn active_layers(&self) -> impl Iterator<Item=&String> {
+        self.layer_stack.iter().rev()
+    }
+
+    pub fn is_layer_active(&self, layer: &str) -> bool {
+        self.layer_stack.iter().any(|active| active == layer)
+    }
+}
+
+/// Formats the trigger of a key mapping in script syntax, i.e. '!a' for a click or '{a down}' for a single key state.
+pub(crate) fn format_trigger(from: &KeyActionWithMods, is_click: bool) -> String {
+    let trigger = if is_click {
+        format_key(&from.key)
+    } else {
+        format!("{{{} {}}}", format_key(&from.key), format_key_state(from))
+    };
+    format!("{}{}", format_modifiers(&from.modifiers), trigger)
+}
+
+fn format_key(key: &Key) -> String {
+    let name = key.event_code.to_string();
+    name.strip_prefix("KEY_").unwrap_or(&name).to_lowercase()
+}
+
+fn format_key_state(action: &KeyActionWithMods) -> &'static str {
+    let is_positive = action.value > 0;
+    match action.key.event_code {
+        EventCode::EV_REL(EV_REL::REL_Y) => if is_positive { "down" } else { "up" },
+        EventCode::EV_REL(EV_REL::REL_X) | EventCode::EV_REL(EV_REL::REL_HWHEEL) | EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES) => {
+            if is_positive { "right" } else { "left" }
+        }
+        EventCode::EV_REL(_) => if is_positive { "up" } else { "down" },
+        _ if action.value == TYPE_DOWN => "down",
+        _ if action.value == TYPE_UP => "up",
+        _ => "repeat",
+    }
+}
+
+fn format_modifiers(modifiers: &KeyModifierFlags) -> String {
+    let mut flags = String::new();
+    if modifiers.ctrl { flags.push('^'); }
+    if modifiers.alt { flags.push('!'); }
+    if modifiers.shift { flags.push('+'); }
+    if modifiers.meta { flags.push('#'); }
+    flags
+}
 
 pub struct State {
     pub modifiers: Arc<KeyModifierState>,
 
     pub ignore_list: IgnoreList,
     pub active_window: Option<ActiveWindowInfo>,
+    pub tap_hold: TapHoldState,
+    pub chords: ChordState,
+    pub sequences: SequenceState,
+    pub mouse_keys: MouseKeysState,
+    pub abs: AbsState,
+    pub hotstrings: HotstringState,
+    pub timers: TimerState,
+    /// keeps the blocks of the script's mappings in the order they were triggered
+    pub execution_queue: ExecutionQueue,
+    /// the device of the most recent input event
+    pub device: Option<Arc<DeviceInfo>>,
+    /// keys that are physically held down
+    pub pressed_keys: Vec<Key>,
+    /// a changed script that gets loaded once all keys are released, along with when it changed
+    pub pending_script: Option<(Block, time::Instant)>,
+    /// counts the loaded versions of the script, unlike the window cycle token it only changes on reload
+    pub script_generation: usize,
+    /// passes all input events through untouched
+    pub paused: bool,
 }
 
 
@@ -21,6 +201,90 @@ impl State {
             modifiers: Arc::new(KeyModifierState::new()),
             ignore_list: IgnoreList::new(),
             active_window: None,
+            tap_hold: TapHoldState::new(),
+            chords: ChordState::new(),
+            sequences: SequenceState::new(),
+            mouse_keys: MouseKeysState::new(),
+            abs: AbsState::new(),
+            hotstrings: HotstringState::new(),
+            timers: TimerState::new(),
+            execution_queue: ExecutionQueue::new(),
+            device: None,
+            pressed_keys: vec![],
+            pending_script: None,
+            script_generation: 0,
+            paused: false,
         }
     }
-}
\ No newline at end of file
+
+    /// Drops the mappings and settings defined by the script, used when the script gets reloaded.
+    pub fn clear_script_state(&mut self) {
+        self.tap_hold = TapHoldState::new();
+        self.chords = ChordState::new();
+        self.sequences = SequenceState::new();
+        self.mouse_keys = MouseKeysState::new();
+        self.abs = AbsState::new();
+        self.hotstrings = HotstringState::new();
+        self.timers.clear();
+        self.execution_queue = ExecutionQueue::new();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tap::Tap;
+
+    use super::*;
+
+    #[test]
+    fn test_device_precedence() {
+        let device = DeviceInfo { path: "/dev/input/event3".to_string(), name: "pad".to_string(), vendor: 0x1209, product: 0xa1e5 };
+        let from = KeyActionWithMods::new(*KEY_A, TYPE_DOWN, KeyModifierFlags::new());
+        let block = |name: &str| Arc::new((
+            Block::new().tap_mut(|b| b.statements = vec![Stmt::Expr(Expr::Name(name.to_string()))]),
+            GuardedVarMap::new(Mutex::new(VarMap::new(None))),
+        ));
+
+        for _ in 0..10 {
+            let mut mappings = CompiledKeyMappings::new();
+            mappings.insert(None, None, from, block("global"));
+            mappings.insert(None, Some("1209:a1e5".to_string()), from, block("id"));
+            mappings.insert(None, Some("pad".to_string()), from, block("name"));
+            mappings.insert(None, Some("/dev/input/event3".to_string()), from, block("path"));
+
+            assert_eq!(mappings.get(&from, Some(&device)).unwrap().0, block("id").0);
+            assert_eq!(mappings.get(&from, None).unwrap().0, block("global").0);
+        }
+    }
+
+    #[test]
+    fn test_abs_device_precedence() {
+        use evdev_rs::enums::EV_ABS;
+
+        let pad = DeviceInfo { path: "/dev/input/event3".to_string(), name: "pad".to_string(), vendor: 0x1209, product: 0xa1e5 };
+        let other = DeviceInfo { path: "/dev/input/event4".to_string(), name: "stick".to_string(), vendor: 0x046d, product: 0xc21d };
+        let block = |name: &str| Block::new().tap_mut(|b| b.statements = vec![Stmt::Expr(Expr::Name(name.to_string()))]);
+        let mapping = |name: &str| AbsMapping {
+            trigger: AbsTrigger { axis: EventCode::EV_ABS(EV_ABS::ABS_X), comparison: AbsComparison::Greater, threshold: 200 },
+            enter: block(name),
+            leave: Block::new(),
+        };
+        let ev = |value| InputEvent { event_code: EventCode::EV_ABS(EV_ABS::ABS_X), value, time: INPUT_EV_DUMMY_TIME };
+        let entered = |blocks: Vec<Arc<(Block, GuardedVarMap)>>| blocks.iter()
+            .map(|block| block.0.clone()).filter(|block| !block.statements.is_empty()).collect::<Vec<_>>();
+
+        let mappings = CompiledKeyMappings::new();
+        let mut abs = AbsState::new();
+        abs.insert(None, None, mapping("global"), GuardedVarMap::new(Mutex::new(VarMap::new(None))));
+        abs.insert(None, Some("pad".to_string()), mapping("name"), GuardedVarMap::new(Mutex::new(VarMap::new(None))));
+        abs.insert(None, Some("1209:a1e5".to_string()), mapping("id"), GuardedVarMap::new(Mutex::new(VarMap::new(None))));
+
+        let (blocks, forward) = abs.process(&ev(300), Some(&pad), &mappings);
+        assert_eq!(entered(blocks), vec![block("name")]);
+        assert!(!forward);
+
+        let (blocks, _) = abs.process(&ev(300), Some(&other), &mappings);
+        assert_eq!(entered(blocks), vec![block("global")]);
+    }
+}
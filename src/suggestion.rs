@@ -0,0 +1,109 @@
+use evdev_rs::enums::EV_KEY;
+use itertools::Itertools;
+
+use crate::*;
+
+lazy_static! {
+    /// Every name a key can be referred to by in a script, i.e. 'enter', 'btn_left' or 'shift'.
+    static ref KEY_NAMES: Vec<String> = {
+        // codes can have several names, i.e. 'BTN_LEFT' and 'BTN_MOUSE', libevdev only knows one of them
+        let mut names: Vec<String> = EventCode::EV_KEY(EV_KEY::KEY_RESERVED).iter()
+            .take_while(|code| matches!(code, EventCode::EV_KEY(_)))
+            .flat_map(|code| match code {
+                EventCode::EV_KEY(key) => vec![format!("{:?}", key), code.to_string()],
+                _ => vec![],
+            })
+            .filter(|name| !name.is_empty())
+            .map(|name| name.strip_prefix("KEY_").unwrap_or(&name).to_lowercase())
+            .unique()
+            .collect();
+
+        let mut aliases: Vec<String> = KEY_ALIAS_TABLE.keys()
+            .filter(|alias| alias.len() > 1)
+            .map(|alias| alias.to_lowercase())
+            .collect();
+        aliases.sort();
+        names.append(&mut aliases);
+        names
+    };
+}
+
+/// Every name a key can be referred to by in a script.
+pub fn key_names() -> &'static [String] {
+    &KEY_NAMES
+}
+
+/// The number of single character insertions, deletions, substitutions and swaps of adjacent characters needed to
+/// turn one string into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // d[i][j] is the distance between the first i chars of a and the first j chars of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() { row[0] = i; }
+    for (j, cell) in d[0].iter_mut().enumerate() { *cell = j; }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+/// The candidate that is closest to the given name, candidates that differ in more than a third of the name aren't
+/// considered similar. On a tie the first candidate wins.
+pub fn closest_match<'a>(name: &str, candidates: impl IntoIterator<Item=&'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The known key name that is closest to the given unknown key name.
+pub fn suggest_key(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    let name = name.strip_prefix("key_").unwrap_or(&name);
+    closest_match(name, KEY_NAMES.iter().map(|name| name.as_str()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("enter", "enter"), 0);
+        assert_eq!(edit_distance("entert", "enter"), 1);
+        assert_eq!(edit_distance("prnt", "print"), 1);
+        assert_eq!(edit_distance("escpae", "escape"), 1);
+        assert_eq!(edit_distance("shfit", "shift"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_closest_match() {
+        assert_eq!(closest_match("prnt", vec!["print", "pop", "push"]), Some("print"));
+        assert_eq!(closest_match("foo", vec!["print", "pop", "push"]), None);
+    }
+
+    #[test]
+    fn test_suggest_key() {
+        assert_eq!(suggest_key("entert"), Some("enter"));
+        assert_eq!(suggest_key("KEY_ENTR"), Some("enter"));
+        assert_eq!(suggest_key("btn_lft"), Some("btn_left"));
+        assert_eq!(suggest_key("shfit"), Some("shift"));
+        assert_eq!(suggest_key("qwertyuiop"), None);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use crate::*;
+
+pub const TAP_HOLD_DEFAULT_TAPPING_TERM: time::Duration = time::Duration::from_millis(200);
+
+/// Decides what happens to a pending tap-hold key when a different key is pressed before the
+/// tapping term runs out.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TapHoldInterrupt {
+    /// resolve as hold only if the other key gets released while the tap-hold key is still down
+    Permissive,
+    /// resolve as hold as soon as any other key gets pressed
+    Hold,
+    /// resolve as tap as soon as any other key gets pressed
+    Tap,
+}
+
+impl TapHoldInterrupt {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "permissive" => Some(TapHoldInterrupt::Permissive),
+            "hold" => Some(TapHoldInterrupt::Hold),
+            "tap" => Some(TapHoldInterrupt::Tap),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TapHoldMapping {
+    pub key: Key,
+    pub tap: KeyClickActionWithMods,
+    pub hold: KeyClickActionWithMods,
+    pub tapping_term: time::Duration,
+    pub interrupt: TapHoldInterrupt,
+}
+
+pub enum TapHoldAction {
+    /// the event is not handled by the tap-hold logic and should be processed normally
+    Forward(InputEvent),
+    /// a key action produced by resolving a tap-hold key, goes straight to the output device
+    Emit(KeyAction),
+}
+
+/// Requests a timeout message after the tapping term of a newly pressed tap-hold key has passed.
+pub struct TapHoldTimer {
+    pub key: Key,
+    pub id: usize,
+    pub duration: time::Duration,
+}
+
+struct PendingTapHold {
+    mapping: TapHoldMapping,
+    id: usize,
+    since: time::Instant,
+    buffered: Vec<InputEvent>,
+}
+
+pub struct TapHoldState {
+    pub mappings: HashMap<Key, TapHoldMapping>,
+    pending: Option<PendingTapHold>,
+    held: Vec<TapHoldMapping>,
+    next_id: usize,
+}
+
+impl TapHoldState {
+    pub fn new() -> Self {
+        TapHoldState { mappings: Default::default(), pending: None, held: vec![], next_id: 0 }
+    }
+
+    /// Forgets the keys that are pending or held, their releases won't be seen, i.e. while the script is paused.
+    pub fn forget_held(&mut self) {
+        self.pending = None;
+        self.held.clear();
+    }
+
+    /// Gives up on the pending key and replays it along with the keys pressed in the meantime, i.e. before the
+    /// script gets paused.
+    pub fn flush(&mut self) -> Vec<TapHoldAction> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return vec![],
+        };
+
+        let mut out = vec![TapHoldAction::Forward(KeyAction::new(pending.mapping.key, TYPE_DOWN).to_input_ev())];
+        for ev in pending.buffered { out.push(TapHoldAction::Forward(ev)); }
+        out
+    }
+
+    pub fn process(&mut self, ev: InputEvent) -> (Vec<TapHoldAction>, Option<TapHoldTimer>) {
+        let mut out = vec![];
+        let mut timer = None;
+        let mut queue = VecDeque::from(vec![ev]);
+
+        while let Some(ev) = queue.pop_front() {
+            let key = Key { event_code: ev.event_code };
+
+            // the pending key itself
+            if self.pending.as_ref().map(|p| p.mapping.key == key).unwrap_or(false) {
+                if ev.value == TYPE_UP {
+                    let pending = self.pending.take().unwrap();
+                    if pending.since.elapsed() >= pending.mapping.tapping_term {
+                        self.resolve_hold(pending, &mut out, &mut queue);
+                        self.release_hold(&key, &mut out);
+                    } else {
+                        self.resolve_tap(pending, &mut out, &mut queue);
+                    }
+                }
+                continue;
+            }
+
+            // a key that already resolved as hold
+            if self.held.iter().any(|m| m.key == key) {
+                if ev.value == TYPE_UP {
+                    self.release_hold(&key, &mut out);
+                } else if ev.value == TYPE_REPEAT {
+                    let hold_key = self.held.iter().find(|m| m.key == key).unwrap().hold.key;
+                    out.push(TapHoldAction::Emit(KeyAction::new(hold_key, TYPE_REPEAT)));
+                }
+                continue;
+            }
+
+            // some other key while a tap-hold key is pending
+            if let Some(pending) = &mut self.pending {
+                match pending.mapping.interrupt {
+                    TapHoldInterrupt::Permissive => {
+                        let is_buffered = pending.buffered.iter()
+                            .any(|buffered_ev| buffered_ev.event_code == ev.event_code);
+
+                        if ev.value == TYPE_DOWN || (ev.value == TYPE_REPEAT && is_buffered) {
+                            pending.buffered.push(ev);
+                            continue;
+                        }
+                        if ev.value == TYPE_UP && is_buffered {
+                            let pending = self.pending.take().unwrap();
+                            queue.push_front(ev);
+                            self.resolve_hold(pending, &mut out, &mut queue);
+                            continue;
+                        }
+                    }
+                    TapHoldInterrupt::Hold => {
+                        if ev.value == TYPE_DOWN {
+                            let pending = self.pending.take().unwrap();
+                            self.resolve_hold(pending, &mut out, &mut queue);
+                        }
+                    }
+                    TapHoldInterrupt::Tap => {
+                        if ev.value == TYPE_DOWN {
+                            let pending = self.pending.take().unwrap();
+                            self.resolve_tap(pending, &mut out, &mut queue);
+                        }
+                    }
+                }
+            }
+
+            // a new tap-hold key
+            if ev.value == TYPE_DOWN && self.pending.is_none() {
+                if let Some(mapping) = self.mappings.get(&key) {
+                    let id = self.next_id;
+                    self.next_id = self.next_id.wrapping_add(1);
+
+                    timer = Some(TapHoldTimer { key, id, duration: mapping.tapping_term });
+                    self.pending = Some(PendingTapHold {
+                        mapping: mapping.clone(),
+                        id,
+                        since: time::Instant::now(),
+                        buffered: vec![],
+                    });
+                    continue;
+                }
+            }
+
+            out.push(TapHoldAction::Forward(ev));
+        }
+
+        (out, timer)
+    }
+
+    /// Called once the tapping term of a tap-hold key ran out, resolves the key as hold if it's still pending.
+    pub fn timeout(&mut self, key: Key, id: usize) -> (Vec<TapHoldAction>, Option<TapHoldTimer>) {
+        let mut out = vec![];
+        let mut timer = None;
+
+        let is_current = self.pending.as_ref().map(|p| p.mapping.key == key && p.id == id).unwrap_or(false);
+        if !is_current { return (out, timer); }
+
+        let mut queue = VecDeque::new();
+        let pending = self.pending.take().unwrap();
+        self.resolve_hold(pending, &mut out, &mut queue);
+
+        for ev in queue {
+            let (mut actions, new_timer) = self.process(ev);
+            out.append(&mut actions);
+            timer = new_timer.or(timer);
+        }
+        (out, timer)
+    }
+
+    fn resolve_tap(&mut self, pending: PendingTapHold, out: &mut Vec<TapHoldAction>, queue: &mut VecDeque<InputEvent>) {
+        let tap = pending.mapping.tap;
+        for action in modifier_actions(&tap.modifiers, TYPE_DOWN) { out.push(TapHoldAction::Emit(action)); }
+        out.push(TapHoldAction::Emit(KeyAction::new(tap.key, TYPE_DOWN)));
+        out.push(TapHoldAction::Emit(KeyAction::new(tap.key, TYPE_UP)));
+        for action in modifier_actions(&tap.modifiers, TYPE_UP) { out.push(TapHoldAction::Emit(action)); }
+
+        // replay the events that arrived while the key was pending
+        for ev in pending.buffered.into_iter().rev() { queue.push_front(ev); }
+    }
+
+    fn resolve_hold(&mut self, pending: PendingTapHold, out: &mut Vec<TapHoldAction>, queue: &mut VecDeque<InputEvent>) {
+        let hold = pending.mapping.hold;
+        for action in modifier_actions(&hold.modifiers, TYPE_DOWN) { out.push(TapHoldAction::Emit(action)); }
+        out.push(TapHoldAction::Emit(KeyAction::new(hold.key, TYPE_DOWN)));
+
+        self.held.push(pending.mapping);
+
+        for ev in pending.buffered.into_iter().rev() { queue.push_front(ev); }
+    }
+
+    fn release_hold(&mut self, key: &Key, out: &mut Vec<TapHoldAction>) {
+        let idx = match self.held.iter().position(|m| m.key == *key) {
+            Some(idx) => idx,
+            None => return,
+        };
+        let hold = self.held.remove(idx).hold;
+
+        out.push(TapHoldAction::Emit(KeyAction::new(hold.key, TYPE_UP)));
+        for action in modifier_actions(&hold.modifiers, TYPE_UP) { out.push(TapHoldAction::Emit(action)); }
+    }
+}
+
+impl Default for TapHoldState {
+    fn default() -> Self { Self::new() }
+}
+
+pub(crate) fn modifier_actions(modifiers: &KeyModifierFlags, value: i32) -> Vec<KeyAction> {
+    let mut actions = vec![];
+    if modifiers.ctrl { actions.push(KeyAction::new(*KEY_LEFT_CTRL, value)); }
+    if modifiers.shift { actions.push(KeyAction::new(*KEY_LEFT_SHIFT, value)); }
+    if modifiers.alt { actions.push(KeyAction::new(*KEY_LEFT_ALT, value)); }
+    if modifiers.meta { actions.push(KeyAction::new(*KEY_LEFT_META, value)); }
+    actions
+}
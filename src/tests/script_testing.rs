@@ -1,6 +1,7 @@
 use crate::*;
 use messaging::*;
 use crate::cli::Configuration;
+use crate::device::output_sink::Output;
 
 #[derive(Default)]
 pub struct ScriptTestingParameters<'a> {
@@ -9,11 +10,16 @@ pub struct ScriptTestingParameters<'a> {
 
 pub struct ScriptTestingAPI {
     pub event_delay: Option<u64>,
+    /// the device that written events appear to come from
+    pub device: Option<Arc<DeviceInfo>>,
 
-    ev_reader_tx: mpsc::Sender<InputEvent>,
+    ev_reader_tx: mpsc::Sender<DeviceEvent>,
+    script_change_tx: mpsc::Sender<Block>,
+    control_tx: mpsc::Sender<ControlRequest>,
     ev_writer_rx: mpsc::Receiver<InputEvent>,
     stop_tx: futures_intrusive::channel::shared::Sender<()>,
     stdout: Arc<tokio::sync::Mutex<Vec<u8>>>,
+    stderr: Arc<tokio::sync::Mutex<Vec<u8>>>,
 }
 
 impl ScriptTestingAPI {
@@ -30,7 +36,7 @@ impl ScriptTestingAPI {
             sleep(delay);
         }
 
-        self.ev_reader_tx.send(ev).await?;
+        self.ev_reader_tx.send((ev, self.device.clone())).await?;
         Ok(())
     }
 
@@ -46,6 +52,29 @@ impl ScriptTestingAPI {
         vec
     }
 
+    /// Replaces the running script the same way a change to the script file does.
+    #[allow(unused)]
+    pub async fn change_script(&mut self, script_path: &str) -> Result<()> {
+        let script_ast = script::load_script(script_path.as_ref())?;
+        self.script_change_tx.send(script_ast).await?;
+        Ok(())
+    }
+
+    /// Sends a command the same way 'map2 ctl' does and returns the response.
+    #[allow(unused)]
+    pub async fn control(&mut self, command: ControlCommand) -> Result<String> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.control_tx.send(ControlRequest { command, response_tx }).await
+            .map_err(|_| anyhow!("the script stopped"))?;
+        Ok(response_rx.await?)
+    }
+
+    /// Accepts commands on a real control socket, like a running instance does.
+    #[allow(unused)]
+    pub async fn serve_control(&self, socket_path: &std::path::Path) -> Result<()> {
+        control::serve(socket_path, self.control_tx.clone()).await
+    }
+
     #[allow(unused)]
     pub async fn collect_stdout(&mut self) -> String {
         let result = String::from_utf8_lossy(&self.stdout.lock().await).into_owned();
@@ -55,46 +84,79 @@ impl ScriptTestingAPI {
 
     #[allow(unused)]
     pub async fn reset_stdout(&mut self) { self.stdout.lock().await.clear(); }
+
+    /// Collects the errors the script ran into without stopping.
+    #[allow(unused)]
+    pub async fn collect_stderr(&mut self) -> String {
+        let result = String::from_utf8_lossy(&self.stderr.lock().await).into_owned();
+        self.stderr.lock().await.clear();
+        result
+    }
 }
 
 pub async fn test_script(
     parameters: ScriptTestingParameters<'_>,
 ) -> Result<ScriptTestingAPI> {
     let mut config = Configuration {
+        script_path: parameters.script_path.into(),
         script_file: fs::File::open(parameters.script_path)?,
         verbosity: 0,
         devices: vec![],
+        socket_path: Default::default(),
+        panic_chord: vec![],
+        output: Output::Trace,
     };
 
-    let script_ast = script::parse_script(&mut config.script_file);
+    let script_ast = script::parse_script(&mut config.script_file)?;
 
     let mut state = State::new();
-    let window_cycle_token: usize = 0;
+    let mut window_cycle_token: usize = 0;
     let mut mappings = CompiledKeyMappings::new();
     let mut window_change_handlers = vec![];
     let stdout = Arc::new(tokio::sync::Mutex::new(vec![]));
+    let stderr = Arc::new(tokio::sync::Mutex::new(vec![]));
 
     let (execution_message_tx, mut execution_message_rx) = mpsc::channel(128);
     let (ev_reader_tx, mut ev_reader_rx) = mpsc::channel(128);
+    let (script_change_tx, mut script_change_rx) = mpsc::channel(1);
+    let (control_tx, mut control_rx) = mpsc::channel(8);
     let (mut ev_writer_tx, ev_writer_rx) = mpsc::channel(128);
 
+    // events emitted by the script are collected from the output device
+    let script_ev_writer_tx = ev_writer_tx.clone();
+
     let (stop_tx, stop_rx) = futures_intrusive::channel::shared::unbuffered_channel();
     {
         let mut execution_message_tx = execution_message_tx.clone();
         let stdout = stdout.clone();
+        let stderr = stderr.clone();
         task::spawn(async move {
             loop {
                 tokio::select! {
-                        Some(ev) = ev_reader_rx.recv() => {
-                            event_handlers::handle_stdin_ev(&mut state, ev, &mut mappings,
-                                &mut ev_writer_tx, &mut execution_message_tx, window_cycle_token, &config).await.unwrap();
+                        Some((ev, device)) = ev_reader_rx.recv() => {
+                            event_handlers::handle_stdin_ev(&mut state, ev, device, &mut mappings,
+                                &mut ev_writer_tx, &mut execution_message_tx, window_cycle_token, config.verbosity).await.unwrap();
+
+                            event_handlers::apply_pending_script(&mut state, &mut mappings, &mut window_change_handlers,
+                                &mut window_cycle_token, &ev_writer_tx, &execution_message_tx).await;
+                        }
+                        Some(script_ast) = script_change_rx.recv() => {
+                            event_handlers::handle_script_change(script_ast, &mut state, &mut mappings, &mut window_change_handlers,
+                                &mut window_cycle_token, &ev_writer_tx, &execution_message_tx).await;
+                        }
+                        Some(request) = control_rx.recv() => {
+                            let ControlRequest { command, response_tx } = request;
+                            let response = event_handlers::handle_control_command(command, &config, &mut state, &mut mappings,
+                                &mut window_change_handlers, &mut window_cycle_token, &ev_writer_tx, &execution_message_tx).await;
+                            let _ = response_tx.send(response);
                         }
                         Some(msg) = execution_message_rx.recv() => {
                             // don't terminate during testing
                             if let ExecutionMessage::Exit(_) = msg{ return; }
 
-                            event_handlers::handle_execution_message(&mut *stdout.lock().await, window_cycle_token, msg, &mut state,
-                                &mut mappings, &mut window_change_handlers).await;
+                            event_handlers::handle_execution_message(&mut *stdout.lock().await, &mut *stderr.lock().await,
+                                window_cycle_token, msg, &mut state,
+                                &mut mappings, &mut window_change_handlers, &mut ev_writer_tx, &mut execution_message_tx).await;
                         }
                         Some(_) = stop_rx.receive() => {
                             return;
@@ -104,19 +166,53 @@ pub async fn test_script(
         });
     }
 
-    script::evaluate_script(script_ast, execution_message_tx, ev_reader_tx.clone(), 0).await;
+    script::evaluate_script(script_ast, execution_message_tx, script_ev_writer_tx, 0, 0).await?;
 
     let api = ScriptTestingAPI {
         ev_reader_tx,
+        script_change_tx,
+        control_tx,
         ev_writer_rx,
         stop_tx,
         stdout,
+        stderr,
         event_delay: None,
+        device: None,
     };
 
     Ok(api)
 }
 
+/// Evaluates the inputs one after the other in the same scope the way the REPL does, returns what the REPL would
+/// show for each of them: the value, an empty string if there is none or the error.
+#[allow(unused)]
+pub async fn eval_inputs(inputs: &[&str]) -> Vec<String> {
+    let (mut message_tx, _message_rx) = mpsc::channel(128);
+    let (ev_writer_tx, _ev_writer_rx) = mpsc::channel(128);
+    let modifier_state = KeyModifierState::new();
+    let mut amb = Ambient {
+        ev_writer_tx,
+        message_tx: Some(&mut message_tx),
+        window_cycle_token: 0,
+        in_window_change_handler: false,
+        script_generation: 0,
+        modifier_state: &modifier_state,
+        layer: None,
+        device: None,
+        event_device: None,
+        turn: None,
+    };
+
+    let mut repl = repl::Repl::new();
+    let mut results = vec![];
+    for input in inputs {
+        results.push(match repl.eval(input, &mut amb).await {
+            Ok(value) => value.map(|value| value.to_string()).unwrap_or_default(),
+            Err(err) => err.to_string(),
+        });
+    }
+    results
+}
 
 pub fn sleep(duration: u64) {
     std::thread::sleep(time::Duration::from_millis(duration));
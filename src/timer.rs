@@ -0,0 +1,99 @@
+use crate::*;
+
+/// Requests a timeout message once a timer is due.
+pub struct TimerTick {
+    pub id: usize,
+    pub duration: time::Duration,
+}
+
+struct Timer {
+    /// the time between two calls of a repeating timer
+    interval: Option<time::Duration>,
+    callback: Arc<(Block, GuardedVarMap)>,
+    /// the window cycle a timer that was set by a window change callback belongs to, the timer is dropped once the
+    /// active window changes
+    window_cycle_token: Option<usize>,
+}
+
+pub struct TimerState {
+    timers: HashMap<usize, Timer>,
+    next_id: usize,
+}
+
+impl TimerState {
+    pub fn new() -> Self {
+        TimerState { timers: HashMap::new(), next_id: 1 }
+    }
+
+    /// Adds a timer and returns its handle, repeating timers get called every `duration` until they're cleared or,
+    /// if a window cycle is given, until the window cycle is over.
+    pub fn insert(&mut self, duration: time::Duration, repeat: bool, window_cycle_token: Option<usize>, block: Block,
+                  var_map: GuardedVarMap) -> TimerTick {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let interval = if repeat { Some(duration) } else { None };
+        self.timers.insert(id, Timer { interval, callback: Arc::new((block, var_map)), window_cycle_token });
+        TimerTick { id, duration }
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        self.timers.remove(&id);
+    }
+
+    /// Drops all timers, handles are never reused so the ticks of dropped timers that are still pending get ignored.
+    pub fn clear(&mut self) {
+        self.timers.clear();
+    }
+
+    /// Returns the callback of a timer that is due, along with the next tick if it repeats. Timers of a window cycle
+    /// other than the current one are dropped instead.
+    pub fn timeout(&mut self, id: usize, window_cycle_token: usize) -> (Option<Arc<(Block, GuardedVarMap)>>, Option<TimerTick>) {
+        let timer = match self.timers.get(&id) {
+            Some(timer) => timer,
+            None => return (None, None),
+        };
+        if timer.window_cycle_token.is_some_and(|token| token != window_cycle_token) {
+            self.timers.remove(&id);
+            return (None, None);
+        }
+
+        let callback = timer.callback.clone();
+        match timer.interval {
+            Some(duration) => (Some(callback), Some(TimerTick { id, duration })),
+            None => {
+                self.timers.remove(&id);
+                (Some(callback), None)
+            }
+        }
+    }
+}
+
+impl Default for TimerState {
+    fn default() -> Self { Self::new() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(timers: &mut TimerState, window_cycle_token: Option<usize>) -> TimerTick {
+        let var_map = GuardedVarMap::new(Mutex::new(VarMap::new(None)));
+        timers.insert(time::Duration::from_millis(10), true, window_cycle_token, Block::new(), var_map)
+    }
+
+    #[test]
+    fn test_window_cycle_timers() {
+        let mut timers = TimerState::new();
+        let script_timer = insert(&mut timers, None);
+        let window_timer = insert(&mut timers, Some(1));
+
+        assert!(timers.timeout(window_timer.id, 1).0.is_some());
+
+        // the active window changed
+        assert!(timers.timeout(script_timer.id, 2).0.is_some());
+        assert!(matches!(timers.timeout(window_timer.id, 2), (None, None)));
+        assert!(matches!(timers.timeout(window_timer.id, 1), (None, None)));
+    }
+}